@@ -0,0 +1,13 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use my_tiny_jpeg_decoder::{decode_coefficients_with_header, try_read_header};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = BufReader::new(Cursor::new(data));
+    if let Ok(header) = try_read_header(&mut reader) {
+        let _ = decode_coefficients_with_header(&mut reader, header);
+    }
+});
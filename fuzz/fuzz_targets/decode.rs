@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use my_tiny_jpeg_decoder::{decode::OutputFormat, decode_jpeg_bytes};
+
+// 任意输入都只能返回错误，不能 panic
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_jpeg_bytes(data, OutputFormat::Rgba8);
+    let _ = decode_jpeg_bytes(data, OutputFormat::YCbCr);
+});
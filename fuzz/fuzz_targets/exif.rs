@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use my_tiny_jpeg_decoder::application::exif::Exif;
+
+// 输入是 "Exif\0\0" 之后的 TIFF 数据
+fuzz_target!(|data: &[u8]| {
+    let mut app1 = b"Exif\0\0".to_vec();
+    app1.extend_from_slice(data);
+    if let Some(exif) = Exif::new(&app1) {
+        let _ = exif.get_make();
+        let _ = exif.get_model();
+        let _ = exif.get_date_time();
+        let _ = exif.get_exposure_time();
+        let _ = exif.get_f_number();
+        let _ = exif.get_iso();
+        let _ = exif.get_focal_length();
+        let _ = exif.get_gps();
+        let _ = exif.get_orientation();
+        let _ = exif.thumbnail();
+    }
+    let _ = Exif::set_orientation(&app1, 1);
+    if let Some(stripped) = Exif::strip_gps(&app1) {
+        let _ = Exif::new(&stripped).map(|exif| exif.get_gps());
+    }
+});
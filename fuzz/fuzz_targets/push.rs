@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use my_tiny_jpeg_decoder::{
+    decode::{push::PushDecoder, OutputFormat},
+    limits::Limits,
+};
+
+// 第一个字节决定每次送入的数据长度，覆盖在任意位置断开的情况
+fuzz_target!(|data: &[u8]| {
+    let Some((&chunk, data)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = PushDecoder::new(OutputFormat::Rgba8, Limits::default()).unwrap();
+    for part in data.chunks(chunk as usize + 1) {
+        if decoder.feed(part).is_err() {
+            return;
+        }
+    }
+    let _ = decoder.finish();
+});
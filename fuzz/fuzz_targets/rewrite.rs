@@ -0,0 +1,20 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use my_tiny_jpeg_decoder::segment::{
+    rewrite::{rewrite, SegmentEdit},
+    Segment, SegmentType,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let edits = [
+        SegmentEdit::StripGps,
+        SegmentEdit::Strip(SegmentType::APPn(0)),
+        SegmentEdit::StripIdentified(SegmentType::APPn(2), b"ICC_PROFILE".to_vec()),
+        SegmentEdit::Replace(Segment::with_data(SegmentType::COM, b"fuzz".to_vec()).unwrap()),
+    ];
+    let mut reader = BufReader::new(Cursor::new(data));
+    let _ = rewrite(&mut reader, &mut Vec::new(), &edits);
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use my_tiny_jpeg_decoder::{
+    decode::{scanline::ScanlineDecoder, OutputFormat},
+    try_read_header,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = BufReader::new(Cursor::new(data));
+    let Ok(header) = try_read_header(&mut reader) else {
+        return;
+    };
+    let Ok(mut decoder) = ScanlineDecoder::new(&mut reader, header, OutputFormat::Rgb8) else {
+        return;
+    };
+    // 每次读 3 行，不是 MCU 高度的整数倍
+    let mut buf = vec![0; decoder.bytes_per_line() * 3];
+    while let Ok(n) = decoder.read_scanlines(&mut buf) {
+        if n == 0 {
+            break;
+        }
+    }
+});
@@ -0,0 +1,537 @@
+use crate::segment::{Segment, SegmentType};
+
+// TIFF 数据类型
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_DATE_TIME: u16 = 0x0132;
+// 指向 Exif 子 IFD 和 GPS IFD 的偏移
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+// Exif 子 IFD
+const TAG_EXPOSURE_TIME: u16 = 0x829A;
+const TAG_F_NUMBER: u16 = 0x829D;
+const TAG_ISO: u16 = 0x8827;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_FOCAL_LENGTH: u16 = 0x920A;
+// GPS IFD
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+// IFD1 中 JPEG 缩略图的偏移和长度
+const TAG_THUMBNAIL_OFFSET: u16 = 0x0201;
+const TAG_THUMBNAIL_LENGTH: u16 = 0x0202;
+
+// 每种数据类型一个值的字节数
+fn type_size(format: u16) -> Option<usize> {
+    match format {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IfdEntry {
+    tag: u16,
+    format: u16,
+    count: u32,
+    // 值不超过 4 字节时直接存放在这里，否则是相对 TIFF 头的偏移
+    value: [u8; 4],
+}
+
+#[derive(Debug)]
+pub struct Exif {
+    little_endian: bool,
+    // "Exif\0\0" 之后的 TIFF 数据
+    tiff: Vec<u8>,
+    ifd0: Vec<IfdEntry>,
+    // 缩略图的 IFD，没有时为空
+    ifd1: Vec<IfdEntry>,
+    // 拍摄参数和 GPS 信息所在的 IFD，没有时为空
+    exif_ifd: Vec<IfdEntry>,
+    gps_ifd: Vec<IfdEntry>,
+}
+
+impl Exif {
+    // 格式不正确时返回 None
+    pub fn new(data: &[u8]) -> Option<Self> {
+        if data.len() < 14 || &data[..6] != b"Exif\0\0" {
+            return None;
+        }
+        let tiff = data[6..].to_vec();
+        let little_endian = match &tiff[..2] {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let mut exif = Self {
+            little_endian,
+            tiff,
+            ifd0: Vec::new(),
+            ifd1: Vec::new(),
+            exif_ifd: Vec::new(),
+            gps_ifd: Vec::new(),
+        };
+        if exif.read_u16(2)? != 42 {
+            return None;
+        }
+        let next;
+        (exif.ifd0, next) = exif.read_ifd(exif.read_u32(4)?)?;
+        if next != 0 {
+            if let Some((ifd1, _)) = exif.read_ifd(next) {
+                exif.ifd1 = ifd1;
+            }
+        }
+        // 子 IFD 损坏时仍然可以读取 IFD0 中的信息
+        let sub_ifd = |tag| {
+            let entry = exif.ifd0.iter().find(|e| e.tag == tag)?;
+            exif.read_ifd(exif.entry_u32(entry)?)
+                .map(|(entries, _)| entries)
+        };
+        let exif_ifd = sub_ifd(TAG_EXIF_IFD).unwrap_or_default();
+        let gps_ifd = sub_ifd(TAG_GPS_IFD).unwrap_or_default();
+        exif.exif_ifd = exif_ifd;
+        exif.gps_ifd = gps_ifd;
+        Some(exif)
+    }
+
+    fn read_u16(&self, offset: usize) -> Option<u16> {
+        let bytes = [*self.tiff.get(offset)?, *self.tiff.get(offset + 1)?];
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn read_u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    // 返回 IFD 的所有条目和下一个 IFD 的偏移
+    fn read_ifd(&self, offset: u32) -> Option<(Vec<IfdEntry>, u32)> {
+        let offset = offset as usize;
+        let count = self.read_u16(offset)? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let pos = offset + 2 + i * 12;
+            entries.push(IfdEntry {
+                tag: self.read_u16(pos)?,
+                format: self.read_u16(pos + 2)?,
+                count: self.read_u32(pos + 4)?,
+                value: self.tiff.get(pos + 8..pos + 12)?.try_into().ok()?,
+            });
+        }
+        let next = self.read_u32(offset + 2 + count * 12).unwrap_or(0);
+        Some((entries, next))
+    }
+
+    fn entry_u16(&self, entry: &IfdEntry) -> Option<u16> {
+        if entry.format != TYPE_SHORT || entry.count == 0 {
+            return None;
+        }
+        let bytes = [entry.value[0], entry.value[1]];
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn entry_u32(&self, entry: &IfdEntry) -> Option<u32> {
+        match entry.format {
+            TYPE_SHORT => self.entry_u16(entry).map(|v| v as u32),
+            TYPE_LONG if entry.count > 0 => Some(if self.little_endian {
+                u32::from_le_bytes(entry.value)
+            } else {
+                u32::from_be_bytes(entry.value)
+            }),
+            _ => None,
+        }
+    }
+
+    // 超过 4 字节的值存放在 TIFF 数据中的其他位置，value 是它的偏移
+    fn entry_offset(&self, entry: &IfdEntry) -> usize {
+        (if self.little_endian {
+            u32::from_le_bytes(entry.value)
+        } else {
+            u32::from_be_bytes(entry.value)
+        }) as usize
+    }
+
+    fn entry_data<'a>(&'a self, entry: &'a IfdEntry, size: usize) -> Option<&'a [u8]> {
+        let len = size.checked_mul(entry.count as usize)?;
+        if len <= 4 {
+            return Some(&entry.value[..len]);
+        }
+        let offset = self.entry_offset(entry);
+        self.tiff.get(offset..offset.checked_add(len)?)
+    }
+
+    // 去掉末尾的 0 和空白
+    fn entry_string(&self, entry: &IfdEntry) -> Option<String> {
+        if entry.format != TYPE_ASCII {
+            return None;
+        }
+        let data = self.entry_data(entry, 1)?;
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        let text = String::from_utf8_lossy(&data[..end]).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    // 分子和分母
+    fn entry_rationals(&self, entry: &IfdEntry) -> Option<Vec<(u32, u32)>> {
+        if entry.format != TYPE_RATIONAL {
+            return None;
+        }
+        let data = self.entry_data(entry, 8)?;
+        let read = |b: &[u8]| {
+            let b: [u8; 4] = b.try_into().unwrap();
+            if self.little_endian {
+                u32::from_le_bytes(b)
+            } else {
+                u32::from_be_bytes(b)
+            }
+        };
+        Some(
+            data.chunks_exact(8)
+                .map(|r| (read(&r[..4]), read(&r[4..])))
+                .collect(),
+        )
+    }
+
+    fn find(ifd: &[IfdEntry], tag: u16) -> Option<&IfdEntry> {
+        ifd.iter().find(|e| e.tag == tag)
+    }
+
+    fn rational(&self, ifd: &[IfdEntry], tag: u16) -> Option<f64> {
+        let (n, d) = *self.entry_rationals(Self::find(ifd, tag)?)?.first()?;
+        (d != 0).then(|| n as f64 / d as f64)
+    }
+
+    pub fn get_make(&self) -> Option<String> {
+        self.entry_string(Self::find(&self.ifd0, TAG_MAKE)?)
+    }
+
+    pub fn get_model(&self) -> Option<String> {
+        self.entry_string(Self::find(&self.ifd0, TAG_MODEL)?)
+    }
+
+    // 拍摄时间，没有时使用 IFD0 中的修改时间，格式为 "YYYY:MM:DD HH:MM:SS"
+    pub fn get_date_time(&self) -> Option<String> {
+        Self::find(&self.exif_ifd, TAG_DATE_TIME_ORIGINAL)
+            .or_else(|| Self::find(&self.ifd0, TAG_DATE_TIME))
+            .and_then(|entry| self.entry_string(entry))
+    }
+
+    // 曝光时间（秒），保留分数形式以便显示为 1/125
+    pub fn get_exposure_time(&self) -> Option<(u32, u32)> {
+        let entry = Self::find(&self.exif_ifd, TAG_EXPOSURE_TIME)?;
+        self.entry_rationals(entry)?
+            .first()
+            .copied()
+            .filter(|&(_, d)| d != 0)
+    }
+
+    pub fn get_f_number(&self) -> Option<f64> {
+        self.rational(&self.exif_ifd, TAG_F_NUMBER)
+    }
+
+    pub fn get_iso(&self) -> Option<u16> {
+        self.entry_u16(Self::find(&self.exif_ifd, TAG_ISO)?)
+    }
+
+    // 焦距（毫米）
+    pub fn get_focal_length(&self) -> Option<f64> {
+        self.rational(&self.exif_ifd, TAG_FOCAL_LENGTH)
+    }
+
+    // 纬度和经度（度），南纬和西经为负数
+    pub fn get_gps(&self) -> Option<(f64, f64)> {
+        let degrees = |tag, ref_tag, negative: &str| {
+            let dms = self.entry_rationals(Self::find(&self.gps_ifd, tag)?)?;
+            if dms.len() != 3 || dms.iter().any(|&(_, d)| d == 0) {
+                return None;
+            }
+            let [d, m, s] = [0, 1, 2].map(|i| dms[i].0 as f64 / dms[i].1 as f64);
+            let value = d + m / 60.0 + s / 3600.0;
+            let reference = self.entry_string(Self::find(&self.gps_ifd, ref_tag)?)?;
+            Some(if reference == negative { -value } else { value })
+        };
+        Some((
+            degrees(TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S")?,
+            degrees(TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, "W")?,
+        ))
+    }
+
+    // 1: 正常，2: 水平翻转，3: 旋转 180°，4: 垂直翻转，
+    // 5: 转置，6: 顺时针旋转 90°，7: 反转置，8: 逆时针旋转 90°
+    pub fn get_orientation(&self) -> Option<u16> {
+        let entry = self.ifd0.iter().find(|e| e.tag == TAG_ORIENTATION)?;
+        self.entry_u16(entry).filter(|o| (1..=8).contains(o))
+    }
+
+    // 修改 IFD0 中方向标签的值，返回新的 APP1 段数据，没有方向标签时返回 None
+    pub fn set_orientation(data: &[u8], orientation: u16) -> Option<Vec<u8>> {
+        let exif = Self::new(data)?;
+        let ifd0 = exif.read_u32(4)? as usize;
+        let count = exif.read_u16(ifd0)? as usize;
+        let pos = (0..count)
+            .map(|i| ifd0 + 2 + i * 12)
+            .find(|&pos| exif.read_u16(pos) == Some(TAG_ORIENTATION))?;
+        if exif.read_u16(pos + 2)? != TYPE_SHORT {
+            return None;
+        }
+        let value = if exif.little_endian {
+            orientation.to_le_bytes()
+        } else {
+            orientation.to_be_bytes()
+        };
+        // tiff 之前是 6 字节的 "Exif\0\0"
+        let mut data = data.to_vec();
+        data.get_mut(6 + pos + 8..6 + pos + 10)?
+            .copy_from_slice(&value);
+        Some(data)
+    }
+
+    // 只有一个方向标签的 APP1 段数据，用于没有 Exif 的文件
+    pub fn orientation_only(orientation: u16) -> Vec<u8> {
+        let mut data = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&TAG_ORIENTATION.to_be_bytes());
+        data.extend_from_slice(&TYPE_SHORT.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 1]);
+        data.extend_from_slice(&orientation.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        data
+    }
+
+    // 从 IFD0 中删除 GPS IFD 的指针并把 GPS IFD 及其数据清零，其他标签的偏移不变
+    // 返回新的 APP1 段数据，没有 GPS 信息时返回 None
+    pub fn strip_gps(data: &[u8]) -> Option<Vec<u8>> {
+        let exif = Self::new(data)?;
+        let ifd0 = exif.read_u32(4)? as usize;
+        let count = exif.read_u16(ifd0)? as usize;
+        let index = exif.ifd0.iter().position(|e| e.tag == TAG_GPS_IFD)?;
+        let gps = exif.entry_u32(&exif.ifd0[index])? as usize;
+
+        // tiff 之前是 6 字节的 "Exif\0\0"
+        let mut data = data.to_vec();
+        let tiff = &mut data[6..];
+        if let Some((entries, _)) = exif.read_ifd(gps as u32) {
+            for entry in &entries {
+                let Some(size) = type_size(entry.format) else {
+                    continue;
+                };
+                let len = size.saturating_mul(entry.count as usize);
+                if len > 4 {
+                    let offset = exif.entry_offset(entry);
+                    if let Some(value) = tiff.get_mut(offset..offset.saturating_add(len)) {
+                        value.fill(0);
+                    }
+                }
+            }
+            let end = gps + 2 + entries.len() * 12 + 4;
+            if let Some(ifd) = tiff.get_mut(gps..end) {
+                ifd.fill(0);
+            }
+        }
+
+        // 后面的条目和下一个 IFD 的偏移前移 12 字节，空出的位置清零
+        let pos = ifd0 + 2 + index * 12;
+        let end = ifd0 + 2 + count * 12 + 4;
+        tiff.get(pos..end)?;
+        tiff.copy_within(pos + 12..end, pos);
+        tiff[end - 12..end].fill(0);
+        let count = (count - 1) as u16;
+        let count = if exif.little_endian {
+            count.to_le_bytes()
+        } else {
+            count.to_be_bytes()
+        };
+        tiff[ifd0..ifd0 + 2].copy_from_slice(&count);
+        Some(data)
+    }
+
+    // 像素已经按方向转正后，把元数据中 Exif 段的方向标签改为 1，没有方向标签的段保持不变
+    pub fn reset_orientation(metadata: &mut [Segment]) {
+        for seg in metadata {
+            if matches!(seg.segment_type, SegmentType::APPn(1)) && seg.identifier() == b"Exif" {
+                if let Some(data) = Self::set_orientation(&seg.data, 1) {
+                    seg.data = data;
+                }
+            }
+        }
+    }
+
+    // IFD1 中嵌入的 JPEG 缩略图
+    pub fn thumbnail(&self) -> Option<&[u8]> {
+        let find = |tag| self.ifd1.iter().find(|e| e.tag == tag);
+        let offset = self.entry_u32(find(TAG_THUMBNAIL_OFFSET)?)? as usize;
+        let length = self.entry_u32(find(TAG_THUMBNAIL_LENGTH)?)? as usize;
+        let data = self.tiff.get(offset..offset.checked_add(length)?)?;
+        data.starts_with(&[0xFF, 0xD8]).then_some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orientation() {
+        // 大端序，IFD0 只有一个方向标签
+        let mut data = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        let exif = Exif::new(&data).unwrap();
+        assert_eq!(exif.get_orientation(), Some(6));
+
+        // 小端序
+        let mut data = b"Exif\0\0II\x2a\0\x08\0\0\0".to_vec();
+        data.extend_from_slice(&[1, 0]);
+        data.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 3, 0, 0, 0]);
+        let exif = Exif::new(&data).unwrap();
+        assert_eq!(exif.get_orientation(), Some(3));
+
+        assert!(Exif::new(b"Exif\0\0XX\0\x2a\0\0\0\x08").is_none());
+    }
+
+    #[test]
+    fn test_set_orientation() {
+        let data = Exif::orientation_only(6);
+        assert_eq!(Exif::new(&data).unwrap().get_orientation(), Some(6));
+        let data = Exif::set_orientation(&data, 3).unwrap();
+        assert_eq!(Exif::new(&data).unwrap().get_orientation(), Some(3));
+
+        // 小端序
+        let mut data = b"Exif\0\0II\x2a\0\x08\0\0\0".to_vec();
+        data.extend_from_slice(&[1, 0]);
+        data.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 3, 0, 0, 0]);
+        let data = Exif::set_orientation(&data, 8).unwrap();
+        assert_eq!(Exif::new(&data).unwrap().get_orientation(), Some(8));
+
+        // 没有方向标签
+        let mut data = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        assert!(Exif::set_orientation(&data, 1).is_none());
+
+        // 方向标签的值被截断
+        let data = Exif::orientation_only(6);
+        assert!(Exif::set_orientation(&data[..6 + 18], 1).is_none());
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        // IFD0 没有条目，IFD1 在偏移 14，缩略图数据在偏移 44
+        let mut data = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 14]);
+        data.extend_from_slice(&[0, 2]);
+        data.extend_from_slice(&[0x02, 0x01, 0, 4, 0, 0, 0, 1, 0, 0, 0, 44]);
+        data.extend_from_slice(&[0x02, 0x02, 0, 4, 0, 0, 0, 1, 0, 0, 0, 4]);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&[0xFF, 0xD8, 0xFF, 0xD9]);
+        let exif = Exif::new(&data).unwrap();
+        assert_eq!(exif.thumbnail(), Some(&[0xFF, 0xD8, 0xFF, 0xD9][..]));
+        assert_eq!(exif.get_orientation(), None);
+    }
+
+    fn camera_and_gps() -> Vec<u8> {
+        let entry = |tag: u16, format: u16, count: u32, value: [u8; 4]| {
+            let mut e = tag.to_be_bytes().to_vec();
+            e.extend_from_slice(&format.to_be_bytes());
+            e.extend_from_slice(&count.to_be_bytes());
+            e.extend_from_slice(&value);
+            e
+        };
+        let rationals = |values: &[(u32, u32)]| {
+            values
+                .iter()
+                .flat_map(|(n, d)| [n.to_be_bytes(), d.to_be_bytes()].concat())
+                .collect::<Vec<u8>>()
+        };
+        // IFD0 在偏移 8，Exif 子 IFD 在 50，GPS IFD 在 88
+        let mut data = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        data.extend_from_slice(&[0, 3]);
+        data.extend(entry(TAG_MAKE, TYPE_ASCII, 4, *b"Foo\0"));
+        data.extend(entry(TAG_EXIF_IFD, TYPE_LONG, 1, 50u32.to_be_bytes()));
+        data.extend(entry(TAG_GPS_IFD, TYPE_LONG, 1, 88u32.to_be_bytes()));
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&[0, 2]);
+        data.extend(entry(
+            TAG_EXPOSURE_TIME,
+            TYPE_RATIONAL,
+            1,
+            80u32.to_be_bytes(),
+        ));
+        data.extend(entry(TAG_ISO, TYPE_SHORT, 1, [0, 200, 0, 0]));
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend(rationals(&[(1, 125)]));
+        data.extend_from_slice(&[0, 4]);
+        data.extend(entry(TAG_GPS_LATITUDE_REF, TYPE_ASCII, 2, *b"N\0\0\0"));
+        data.extend(entry(
+            TAG_GPS_LATITUDE,
+            TYPE_RATIONAL,
+            3,
+            142u32.to_be_bytes(),
+        ));
+        data.extend(entry(TAG_GPS_LONGITUDE_REF, TYPE_ASCII, 2, *b"W\0\0\0"));
+        data.extend(entry(
+            TAG_GPS_LONGITUDE,
+            TYPE_RATIONAL,
+            3,
+            166u32.to_be_bytes(),
+        ));
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend(rationals(&[(10, 1), (30, 1), (0, 1)]));
+        data.extend(rationals(&[(20, 1), (15, 1), (36, 1)]));
+        data
+    }
+
+    #[test]
+    fn test_camera_and_gps() {
+        let data = camera_and_gps();
+        let exif = Exif::new(&data).unwrap();
+        assert_eq!(exif.get_make().as_deref(), Some("Foo"));
+        assert_eq!(exif.get_model(), None);
+        assert_eq!(exif.get_exposure_time(), Some((1, 125)));
+        assert_eq!(exif.get_iso(), Some(200));
+        assert_eq!(exif.get_f_number(), None);
+        let (lat, lon) = exif.get_gps().unwrap();
+        assert!((lat - 10.5).abs() < 1e-9);
+        assert!((lon + 20.26).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_strip_gps() {
+        let data = camera_and_gps();
+        let stripped = Exif::strip_gps(&data).unwrap();
+        assert_eq!(stripped.len(), data.len());
+        let exif = Exif::new(&stripped).unwrap();
+        assert_eq!(exif.get_gps(), None);
+        assert_eq!(exif.get_make().as_deref(), Some("Foo"));
+        assert_eq!(exif.get_exposure_time(), Some((1, 125)));
+        assert_eq!(exif.get_iso(), Some(200));
+        // GPS IFD 和坐标数据都被清零
+        assert!(stripped[6 + 88..].iter().all(|&b| b == 0));
+        assert_eq!(stripped[6 + 8..6 + 10], [0, 2]);
+
+        assert!(Exif::strip_gps(&stripped).is_none());
+        assert!(Exif::strip_gps(&Exif::orientation_only(1)).is_none());
+    }
+}
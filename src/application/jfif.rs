@@ -34,4 +34,26 @@ impl JFIF {
             nail_data: nail_data,
         }
     }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    // 0: 无单位（只表示宽高比），1: 每英寸点数，2: 每厘米点数
+    pub fn units(&self) -> u8 {
+        self.units
+    }
+
+    pub fn density(&self) -> (u16, u16) {
+        (self.x_density, self.y_density)
+    }
+
+    pub fn thumbnail_size(&self) -> (u8, u8) {
+        (self.x_thumbnail, self.y_thumbnail)
+    }
+
+    // 未压缩的 RGB 缩略图数据
+    pub fn thumbnail(&self) -> &[u8] {
+        &self.nail_data
+    }
 }
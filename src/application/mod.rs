@@ -1,4 +1,6 @@
-mod jfif;
+pub mod exif;
+pub mod jfif;
+use exif::Exif;
 use jfif::JFIF;
 
 use crate::segment::Segment;
@@ -6,7 +8,7 @@ use crate::segment::Segment;
 #[derive(Debug)]
 pub enum InterchangeFormat {
     JFIF(JFIF),
-    EXIF,
+    EXIF(Exif),
     Unknown,
 }
 
@@ -17,9 +19,18 @@ pub enum IfErrorType {
 impl InterchangeFormat {
     pub fn new(n: u8, seg: &Segment) -> Self {
         if n == 0 {
-            InterchangeFormat::JFIF(JFIF::new(&seg.data))
+            // APP0 也可能是 JFXX 扩展段
+            if seg.identifier() == b"JFIF" && seg.data.len() >= 14 {
+                InterchangeFormat::JFIF(JFIF::new(&seg.data))
+            } else {
+                InterchangeFormat::Unknown
+            }
         } else if n == 1 {
-            InterchangeFormat::EXIF
+            // APP1 也可能是 XMP
+            match Exif::new(&seg.data) {
+                Some(exif) => InterchangeFormat::EXIF(exif),
+                None => InterchangeFormat::Unknown,
+            }
         } else {
             println!("无效的交换格式！");
             InterchangeFormat::Unknown
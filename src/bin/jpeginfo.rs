@@ -0,0 +1,494 @@
+use std::{env, fmt::Write, fs::File, io::BufReader, process, rc::Rc};
+
+use my_tiny_jpeg_decoder::{
+    application::InterchangeFormat,
+    component::{frame::Frame, scan::Scan},
+    dht::{HuffmanTable, HuffmanTableType},
+    dqt::Dqt,
+    segment::{Segment, SegmentType},
+};
+use rustc_hash::FxHashMap;
+
+const USAGE: &str = "用法: jpeginfo [--json] <文件>...";
+
+enum Value {
+    Num(i64),
+    Str(String),
+    List(Vec<Value>),
+    Object(Vec<(&'static str, Value)>),
+}
+
+fn num(n: impl Into<i64>) -> Value {
+    Value::Num(n.into())
+}
+
+fn string(s: impl Into<String>) -> Value {
+    Value::Str(s.into())
+}
+
+struct SegmentInfo {
+    offset: u64,
+    marker: u8,
+    name: String,
+    length: u16,
+    fields: Vec<(&'static str, Value)>,
+    // 文本模式下代替 fields 输出的内容，如 Dqt 的 Display
+    text: Option<String>,
+}
+
+fn segment_name(seg_type: SegmentType) -> String {
+    match seg_type {
+        SegmentType::SOI => "SOI".to_string(),
+        SegmentType::APPn(n) => format!("APP{}", n),
+        SegmentType::DQT => "DQT".to_string(),
+        SegmentType::SOFn(n) => format!("SOF{}", n),
+        SegmentType::DHT => "DHT".to_string(),
+        SegmentType::DRI => "DRI".to_string(),
+        SegmentType::SOS(_, _) => "SOS".to_string(),
+        SegmentType::COM => "COM".to_string(),
+        SegmentType::EOI => "EOI".to_string(),
+    }
+}
+
+fn app_fields(n: u8, seg: &Segment) -> Vec<(&'static str, Value)> {
+    let identifier = String::from_utf8_lossy(seg.identifier()).into_owned();
+    let mut fields = vec![("identifier", string(identifier))];
+    if n == 0 && seg.identifier() == b"JFIF" && seg.data.len() >= 14 {
+        if let InterchangeFormat::JFIF(jfif) = InterchangeFormat::new(0, seg) {
+            let (x_density, y_density) = jfif.density();
+            let (x_thumbnail, y_thumbnail) = jfif.thumbnail_size();
+            let units = match jfif.units() {
+                0 => "none",
+                1 => "dpi",
+                2 => "dpcm",
+                _ => "unknown",
+            };
+            fields.push(("version", string(jfif.version())));
+            fields.push(("units", string(units)));
+            fields.push(("density", Value::List(vec![num(x_density), num(y_density)])));
+            fields.push((
+                "thumbnail",
+                Value::List(vec![num(x_thumbnail), num(y_thumbnail)]),
+            ));
+        }
+    }
+    if n == 1 {
+        if let InterchangeFormat::EXIF(exif) = InterchangeFormat::new(1, seg) {
+            if let Some(orientation) = exif.get_orientation() {
+                fields.push(("orientation", num(orientation)));
+            }
+        }
+    }
+    fields
+}
+
+fn dqt_info(seg: &Segment) -> (Vec<(&'static str, Value)>, Option<String>) {
+    let mut map: FxHashMap<u8, Rc<Dqt>> = FxHashMap::default();
+    if let Err(e) = Dqt::new(&mut map, seg.length, seg.data.clone()) {
+        return (vec![("error", string(format!("{:?}", e)))], None);
+    }
+    let mut tables: Vec<&Rc<Dqt>> = map.values().collect();
+    tables.sort_by_key(|dqt| dqt.id());
+
+    let mut text = String::new();
+    let list = tables
+        .iter()
+        .map(|dqt| {
+            writeln!(text, "{}", dqt).unwrap();
+            Value::Object(vec![
+                ("id", num(dqt.id())),
+                ("precision", num(if dqt.precision() == 0 { 8 } else { 16 })),
+                (
+                    "table",
+                    Value::List(dqt.natural_table().iter().map(|&q| num(q)).collect()),
+                ),
+            ])
+        })
+        .collect();
+    (vec![("tables", Value::List(list))], Some(text))
+}
+
+fn dht_fields(seg: &Segment) -> Vec<(&'static str, Value)> {
+    let mut dc_map = FxHashMap::default();
+    let mut ac_map = FxHashMap::default();
+    if let Err(e) = HuffmanTable::new(&mut dc_map, &mut ac_map, seg.length, seg.data.clone()) {
+        return vec![("error", string(format!("{:?}", e)))];
+    }
+    let mut tables: Vec<&Rc<HuffmanTable>> = dc_map.values().chain(ac_map.values()).collect();
+    tables.sort_by_key(|t| (matches!(t.get_type(), HuffmanTableType::AC), t.id()));
+
+    let list = tables
+        .iter()
+        .map(|t| {
+            let class = match t.get_type() {
+                HuffmanTableType::DC => "DC",
+                HuffmanTableType::AC => "AC",
+            };
+            let lengths = t.huff.lengths();
+            Value::Object(vec![
+                ("class", string(class)),
+                ("id", num(t.id())),
+                (
+                    "symbols",
+                    num(lengths.iter().map(|&n| n as i64).sum::<i64>()),
+                ),
+                (
+                    "counts",
+                    Value::List(lengths.iter().map(|&n| num(n)).collect()),
+                ),
+            ])
+        })
+        .collect();
+    vec![("tables", Value::List(list))]
+}
+
+fn frame_fields(n: u8, seg: &Segment) -> Vec<(&'static str, Value)> {
+    let frame = match Frame::new(n, seg.data.clone()) {
+        Ok(frame) => frame,
+        Err(e) => return vec![("error", string(format!("{:?}", e)))],
+    };
+    let mut comps: Vec<_> = frame.components.values().collect();
+    comps.sort_by_key(|c| c.get_id());
+    vec![
+        ("type", string(frame.get_type().to_string())),
+        ("precision", num(frame.get_precision())),
+        ("width", num(frame.get_width())),
+        ("height", num(frame.get_height())),
+        (
+            "components",
+            Value::List(
+                comps
+                    .iter()
+                    .map(|c| {
+                        Value::Object(vec![
+                            ("id", num(c.get_id())),
+                            ("h", num(c.get_factor_x())),
+                            ("v", num(c.get_factor_y())),
+                            ("tq", num(c.get_qid())),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ]
+}
+
+fn scan_fields(seg: &Segment, start: u64, end: u64) -> Vec<(&'static str, Value)> {
+    let data = &seg.data;
+    let scan = match Scan::new(data.clone()) {
+        Ok(scan) => scan,
+        Err(e) => return vec![("error", string(format!("{:?}", e)))],
+    };
+    let mut comps: Vec<_> = scan.components.values().collect();
+    comps.sort_by_key(|c| c.get_id());
+
+    // 分量之后是频谱选择和逐次逼近参数
+    let tail = 1 + data[0] as usize * 2;
+    let mut fields = vec![(
+        "components",
+        Value::List(
+            comps
+                .iter()
+                .map(|c| {
+                    Value::Object(vec![
+                        ("id", num(c.get_id())),
+                        ("dc", num(c.get_dc_id())),
+                        ("ac", num(c.get_ac_id())),
+                    ])
+                })
+                .collect(),
+        ),
+    )];
+    if let Some(p) = data.get(tail..tail + 3) {
+        fields.push(("ss", num(p[0])));
+        fields.push(("se", num(p[1])));
+        fields.push(("ah", num(p[2] >> 4)));
+        fields.push(("al", num(p[2] & 0x0F)));
+    }
+    fields.push(("data_offset", num(start as i64)));
+    fields.push(("data_length", num((end - start) as i64)));
+    fields
+}
+
+fn segment_info(seg: &Segment) -> SegmentInfo {
+    let mut text = None;
+    let fields = match seg.segment_type {
+        SegmentType::SOI | SegmentType::EOI => vec![],
+        SegmentType::APPn(n) => app_fields(n, seg),
+        SegmentType::DQT => {
+            let (fields, t) = dqt_info(seg);
+            text = t;
+            fields
+        }
+        SegmentType::SOFn(n) => frame_fields(n, seg),
+        SegmentType::DHT => dht_fields(seg),
+        SegmentType::DRI => match seg.data.get(..2) {
+            Some(d) => vec![("interval", num(u16::from_be_bytes([d[0], d[1]])))],
+            None => vec![("error", string("segment too short"))],
+        },
+        SegmentType::SOS(start, end) => scan_fields(seg, start, end),
+        SegmentType::COM => vec![(
+            "text",
+            string(String::from_utf8_lossy(&seg.data).into_owned()),
+        )],
+    };
+    SegmentInfo {
+        offset: seg.offset,
+        marker: seg.segment_type.marker(),
+        name: segment_name(seg.segment_type),
+        length: seg.length,
+        fields,
+        text,
+    }
+}
+
+// 文本模式下的值：列表写在一行内，对象写成 key=value
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::Num(n) => n.to_string(),
+        Value::Str(s) => s.clone(),
+        Value::List(list) => {
+            let items: Vec<String> = list.iter().map(text_value).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(fields) => {
+            let items: Vec<String> = fields
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, text_value(v)))
+                .collect();
+            items.join(" ")
+        }
+    }
+}
+
+fn print_text(path: &str, infos: &[SegmentInfo]) {
+    println!("{}:", path);
+    for info in infos {
+        print!(
+            "0x{:08X}  FF{:02X}  {}",
+            info.offset, info.marker, info.name
+        );
+        if info.length > 0 {
+            print!(
+                "{}length {}",
+                " ".repeat(8 - info.name.len().min(7)),
+                info.length
+            );
+        }
+        println!();
+        if let Some(text) = &info.text {
+            for line in text.lines() {
+                println!("    {}", line);
+            }
+            continue;
+        }
+        for (key, value) in &info.fields {
+            match value {
+                // 对象列表每项一行
+                Value::List(list) if matches!(list.first(), Some(Value::Object(_))) => {
+                    println!("    {}:", key);
+                    for item in list {
+                        println!("      {}", text_value(item));
+                    }
+                }
+                _ => println!("    {}: {}", key, text_value(value)),
+            }
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// 数字列表写在一行内，其余按层级缩进
+fn json_value(value: &Value, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent + 1);
+    match value {
+        Value::Num(n) => write!(out, "{}", n).unwrap(),
+        Value::Str(s) => out.push_str(&json_string(s)),
+        Value::List(list) if list.iter().all(|v| matches!(v, Value::Num(_))) => {
+            let items: Vec<String> = list.iter().map(text_value).collect();
+            write!(out, "[{}]", items.join(", ")).unwrap();
+        }
+        Value::List(list) => {
+            out.push_str("[\n");
+            for (i, item) in list.iter().enumerate() {
+                out.push_str(&pad);
+                json_value(item, indent + 1, out);
+                out.push_str(if i + 1 < list.len() { ",\n" } else { "\n" });
+            }
+            write!(out, "{}]", "  ".repeat(indent)).unwrap();
+        }
+        Value::Object(fields) => {
+            out.push_str("{\n");
+            for (i, (key, item)) in fields.iter().enumerate() {
+                write!(out, "{}{}: ", pad, json_string(key)).unwrap();
+                json_value(item, indent + 1, out);
+                out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+            }
+            write!(out, "{}}}", "  ".repeat(indent)).unwrap();
+        }
+    }
+}
+
+fn json_file(path: &str, infos: Vec<SegmentInfo>) -> Value {
+    let segments = infos
+        .into_iter()
+        .map(|info| {
+            let mut fields = vec![
+                ("offset", num(info.offset as i64)),
+                ("marker", num(info.marker)),
+                ("name", string(info.name)),
+                ("length", num(info.length)),
+            ];
+            fields.extend(info.fields);
+            Value::Object(fields)
+        })
+        .collect();
+    Value::Object(vec![
+        ("file", string(path)),
+        ("segments", Value::List(segments)),
+    ])
+}
+
+fn read_segments(path: &str) -> Result<Vec<SegmentInfo>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(file);
+    let segments = Segment::from_file(&mut reader).map_err(|e| format!("{:?}", e))?;
+    Ok(segments.iter().map(segment_info).collect())
+}
+
+fn main() {
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    let mut files = Vec::new();
+    for path in &paths {
+        match read_segments(path) {
+            Ok(infos) if json => files.push(json_file(path, infos)),
+            Ok(infos) => print_text(path, &infos),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+    if json {
+        // 始终输出数组，便于脚本处理多个文件
+        let mut out = String::new();
+        json_value(&Value::List(files), 0, &mut out);
+        println!("{}", out);
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(json_string("\n\r\t"), r#""\n\r\t""#);
+        assert_eq!(
+            json_string("\u{0}\u{1f}\u{7f}é"),
+            "\"\\u0000\\u001f\u{7f}é\""
+        );
+    }
+
+    #[test]
+    fn test_json_value() {
+        let value = Value::Object(vec![
+            ("name", string("COM")),
+            ("counts", Value::List(vec![num(1), num(-2)])),
+            ("empty", Value::List(vec![])),
+            (
+                "tables",
+                Value::List(vec![Value::Object(vec![("id", num(0))])]),
+            ),
+        ]);
+        let mut out = String::new();
+        json_value(&value, 0, &mut out);
+        let expected = r#"{
+  "name": "COM",
+  "counts": [1, -2],
+  "empty": [],
+  "tables": [
+    {
+      "id": 0
+    }
+  ]
+}"#;
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_segment_info() {
+        let infos = read_segments("tests/data/baseline_420.jpg").unwrap();
+        let names: Vec<&str> = infos.iter().map(|info| info.name.as_str()).collect();
+        assert_eq!(names.first(), Some(&"SOI"));
+        assert_eq!(names.last(), Some(&"EOI"));
+        assert!(names.contains(&"DHT"));
+
+        let app0 = &infos[1];
+        assert_eq!(
+            (app0.name.as_str(), app0.offset, app0.length),
+            ("APP0", 2, 16)
+        );
+        assert_eq!(text_value(&app0.fields[0].1), "JFIF");
+
+        let sof = infos.iter().find(|info| info.name == "SOF0").unwrap();
+        let field = |key| {
+            let (_, value) = sof.fields.iter().find(|(k, _)| *k == key).unwrap();
+            text_value(value)
+        };
+        assert_eq!(field("width"), "48");
+        assert_eq!(field("height"), "32");
+        assert_eq!(
+            field("components"),
+            "[id=0 h=2 v=2 tq=0, id=1 h=1 v=1 tq=1, id=2 h=1 v=1 tq=1]"
+        );
+
+        // DQT 在文本模式下使用 Display 的输出
+        let dqt = infos.iter().find(|info| info.name == "DQT").unwrap();
+        assert!(dqt.text.is_some());
+
+        let mut out = String::new();
+        json_value(&json_file("a.jpg", infos), 0, &mut out);
+        assert!(out.starts_with("{\n  \"file\": \"a.jpg\",\n  \"segments\": [\n"));
+        assert!(out.contains("\"name\": \"SOF0\",\n      \"length\": 17,"));
+        assert!(out.contains("\"density\": [1, 1],"));
+    }
+}
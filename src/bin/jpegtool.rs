@@ -0,0 +1,374 @@
+use std::{
+    env, fs,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
+
+use my_tiny_jpeg_decoder::{
+    compare::{self, gray_to_rgb},
+    component::frame::FrameType,
+    decode::OutputFormat,
+    decode_with_header,
+    export::{self, pixels, ImageFormat},
+    import, open_jpeg, JpegErrorType,
+};
+
+const USAGE: &str = "用法:
+  jpegtool convert [选项] <输入.jpg> <输出文件>
+    --format png|ppm|pgm|bmp    输出格式，默认按输出文件的扩展名
+    --scale 1|2|4|8             缩小倍数，也可以写成 1/2、1/4、1/8
+    --orientation auto|ignore   是否按 EXIF 方向标签转正，默认 auto
+    --gray                      输出灰度图（PNG、BMP）
+
+  jpegtool batch <目录>
+    递归解码目录下所有 .jpg/.jpeg 文件，每个文件输出一行:
+    状态  耗时(ms)  宽x高  帧类型  路径  错误信息
+    状态为 ok、unsupported、corrupt、truncated 或 error（读取失败），最后输出统计
+    有文件解码失败时退出码为 1
+
+  jpegtool compare [选项] <图像A> <图像B>
+    比较两幅图像，输出 PSNR、SSIM、最大误差和平均误差
+    图像可以是 JPEG（用本解码器解码）、PNG、BMP 或二进制 PPM/PGM
+    --diff <输出文件>            输出放大后的差值图
+    --amplify <倍数>             差值图的放大倍数，默认 10
+    --min-psnr <dB>              PSNR 低于此值时退出码为 1
+
+退出码: 0 成功，1 文件损坏或不完整，2 参数错误，3 读写文件失败，4 不支持的 JPEG 类型或超出尺寸限制";
+
+const EXIT_CORRUPT: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO: i32 = 3;
+const EXIT_UNSUPPORTED: i32 = 4;
+
+struct DecodedImage {
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: Vec<u8>,
+    orientation: Option<u16>,
+    frame_type: FrameType,
+}
+
+fn exit_code(e: &JpegErrorType) -> i32 {
+    match e {
+        JpegErrorType::IOError(_) => EXIT_IO,
+        JpegErrorType::UnsupportedFrameType(_)
+        | JpegErrorType::UnsupportedPrecision(_)
+        | JpegErrorType::UnsupportedScanCount(_)
+        | JpegErrorType::UnsupportedFormat(_)
+        | JpegErrorType::LimitExceeded(_) => EXIT_UNSUPPORTED,
+        JpegErrorType::Truncated | JpegErrorType::Corrupt(_) => EXIT_CORRUPT,
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n\n{}", msg, USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+fn decode(path: &str, format: OutputFormat) -> Result<DecodedImage, JpegErrorType> {
+    let (mut reader, header) = open_jpeg(path)?;
+    let orientation = header.orientation();
+    let frame_type = header.frame.get_type();
+    let (width, height, pixels) = decode_with_header(&mut reader, header, format)?;
+    Ok(DecodedImage {
+        width,
+        height,
+        channels: format.bytes_per_pixel(),
+        pixels,
+        orientation,
+        frame_type,
+    })
+}
+
+fn parse_scale(arg: &str) -> Option<usize> {
+    let factor = arg.strip_prefix("1/").unwrap_or(arg).parse().ok()?;
+    match factor {
+        1 | 2 | 4 | 8 => Some(factor),
+        _ => None,
+    }
+}
+
+fn convert(args: &[String]) -> i32 {
+    let mut format = None;
+    let mut scale = 1;
+    let mut orientation = true;
+    let mut gray = false;
+    let mut paths = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| match iter.next() {
+            Some(v) => v.clone(),
+            None => usage_error(&format!("{} 需要一个参数", name)),
+        };
+        match arg.as_str() {
+            "--format" => {
+                let v = value("--format");
+                format = Some(
+                    ImageFormat::from_name(&v)
+                        .unwrap_or_else(|| usage_error(&format!("未知的输出格式: {}", v))),
+                );
+            }
+            "--scale" => {
+                let v = value("--scale");
+                scale = parse_scale(&v)
+                    .unwrap_or_else(|| usage_error(&format!("无效的缩小倍数: {}", v)));
+            }
+            "--orientation" => {
+                orientation = match value("--orientation").as_str() {
+                    "auto" => true,
+                    "ignore" => false,
+                    v => usage_error(&format!("无效的方向选项: {}", v)),
+                };
+            }
+            "--gray" => gray = true,
+            _ if arg.starts_with("--") => usage_error(&format!("未知的选项: {}", arg)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    let (input, output) = match &paths[..] {
+        [input, output] => (input, output),
+        _ => usage_error("需要一个输入文件和一个输出文件"),
+    };
+    let format = match format.or_else(|| ImageFormat::from_path(output)) {
+        Some(format) => format,
+        None => usage_error("无法从扩展名判断输出格式，请使用 --format"),
+    };
+
+    let image = match decode(input, format.output_format(gray)) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return exit_code(&e);
+        }
+    };
+
+    let (mut width, mut height, mut data) = (image.width, image.height, image.pixels);
+    if let (true, Some(o)) = (orientation, image.orientation) {
+        (width, height, data) = pixels::apply_orientation(width, height, image.channels, &data, o);
+    }
+    (width, height, data) = pixels::downscale(width, height, image.channels, &data, scale);
+
+    let result = File::create(output).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        export::write_image(&mut writer, format, width, height, image.channels, &data)?;
+        writer.flush()
+    });
+    if let Err(e) = result {
+        eprintln!("{}: 写入失败: {}", output, e);
+        return EXIT_IO;
+    }
+    0
+}
+
+// 按文件名排序，保证每次输出的顺序相同
+fn collect_jpegs(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) => {
+            eprintln!("{}: {}", dir.display(), e);
+            return;
+        }
+    };
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_jpegs(&path, files);
+        } else if is_jpeg(&path.to_string_lossy()) {
+            files.push(path);
+        }
+    }
+}
+
+fn status(e: &JpegErrorType) -> &'static str {
+    match e {
+        JpegErrorType::IOError(_) => "error",
+        JpegErrorType::Truncated => "truncated",
+        JpegErrorType::UnsupportedFrameType(_)
+        | JpegErrorType::UnsupportedPrecision(_)
+        | JpegErrorType::UnsupportedScanCount(_)
+        | JpegErrorType::UnsupportedFormat(_)
+        | JpegErrorType::LimitExceeded(_) => "unsupported",
+        JpegErrorType::Corrupt(_) => "corrupt",
+    }
+}
+
+fn batch(args: &[String]) -> i32 {
+    let dir = match args {
+        [dir] => Path::new(dir),
+        _ => usage_error("需要一个目录"),
+    };
+    let mut files = Vec::new();
+    collect_jpegs(dir, &mut files);
+
+    // 按状态计数，顺序与输出的统计顺序一致
+    let mut tally = [
+        ("ok", 0),
+        ("unsupported", 0),
+        ("corrupt", 0),
+        ("truncated", 0),
+        ("error", 0),
+    ];
+    let total_start = Instant::now();
+    for path in &files {
+        let start = Instant::now();
+        let result = decode(&path.to_string_lossy(), OutputFormat::Rgb8);
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let line = match &result {
+            Ok(image) => format!(
+                "ok\t{:.1}\t{}x{}\t{}\t{}",
+                ms,
+                image.width,
+                image.height,
+                image.frame_type,
+                path.display()
+            ),
+            Err(e) => format!("{}\t{:.1}\t-\t-\t{}\t{}", status(e), ms, path.display(), e),
+        };
+        println!("{}", line);
+        let name = match &result {
+            Ok(_) => "ok",
+            Err(e) => status(e),
+        };
+        if let Some(entry) = tally.iter_mut().find(|(s, _)| *s == name) {
+            entry.1 += 1;
+        }
+    }
+
+    let counts: Vec<String> = tally.iter().map(|(s, n)| format!("{} {}", s, n)).collect();
+    println!(
+        "共 {} 个文件，用时 {:.1} 秒: {}",
+        files.len(),
+        total_start.elapsed().as_secs_f64(),
+        counts.join(", ")
+    );
+    if tally[0].1 == files.len() {
+        0
+    } else {
+        1
+    }
+}
+
+fn is_jpeg(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+}
+
+// 读取要比较的图像，返回宽、高、通道数和像素，失败时返回退出码
+fn load(path: &str) -> Result<(usize, usize, usize, Vec<u8>), i32> {
+    if is_jpeg(path) {
+        return match decode(path, OutputFormat::Rgb8) {
+            Ok(image) => Ok((image.width, image.height, 3, image.pixels)),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                Err(exit_code(&e))
+            }
+        };
+    }
+    import::read_image(path).map_err(|e| {
+        eprintln!("{}: {}", path, e);
+        EXIT_IO
+    })
+}
+
+fn compare_images(args: &[String]) -> i32 {
+    let mut diff = None;
+    let mut amplify = 10;
+    let mut min_psnr = None;
+    let mut paths = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| match iter.next() {
+            Some(v) => v.clone(),
+            None => usage_error(&format!("{} 需要一个参数", name)),
+        };
+        match arg.as_str() {
+            "--diff" => diff = Some(value("--diff")),
+            "--amplify" => {
+                let v = value("--amplify");
+                amplify = v
+                    .parse()
+                    .unwrap_or_else(|_| usage_error(&format!("无效的放大倍数: {}", v)));
+            }
+            "--min-psnr" => {
+                let v = value("--min-psnr");
+                min_psnr = Some(
+                    v.parse::<f64>()
+                        .unwrap_or_else(|_| usage_error(&format!("无效的 PSNR: {}", v))),
+                );
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("未知的选项: {}", arg)),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    let (path_a, path_b) = match &paths[..] {
+        [a, b] => (a, b),
+        _ => usage_error("需要两个图像文件"),
+    };
+    let diff_format = diff.as_ref().map(|path| {
+        ImageFormat::from_path(path).unwrap_or_else(|| usage_error("无法从扩展名判断差值图的格式"))
+    });
+
+    let (a, b) = match (load(path_a), load(path_b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(code), _) | (_, Err(code)) => return code,
+    };
+    if (a.0, a.1) != (b.0, b.1) {
+        eprintln!("图像尺寸不同: {}x{} 和 {}x{}", a.0, a.1, b.0, b.1);
+        return EXIT_CORRUPT;
+    }
+    let (width, height) = (a.0, a.1);
+    // 一幅是灰度图、一幅是彩色图时，把灰度图扩展为 RGB 再比较
+    let (channels, pa, pb) = match (a.2, b.2) {
+        (1, 3) => (3, gray_to_rgb(&a.3), b.3),
+        (3, 1) => (3, a.3, gray_to_rgb(&b.3)),
+        (c, _) => (c, a.3, b.3),
+    };
+
+    let result = compare::compare(width, height, channels, &pa, &pb);
+    println!("PSNR: {:.2} dB", result.psnr);
+    println!("SSIM: {:.5}", result.ssim);
+    println!("最大误差: {}", result.max_error);
+    println!("平均误差: {:.4}", result.mean_error);
+
+    if let (Some(path), Some(format)) = (diff, diff_format) {
+        let pixels = compare::diff_image(&pa, &pb, amplify);
+        let result = File::create(&path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            export::write_image(&mut writer, format, width, height, channels, &pixels)?;
+            writer.flush()
+        });
+        if let Err(e) = result {
+            eprintln!("{}: 写入失败: {}", path, e);
+            return EXIT_IO;
+        }
+    }
+
+    match min_psnr {
+        Some(min) if result.psnr < min => 1,
+        _ => 0,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(|s| s.as_str()) {
+        Some("convert") => convert(&args[1..]),
+        Some("batch") => batch(&args[1..]),
+        Some("compare") => compare_images(&args[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            0
+        }
+        Some(cmd) => usage_error(&format!("未知的命令: {}", cmd)),
+        None => usage_error("缺少命令"),
+    };
+    process::exit(code);
+}
@@ -1,6 +1,5 @@
 use std::{
     cmp::min,
-    fs::File,
     io::{self, BufRead, BufReader, Read, Seek},
 };
 
@@ -49,11 +48,11 @@ pub trait BitReader {
     fn print_pos(&mut self);
 }
 
-impl BitReader for BufReader<File> {
+impl<R: Read + Seek> BitReader for BufReader<R> {
     fn get_position(&mut self) -> Result<usize, BitStreamErrorType> {
-        self.stream_position()
+        let pos = self.stream_position()
             .map_err(|e| BitStreamErrorType::IOError(e))?;
-        Ok(0)
+        Ok(pos as usize)
     }
     fn read_byte(&mut self, offset: usize) -> Result<(u8, usize), BitStreamErrorType> {
         let buffer = self.fill_buf()
@@ -232,6 +231,21 @@ impl<'a, R: BitReader> BitStream<'a, R> {
         Ok(())
     }
 
+    // 下一个未读字节在 reader 中的位置
+    pub fn position(&mut self) -> Result<usize, BitStreamErrorType> {
+        self.reader.get_position()
+    }
+
+    // 当前字节中未读完的位，换一个 reader 继续读取时用 restore 恢复
+    pub fn save(&self) -> (u8, usize) {
+        (self.cur_byte, self.bit_start)
+    }
+
+    pub fn restore(&mut self, (cur_byte, bit_start): (u8, usize)) {
+        self.cur_byte = cur_byte;
+        self.bit_start = bit_start;
+    }
+
     pub fn get_bit_start(&self) -> usize {
         self.bit_start
     }
@@ -0,0 +1,111 @@
+// SSIM 的窗口大小和步长
+const SSIM_WINDOW: usize = 8;
+const SSIM_STEP: usize = 4;
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    // 两幅图完全相同时为无穷大
+    pub psnr: f64,
+    pub ssim: f64,
+    pub max_error: u8,
+    pub mean_error: f64,
+}
+
+// a、b 都是每像素 channels 字节、尺寸相同的图像
+pub fn compare(width: usize, height: usize, channels: usize, a: &[u8], b: &[u8]) -> Comparison {
+    let len = width * height * channels;
+    let mut max_error = 0;
+    let mut sum = 0u64;
+    let mut sum_sq = 0u64;
+    for (&x, &y) in a[..len].iter().zip(&b[..len]) {
+        let d = x.abs_diff(y);
+        max_error = max_error.max(d);
+        sum += d as u64;
+        sum_sq += d as u64 * d as u64;
+    }
+    let mse = sum_sq as f64 / len as f64;
+    let psnr = if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    };
+    Comparison {
+        psnr,
+        ssim: ssim(width, height, channels, a, b),
+        max_error,
+        mean_error: sum as f64 / len as f64,
+    }
+}
+
+// 每个通道分别在 8x8 的滑动窗口上计算 SSIM，再取所有窗口的平均值
+pub fn ssim(width: usize, height: usize, channels: usize, a: &[u8], b: &[u8]) -> f64 {
+    // 图像比窗口小时用整幅图作为一个窗口
+    let win_x = SSIM_WINDOW.min(width);
+    let win_y = SSIM_WINDOW.min(height);
+    let n = (win_x * win_y) as f64;
+    let mut total = 0.0;
+    let mut count = 0;
+    for c in 0..channels {
+        for y0 in (0..=height - win_y).step_by(SSIM_STEP) {
+            for x0 in (0..=width - win_x).step_by(SSIM_STEP) {
+                let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+                for y in y0..y0 + win_y {
+                    for x in x0..x0 + win_x {
+                        let idx = (y * width + x) * channels + c;
+                        let (pa, pb) = (a[idx] as f64, b[idx] as f64);
+                        sa += pa;
+                        sb += pb;
+                        saa += pa * pa;
+                        sbb += pb * pb;
+                        sab += pa * pb;
+                    }
+                }
+                let (ma, mb) = (sa / n, sb / n);
+                let va = saa / n - ma * ma;
+                let vb = sbb / n - mb * mb;
+                let cov = sab / n - ma * mb;
+                total += ((2.0 * ma * mb + C1) * (2.0 * cov + C2))
+                    / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+                count += 1;
+            }
+        }
+    }
+    total / count as f64
+}
+
+// 差值图：每个样本是 |a - b| * amplify，超过 255 的截断
+pub fn diff_image(a: &[u8], b: &[u8], amplify: u32) -> Vec<u8> {
+    a.iter()
+        .zip(b)
+        .map(|(&x, &y)| (x.abs_diff(y) as u32 * amplify).min(255) as u8)
+        .collect()
+}
+
+// 灰度图扩展为 RGB，便于和彩色图比较
+pub fn gray_to_rgb(pixels: &[u8]) -> Vec<u8> {
+    pixels.iter().flat_map(|&p| [p, p, p]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let a: Vec<u8> = (0..16 * 16).map(|i| (i % 251) as u8).collect();
+        let same = compare(16, 16, 1, &a, &a);
+        assert!(same.psnr.is_infinite());
+        assert!((same.ssim - 1.0).abs() < 1e-9);
+        assert_eq!(same.max_error, 0);
+
+        let b: Vec<u8> = a.iter().map(|&p| p.saturating_add(4)).collect();
+        let result = compare(16, 16, 1, &a, &b);
+        assert_eq!(result.max_error, 4);
+        // MSE 接近 16 时 PSNR 约为 36 dB
+        assert!(result.psnr > 35.0 && result.psnr < 37.0);
+        assert!(result.ssim < 1.0 && result.ssim > 0.9);
+        assert_eq!(diff_image(&a, &b, 10)[0], 40);
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use rustc_hash::FxHashMap;
 
 
@@ -14,6 +16,21 @@ pub enum FrameType {
     Lossless(FrameTypeCoding),
 }
 
+impl fmt::Display for FrameType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coding = |c: &FrameTypeCoding| match c {
+            FrameTypeCoding::HuffmanCoding => "Huffman",
+            FrameTypeCoding::ArithmeticCoding => "arithmetic",
+        };
+        match self {
+            FrameType::BaselineDCT => write!(f, "baseline DCT, Huffman"),
+            FrameType::ExtendedDCT(c) => write!(f, "extended DCT, {}", coding(c)),
+            FrameType::ProgressiveDCT(c) => write!(f, "progressive DCT, {}", coding(c)),
+            FrameType::Lossless(c) => write!(f, "lossless, {}", coding(c)),
+        }
+    }
+}
+
 pub struct Frame {
     frame_type: FrameType,
     _sample_precision: u8,
@@ -28,20 +45,46 @@ pub struct FrameComponent {
     quantization_table_id: u8,
 }
 
+#[derive(Debug)]
 pub enum FrameErrorType {
     InvalidFrameType(u8),
+    InvalidLength(usize),
+    InvalidDimensions(u16, u16),
+    InvalidComponentCount(u8),
+    DuplicateComponent(u8),
+    InvalidSamplingFactor(u8 /* 分量 id */, u8 /* hv */),
 }
 
 impl Frame {
     pub fn new(n: u8, data: Vec<u8>) -> Result<Self, FrameErrorType> {
+        if data.len() < 6 {
+            return Err(FrameErrorType::InvalidLength(data.len()));
+        }
         let precision = data[0];
         let height = u16::from_be_bytes([data[1], data[2]]);
         let width = u16::from_be_bytes([data[3], data[4]]);
+        // 高度为 0 表示由 DNL 段给出，这里不支持
+        if width == 0 || height == 0 {
+            return Err(FrameErrorType::InvalidDimensions(width, height));
+        }
         let comp_nr = data[5] as usize;
+        if comp_nr == 0 || comp_nr > 4 {
+            return Err(FrameErrorType::InvalidComponentCount(data[5]));
+        }
+        if data.len() < 6 + comp_nr * 3 {
+            return Err(FrameErrorType::InvalidLength(data.len()));
+        }
         let mut v = FxHashMap::default();
         for i in 0..comp_nr {
-            let comp = FrameComponent::new(data[i * 3 + 6], data[i * 3 + 7], data[i * 3 + 8]);
-            v.insert(comp.get_id(), comp);
+            let (id, hv) = (data[i * 3 + 6], data[i * 3 + 7]);
+            // 采样因子的取值范围是 1~4
+            if !(1..=4).contains(&(hv >> 4)) || !(1..=4).contains(&(hv & 0x0f)) {
+                return Err(FrameErrorType::InvalidSamplingFactor(id, hv));
+            }
+            let comp = FrameComponent::new(id, hv, data[i * 3 + 8]);
+            if v.insert(comp.get_id(), comp).is_some() {
+                return Err(FrameErrorType::DuplicateComponent(id));
+            }
         }
 
         let frame_type = match n {
@@ -70,6 +113,10 @@ impl Frame {
         self.frame_type
     }
 
+    pub fn get_precision(&self) -> u8 {
+        self._sample_precision
+    }
+
     pub fn get_width(&self) -> u16 {
         self.width
     }
@@ -77,6 +124,17 @@ impl Frame {
     pub fn get_height(&self) -> u16 {
         self.height
     }
+
+    // 所有分量中最大的水平、垂直采样因子，即一个 MCU 包含的块数
+    pub fn max_factors(&self) -> (usize, usize) {
+        let mut max_x = 0;
+        let mut max_y = 0;
+        for (_, comp) in self.components.iter() {
+            max_x = max_x.max(comp.get_factor_x() as usize);
+            max_y = max_y.max(comp.get_factor_y() as usize);
+        }
+        (max_x, max_y)
+    }
 }
 
 impl FrameComponent {
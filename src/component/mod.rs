@@ -22,6 +22,7 @@ pub struct Component {
 pub enum ComponentErrorType {
     InvalidQuantizationId(u8),
     InvalidFrameId(u8),
+    InvalidHuffmanId(u8),
 }
 
 impl Component {
@@ -35,8 +36,18 @@ impl Component {
         let mut map = FxHashMap::default();
 
         for (id, comp) in scan_map.components {
-            let dc_huff = dc_map[&comp.get_dc_id()].clone();
-            let ac_huff = ac_map[&comp.get_ac_id()].clone();
+            let dc_huff = match dc_map.get(&comp.get_dc_id()) {
+                Some(huff) => huff.clone(),
+                None => {
+                    return Err(ComponentErrorType::InvalidHuffmanId(comp.get_dc_id()));
+                }
+            };
+            let ac_huff = match ac_map.get(&comp.get_ac_id()) {
+                Some(huff) => huff.clone(),
+                None => {
+                    return Err(ComponentErrorType::InvalidHuffmanId(comp.get_ac_id()));
+                }
+            };
             let fcomp = match frame.components.get(&id) {
                 Some(fcomp) => fcomp,
                 None => {
@@ -64,6 +75,10 @@ impl Component {
         Ok(map)
     }
 
+    pub fn get_id(&self) -> u8 {
+        self.id
+    }
+
     pub fn get_factor_x(&self) -> u8 {
         self.factor_x
     }
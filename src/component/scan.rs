@@ -33,21 +33,37 @@ impl ScanComponent {
     }
 }
 
+#[derive(Debug)]
+pub enum ScanErrorType {
+    InvalidLength(usize),
+    InvalidComponentCount(u8),
+    DuplicateComponent(u8),
+}
+
 impl Scan {
-    pub fn new(data: Vec<u8>) -> Self {
+    pub fn new(data: Vec<u8>) -> Result<Self, ScanErrorType> {
         let mut components = FxHashMap::default();
-        let comp_nr = data[0] as usize;
+        let comp_nr = *data.first().ok_or(ScanErrorType::InvalidLength(0))? as usize;
+        if comp_nr == 0 || comp_nr > 4 {
+            return Err(ScanErrorType::InvalidComponentCount(comp_nr as u8));
+        }
+        // 分量之后还有 3 字节的频谱选择和逐次逼近参数
+        if data.len() < 1 + comp_nr * 2 + 3 {
+            return Err(ScanErrorType::InvalidLength(data.len()));
+        }
 
         for i in 0..comp_nr {
             let frame_comp_id = data[1 + i * 2 + 0];
             let dc_id = data[1 + i * 2 + 1] >> 4;
             let ac_id = data[1 + i * 2 + 1] & 0x0f;
             let comp = ScanComponent::new(frame_comp_id, dc_id, ac_id);
-            components.insert(comp.get_id(), comp);
+            if components.insert(comp.get_id(), comp).is_some() {
+                return Err(ScanErrorType::DuplicateComponent(frame_comp_id));
+            }
         }
 
-        Self {
+        Ok(Self {
             components: components,
-        }
+        })
     }
 }
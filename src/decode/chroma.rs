@@ -1,28 +1,28 @@
+pub fn gray2rgb(y: &[f32], buf: &mut [u8]) {
+    for (pixel, y) in buf.chunks_exact_mut(4).zip(y) {
+        let v = (128.0 + y).round() as u8;
+        pixel.copy_from_slice(&[v, v, v, 0xff]);
+    }
+}
+
+pub fn gray2luma(y: &[f32], buf: &mut [u8]) {
+    for (pixel, y) in buf.iter_mut().zip(y) {
+        *pixel = (128.0 + y).round() as u8;
+    }
+}
+
 #[cfg(not(target_feature = "sse2"))]
-pub fn ycbcr2rgb(
-    y: &[f32], y_fact: usize,
-    cb: &[f32], cb_fact: usize,
-    cr: &[f32], cr_fact: usize,
-    buf: &mut [u8]
-) {
-    let _y = [y[7/y_fact], y[6/y_fact], y[5/y_fact], y[4/y_fact], y[4/y_fact], y[3/y_fact], y[2/y_fact], y[1/y_fact]];
-    let _cb = [cb[7/cb_fact], cb[6/cb_fact], cb[5/cb_fact], cb[4/cb_fact], cb[4/cb_fact], cb[3/cb_fact], cb[2/cb_fact], cb[1/cb_fact]];
-    let _cr = [cr[7/cr_fact], cr[6/cr_fact], cr[5/cr_fact], cr[4/cr_fact], cr[4/cr_fact], cr[3/cr_fact], cr[2/cr_fact], cr[1/cr_fact]];
+pub fn ycbcr2rgb(y: &[f32], cb: &[f32], cr: &[f32], buf: &mut [u8]) {
     for x in 0..8 {
-        buf[x * 4 + 0] = (128.0 + y + 1.402 * cr).round() as u8;
-        buf[x * 4 + 1] = (128.0 + y - 0.714 * cr - 0.344 * cb).round() as u8;
-        buf[x * 4 + 2] = (128.0 + y + 1.772 * cb).round() as u8;
+        buf[x * 4 + 0] = (128.0 + y[x] + 1.402 * cr[x]).round() as u8;
+        buf[x * 4 + 1] = (128.0 + y[x] - 0.714 * cr[x] - 0.344 * cb[x]).round() as u8;
+        buf[x * 4 + 2] = (128.0 + y[x] + 1.772 * cb[x]).round() as u8;
         buf[x * 4 + 3] = 0xff;
     }
 }
 
 #[cfg(target_feature = "sse2")]
-pub fn ycbcr2rgb(
-    y: &[f32], y_fact: usize,
-    cb: &[f32], cb_fact: usize,
-    cr: &[f32], cr_fact: usize,
-    buf: &mut [u8]
-) {
+pub fn ycbcr2rgb(y: &[f32], cb: &[f32], cr: &[f32], buf: &mut [u8]) {
     use std::arch::x86_64::*;
     unsafe {
         let zero = _mm_setzero_ps();
@@ -33,12 +33,12 @@ pub fn ycbcr2rgb(
         let kg2 = _mm_set1_ps(0.344);
         let kb1 = _mm_set1_ps(1.772);
 
-        let y0 = _mm_add_ps(_mm_set_ps(y[4/y_fact], y[3/y_fact], y[2/y_fact], y[1/y_fact]), offset);
-        let y1 = _mm_add_ps(_mm_set_ps(y[7/y_fact], y[6/y_fact], y[5/y_fact], y[4/y_fact]), offset);
-        let cb0 = _mm_set_ps(cb[4/cb_fact], cb[3/cb_fact], cb[2/cb_fact], cb[1/cb_fact]);
-        let cb1 = _mm_set_ps(cb[7/cb_fact], cb[6/cb_fact], cb[5/cb_fact], cb[4/cb_fact]);
-        let cr0 = _mm_set_ps(cr[4/cr_fact], cr[3/cr_fact], cr[2/cr_fact], cr[1/cr_fact]);
-        let cr1 = _mm_set_ps(cr[7/cr_fact], cr[6/cr_fact], cr[5/cr_fact], cr[4/cr_fact]);
+        let y0 = _mm_add_ps(_mm_loadu_ps(y[0..4].as_ptr()), offset);
+        let y1 = _mm_add_ps(_mm_loadu_ps(y[4..8].as_ptr()), offset);
+        let cb0 = _mm_loadu_ps(cb[0..4].as_ptr());
+        let cb1 = _mm_loadu_ps(cb[4..8].as_ptr());
+        let cr0 = _mm_loadu_ps(cr[0..4].as_ptr());
+        let cr1 = _mm_loadu_ps(cr[4..8].as_ptr());
 
         let r0 = _mm_min_epi32(
             _mm_cvtps_epi32(
@@ -90,12 +90,12 @@ pub fn ycbcr2rgb(
         let rgb0 = _mm_or_si128(_mm_or_si128(r0, _mm_slli_epi32(g0, 8)), _mm_slli_epi32(b0, 16));
         let rgb1 = _mm_or_si128(_mm_or_si128(r1, _mm_slli_epi32(g1, 8)), _mm_slli_epi32(b1, 16));
 
-        _mm_storeu_si128(buf[0..15].as_mut_ptr() as *mut __m128i, rgb0);
+        _mm_storeu_si128(buf[0..16].as_mut_ptr() as *mut __m128i, rgb0);
         buf[3] = 0xff;
         buf[7] = 0xff;
         buf[11] = 0xff;
         buf[15] = 0xff;
-        _mm_storeu_si128(buf[16..31].as_mut_ptr() as *mut __m128i, rgb1);
+        _mm_storeu_si128(buf[16..32].as_mut_ptr() as *mut __m128i, rgb1);
         buf[19] = 0xff;
         buf[23] = 0xff;
         buf[27] = 0xff;
@@ -108,11 +108,18 @@ mod tests {
     #[test]
     fn test() {
         let y:[f32;8] = [127.0,127.0,127.0,127.0,127.0,127.0,127.0,127.0];
-        let cb:[f32;4] = [-1.0272651, -1.391968, -1.8156782, -1.8800913];
-        let cr:[f32;4] = [-2.6093392, -2.444171, -2.1389794, -1.7402275];
+        let cb:[f32;8] = [-1.0272651, -1.0272651, -1.391968, -1.391968, -1.8156782, -1.8156782, -1.8800913, -1.8800913];
+        let cr:[f32;8] = [-2.6093392, -2.6093392, -2.444171, -2.444171, -2.1389794, -2.1389794, -1.7402275, -1.7402275];
 
         let mut buf:[u8;32] = [0;32];
-        super::ycbcr2rgb(&y, 1, &cb, 2, &cr, 2, &mut buf);
+        super::ycbcr2rgb(&y, &cb, &cr, &mut buf);
         print!("{:?}", buf);
+        for x in 0..8 {
+            let r = (255.0 + 1.402 * cr[x]).round() as u8;
+            let b = (255.0 + 1.772 * cb[x]).round() as u8;
+            assert_eq!(buf[x * 4], r);
+            assert_eq!(buf[x * 4 + 2], b);
+            assert_eq!(buf[x * 4 + 3], 0xff);
+        }
     }
 }
\ No newline at end of file
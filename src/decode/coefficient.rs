@@ -0,0 +1,168 @@
+use std::{
+    error,
+    io::{BufReader, Read, Seek},
+    rc::Rc,
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    bitstream::BitStream,
+    component::{frame::Frame, Component},
+    dqt::Dqt,
+    zigzag::ZigZagScan,
+};
+
+use super::{
+    check_restart,
+    mcu::{decode_mcu_coefficients, sorted_components},
+    mcu_count,
+};
+
+// 一个分量的全部量化系数，块按行优先排列，包括图像边缘补齐的块
+#[derive(Clone)]
+pub struct ComponentCoefficients {
+    pub id: u8,
+    pub factor_x: u8,
+    pub factor_y: u8,
+    pub blocks_x: usize,
+    pub blocks_y: usize,
+    // 每个块的 64 个系数按自然顺序（行优先）存放，未反量化
+    pub blocks: Vec<[i16; 64]>,
+    pub dqt: Rc<Dqt>,
+}
+
+impl ComponentCoefficients {
+    pub fn block(&self, x: usize, y: usize) -> &[i16; 64] {
+        &self.blocks[y * self.blocks_x + x]
+    }
+}
+
+// Z 字形顺序转自然顺序，损坏的数据可能让 DC 累加到 i16 的范围之外
+pub fn zigzag_to_natural(code: &[isize; 64]) -> Result<[i16; 64], Box<dyn error::Error>> {
+    let mut block = [0i16; 64];
+    for (i, (x, y)) in ZigZagScan::new(8).enumerate() {
+        block[y * 8 + x] =
+            i16::try_from(code[i]).map_err(|_| format!("系数超出 i16 范围: {}", code[i]))?;
+    }
+    Ok(block)
+}
+
+// 全部量化系数占用的字节数，每个块 64 个 i16，包括图像边缘补齐的块
+pub fn coefficient_bytes(frame: &Frame) -> usize {
+    let (x_cnt, y_cnt) = mcu_count(frame);
+    frame
+        .components
+        .values()
+        .map(|comp| {
+            let blocks_x = x_cnt.saturating_mul(comp.get_factor_x() as usize);
+            let blocks_y = y_cnt.saturating_mul(comp.get_factor_y() as usize);
+            blocks_x.saturating_mul(blocks_y).saturating_mul(128)
+        })
+        .fold(0, usize::saturating_add)
+}
+
+pub fn decode_coefficients<R: Read + Seek>(
+    frame: &Frame,
+    comps: FxHashMap<u8, Rc<Component>>,
+    bs: &mut BitStream<BufReader<R>>,
+    restart_interval: Option<u16>,
+) -> Result<Vec<ComponentCoefficients>, Box<dyn error::Error>> {
+    let mut last_dc = vec![0isize; comps.len()];
+    let (x_cnt, y_cnt) = mcu_count(frame);
+
+    let mut result: Vec<ComponentCoefficients> = sorted_components(&comps)
+        .iter()
+        .map(|comp| {
+            let blocks_x = x_cnt * comp.get_factor_x() as usize;
+            let blocks_y = y_cnt * comp.get_factor_y() as usize;
+            ComponentCoefficients {
+                id: comp.get_id(),
+                factor_x: comp.get_factor_x(),
+                factor_y: comp.get_factor_y(),
+                blocks_x,
+                blocks_y,
+                blocks: vec![[0; 64]; blocks_x * blocks_y],
+                dqt: comp.get_dqt(),
+            }
+        })
+        .collect();
+
+    let mut cnt = 0;
+
+    for y1 in 0..y_cnt {
+        for x1 in 0..x_cnt {
+            let mcu;
+            (last_dc, mcu) = decode_mcu_coefficients(last_dc, &comps, bs)?;
+            for (coefs, codes) in result.iter_mut().zip(mcu) {
+                let h = coefs.factor_x as usize;
+                let v = coefs.factor_y as usize;
+                for (by, row) in codes.iter().enumerate() {
+                    for (bx, code) in row.iter().enumerate() {
+                        let idx = (y1 * v + by) * coefs.blocks_x + x1 * h + bx;
+                        coefs.blocks[idx] = zigzag_to_natural(code)?;
+                    }
+                }
+            }
+            // 最后一个 MCU 之后没有复位标记
+            if y1 + 1 < y_cnt || x1 + 1 < x_cnt {
+                check_restart(bs, restart_interval, &mut cnt, &mut last_dc)?;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    use super::*;
+    use crate::{decode::tests::restart_at_end, get_jpeg_coefficients, try_read_header};
+
+    #[test]
+    fn test_zigzag_to_natural() {
+        let mut code = [0isize; 64];
+        for (i, c) in code.iter_mut().enumerate() {
+            *c = i as isize;
+        }
+        let block = zigzag_to_natural(&code).unwrap();
+        assert_eq!(block[0], 0);
+        assert_eq!(block[1], 1);
+        assert_eq!(block[8], 2);
+        assert_eq!(block[16], 3);
+        assert_eq!(block[9], 4);
+        assert_eq!(block[2], 5);
+        assert_eq!(block[63], 63);
+
+        code[0] = i16::MAX as isize + 1;
+        assert!(zigzag_to_natural(&code).is_err());
+        code[0] = i16::MIN as isize;
+        assert_eq!(zigzag_to_natural(&code).unwrap()[0], i16::MIN);
+    }
+
+    #[test]
+    fn test_restart_at_end() {
+        let data = restart_at_end();
+        let mut reader = BufReader::new(Cursor::new(&data));
+        let header = try_read_header(&mut reader).unwrap();
+        reader.seek(SeekFrom::Start(header.scan_start)).unwrap();
+        let comps = Component::new(
+            &header.frame,
+            header.dqt_map,
+            header.dc_map,
+            header.ac_map,
+            header.scan,
+        )
+        .unwrap();
+        let mut bs = BitStream::new(&mut reader);
+        let coefs = decode_coefficients(&header.frame, comps, &mut bs, header.restart_interval);
+        let expected = get_jpeg_coefficients("tests/data/baseline_420.jpg".to_string()).unwrap();
+        for (a, b) in coefs.unwrap().iter().zip(&expected) {
+            assert_eq!(a.blocks, b.blocks);
+        }
+        // EOI 没有被当作复位标记读掉
+        assert_eq!(reader.stream_position().unwrap(), data.len() as u64 - 2);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{component::frame::Frame, JpegErrorType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Rgb8,
+    Rgba8,
+    Bgra8,
+    Gray8,
+    // 平面格式，按分量 id 顺序依次存放 Y、Cb、Cr，每个平面保持原始的采样分辨率
+    YCbCr,
+}
+
+impl OutputFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            OutputFormat::Rgb8 => 3,
+            OutputFormat::Rgba8 | OutputFormat::Bgra8 => 4,
+            OutputFormat::Gray8 | OutputFormat::YCbCr => 1,
+        }
+    }
+
+    // 把 RGBA 像素转换成目标格式，只支持打包的 RGB 类格式
+    pub fn pack_rgba(&self, rgba: &[u8], buf: &mut [u8]) -> Result<(), JpegErrorType> {
+        match self {
+            OutputFormat::Rgba8 => buf.copy_from_slice(rgba),
+            OutputFormat::Bgra8 => {
+                for (dst, src) in buf.chunks_exact_mut(4).zip(rgba.chunks_exact(4)) {
+                    dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+                }
+            }
+            OutputFormat::Rgb8 => {
+                for (dst, src) in buf.chunks_exact_mut(3).zip(rgba.chunks_exact(4)) {
+                    dst.copy_from_slice(&src[..3]);
+                }
+            }
+            OutputFormat::Gray8 | OutputFormat::YCbCr => {
+                return Err(JpegErrorType::UnsupportedFormat(*self))
+            }
+        }
+        Ok(())
+    }
+}
+
+// 各分量平面的宽高（按分量 id 排序），即 ceil(X * H / Hmax) x ceil(Y * V / Vmax)
+pub fn plane_dimensions(frame: &Frame) -> Vec<(usize, usize)> {
+    let (max_x, max_y) = frame.max_factors();
+    let width = frame.get_width() as usize;
+    let height = frame.get_height() as usize;
+
+    let mut ids: Vec<&u8> = frame.components.keys().collect();
+    ids.sort();
+    ids.into_iter()
+        .map(|id| {
+            let comp = &frame.components[id];
+            (
+                (width * comp.get_factor_x() as usize).div_ceil(max_x),
+                (height * comp.get_factor_y() as usize).div_ceil(max_y),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_rgba() {
+        let rgba = [1, 2, 3, 255, 4, 5, 6, 128];
+        let mut buf = [0; 8];
+        OutputFormat::Bgra8.pack_rgba(&rgba, &mut buf).unwrap();
+        assert_eq!(buf, [3, 2, 1, 255, 6, 5, 4, 128]);
+        let mut buf = [0; 6];
+        OutputFormat::Rgb8.pack_rgba(&rgba, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6]);
+
+        // 灰度和平面格式不经过 RGBA
+        for format in [OutputFormat::Gray8, OutputFormat::YCbCr] {
+            let mut buf = [0; 2];
+            assert!(matches!(
+                format.pack_rgba(&rgba, &mut buf),
+                Err(JpegErrorType::UnsupportedFormat(f)) if f == format
+            ));
+        }
+    }
+}
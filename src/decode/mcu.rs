@@ -1,5 +1,7 @@
 use std::{
-    borrow::BorrowMut, error, fs::File, io::BufReader,
+    borrow::BorrowMut,
+    error,
+    io::{BufReader, Read, Seek},
     rc::Rc,
 };
 
@@ -22,36 +24,59 @@ pub struct Block {
     pub data: Vec<Vec<[[f32; 8]; 8]>>,
 }
 
-fn decode_dct(
+// 每个分量按 [块行][块列] 排列的 Z 字形系数
+pub type McuCoefficients = Vec<Vec<Vec<[isize; 64]>>>;
+
+// 读取 len 位的差值并按 JPEG 的规则还原符号
+fn read_value<R: Read + Seek>(
+    bs: &mut BitStream<BufReader<R>>,
+    len: usize,
+) -> Result<isize, Box<dyn error::Error>> {
+    let read = |bs: &mut BitStream<BufReader<R>>, n| {
+        bs.read(n).map_err(|e| format!("读取数据失败: {:?}", e))
+    };
+    if len == 1 {
+        return Ok(read(bs, 1)? as isize * 2 - 1); // 0 -> -1, 1 -> 1
+    }
+    let sign = read(bs, 1)?;
+    let num = sign << (len - 1) | read(bs, len - 1)?;
+    if sign == 0 {
+        Ok(-(((!num) & ((1 << len) - 1)) as isize))
+    } else {
+        Ok(num as isize)
+    }
+}
+
+fn decode_dct<R: Read + Seek>(
     dc: &HuffmanTable,
     last_dc: isize,
     ac: &HuffmanTable,
-    bs: &mut BitStream<BufReader<File>>,
-) -> [isize; 64] {
+    bs: &mut BitStream<BufReader<R>>,
+) -> Result<[isize; 64], Box<dyn error::Error>> {
     let mut code = [0isize; 64];
     // DC
-    let codeval = dc.huff.decode(bs).unwrap();
+    let codeval = dc
+        .huff
+        .decode(bs)
+        .map_err(|e| format!("DC 哈夫曼解码失败: {:?}", e))?;
     let len = codeval as usize;
-    if len == 0 {
-        code[0] = last_dc;
-    } else if len == 1 {
-        code[0] = last_dc + bs.read(len).unwrap() as isize * 2 - 1; // 0 -> -1, 1 -> 1
-    } else {
-        let sign = bs.read(1).unwrap();
-        let num = sign << (len - 1) | bs.read(len - 1).unwrap();
-        let result;
-        if sign == 0 {
-            result = -(((!num) & ((1 << len) - 1)) as isize);
-        } else {
-            result = num as isize;
-        }
-        code[0] = result + last_dc;
+    // 8 位精度的 DC 差值最多 11 位
+    if len > 11 {
+        return Err(format!("无效的 DC 差值长度: {}", len).into());
     }
+    code[0] = if len == 0 {
+        last_dc
+    } else {
+        last_dc + read_value(bs, len)?
+    };
 
     // AC
     let mut i = 1;
     while i < 64 {
-        let codeval = ac.huff.decode(bs).unwrap();
+        let codeval = ac
+            .huff
+            .decode(bs)
+            .map_err(|e| format!("AC 哈夫曼解码失败: {:?}", e))?;
         let zero = codeval >> 4;
         let len = (codeval & 0x0f) as usize;
 
@@ -61,41 +86,69 @@ fn decode_dct(
             } else {
                 break;
             }
-        } else if len == 1 {
-            i += zero as usize;
-            code[i] = bs.read(len).unwrap() as isize * 2 - 1; // 0 -> -1, 1 -> 1
         } else {
-            let sign = bs.read(1).unwrap();
-            let num = sign << (len - 1) | bs.read(len - 1).unwrap();
-            let result;
-            if sign == 0 {
-                result = -(((!num) & ((1 << len) - 1)) as isize);
-            } else {
-                result = num as isize;
-            }
             i += zero as usize;
-            code[i] = result;
+            if i > 63 {
+                return Err(format!("AC 系数下标越界: {}", i).into());
+            }
+            code[i] = read_value(bs, len)?;
         }
         i += 1;
     }
-    code
+    Ok(code)
+}
+
+// 按分量 id 排序，id 不一定从 1 开始（有的编码器从 0 开始编号）
+pub fn sorted_components(comps: &FxHashMap<u8, Rc<Component>>) -> Vec<Rc<Component>> {
+    let mut sorted: Vec<Rc<Component>> = comps.values().cloned().collect();
+    sorted.sort_by_key(|comp| comp.get_id());
+    sorted
 }
 
-pub fn decode_blocks(
+// 解码一个 MCU 中各分量的量化系数（Z 字形顺序），不做反量化和 IDCT
+pub fn decode_mcu_coefficients<R: Read + Seek>(
     mut last_dc: Vec<isize>,
+    comps: &FxHashMap<u8, Rc<Component>>,
+    bs: &mut BitStream<BufReader<R>>,
+) -> Result<(Vec<isize>, McuCoefficients), Box<dyn error::Error>> {
+    let mut mcu = Vec::new();
+
+    for (idx, comp) in sorted_components(comps).into_iter().enumerate() {
+        let width = comp.get_factor_x() as usize;
+        let height = comp.get_factor_y() as usize;
+
+        let mut block = vec![vec![[0isize; 64]; width]; height];
+
+        let ac_huff = comp.get_ac_huff();
+        let dc_huff = comp.get_dc_huff();
+
+        let mut dc = last_dc[idx];
+
+        for row in block.iter_mut() {
+            for code in row.iter_mut() {
+                *code = decode_dct(&*dc_huff, dc, &*ac_huff, bs)?;
+                dc = code[0];
+            }
+        }
+        last_dc[idx] = dc;
+        mcu.push(block);
+    }
+    Ok((last_dc, mcu))
+}
+
+pub fn decode_blocks<R: Read + Seek>(
+    last_dc: Vec<isize>,
     comps: FxHashMap<u8, Rc<Component>>,
-    bs: &mut BitStream<BufReader<File>>,
+    bs: &mut BitStream<BufReader<R>>,
     dct: &DCT,
 ) -> Result<(Vec<isize>, MCU), Box<dyn error::Error>> {
+    let (last_dc, codes) = decode_mcu_coefficients(last_dc, &comps, bs)?;
     let mut mcu = Vec::new();
 
     let mut max_width = 0;
     let mut max_height = 0;
 
-    let len = comps.len();
-    for idx in 1..=len {
-        let comp = comps.get(&(idx as u8)).unwrap().clone();
-
+    for (comp, codes) in sorted_components(&comps).into_iter().zip(codes) {
         let width = comp.get_factor_x() as usize;
         let height = comp.get_factor_y() as usize;
         max_width = std::cmp::max(max_width, width);
@@ -103,15 +156,9 @@ pub fn decode_blocks(
 
         let mut block = vec![vec![Default::default(); width]; height];
 
-        let ac_huff = comp.get_ac_huff();
-        let dc_huff = comp.get_dc_huff();
-
-        let mut dc = last_dc[idx - 1];
-
         for y in 0..height {
             for x in 0..width {
-                let code = decode_dct(&*dc_huff, dc, &*ac_huff, bs);
-                dc = code[0];
+                let code = codes[y][x];
 
                 let arr = Array2::from_shape_vec((8, 8), code.to_vec()).unwrap();
                 let dqt = &*comp.get_dqt().borrow_mut().table.clone();
@@ -132,7 +179,6 @@ pub fn decode_blocks(
                 block[y][x] = dct.idct2d(result);
             }
         }
-        last_dc[idx - 1] = dc;
         mcu.push(Block {
             width: width,
             height: height,
@@ -148,3 +194,60 @@ pub fn decode_blocks(
         },
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{decode::OutputFormat, decode_jpeg_bytes, try_read_header};
+
+    // 把 SOF 和 SOS 中的分量 id 都加 1
+    fn renumber(mut data: Vec<u8>) -> Vec<u8> {
+        let mut pos = 2;
+        loop {
+            let marker = data[pos + 1];
+            let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            match marker {
+                0xC0 => {
+                    for i in 0..data[pos + 9] as usize {
+                        data[pos + 10 + i * 3] += 1;
+                    }
+                }
+                0xDA => {
+                    for i in 0..data[pos + 4] as usize {
+                        data[pos + 5 + i * 2] += 1;
+                    }
+                    return data;
+                }
+                _ => {}
+            }
+            pos += 2 + len;
+        }
+    }
+
+    #[test]
+    fn test_sorted_components() {
+        // 测试图像的分量 id 是 0、1、2
+        let data = std::fs::read("tests/data/baseline_420.jpg").unwrap();
+        let mut reader = BufReader::new(Cursor::new(&data));
+        let header = try_read_header(&mut reader).unwrap();
+        let comps = Component::new(
+            &header.frame,
+            header.dqt_map,
+            header.dc_map,
+            header.ac_map,
+            header.scan,
+        )
+        .unwrap();
+        let sorted = sorted_components(&comps);
+        let ids: Vec<u8> = sorted.iter().map(|comp| comp.get_id()).collect();
+        assert_eq!(ids, [0, 1, 2]);
+        assert_eq!((sorted[0].get_factor_x(), sorted[0].get_factor_y()), (2, 2));
+
+        // 解码结果和从 1 开始编号的文件相同
+        let expected = decode_jpeg_bytes(&renumber(data.clone()), OutputFormat::Rgb8).unwrap();
+        let actual = decode_jpeg_bytes(&data, OutputFormat::Rgb8).unwrap();
+        assert_eq!(actual, expected);
+    }
+}
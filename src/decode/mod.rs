@@ -1,170 +1,486 @@
-use std::{error, fs::File, io::BufReader, rc::Rc, vec};
+use std::{
+    error,
+    io::{BufReader, Read, Seek},
+    rc::Rc,
+    vec,
+};
 
-use chroma::ycbcr2rgb;
+use chroma::{gray2luma, gray2rgb, ycbcr2rgb};
 use dct::DCT;
+pub use format::{plane_dimensions, OutputFormat};
 use mcu::decode_blocks;
 use rustc_hash::FxHashMap;
+use upsample::upsample;
 
 use crate::{
     bitstream::BitStream,
     component::{frame::Frame, Component},
+    JpegErrorType,
 };
 
 mod chroma;
+pub mod coefficient;
 pub mod dct;
+mod format;
 pub mod mcu;
+pub mod push;
+pub mod scanline;
+mod upsample;
 
-pub struct Coordinate {
-    pub x: usize,
-    pub y: usize,
-}
-
-pub fn decode_mcu(
+pub fn decode_mcu<R: Read + Seek>(
     last_dc: Vec<isize>,
     comps: FxHashMap<u8, Rc<Component>>,
-    bs: &mut BitStream<BufReader<File>>,
+    bs: &mut BitStream<BufReader<R>>,
     dct: &DCT,
+    format: OutputFormat,
 ) -> Result<(Vec<isize>, Vec<u8>), Box<dyn error::Error>> {
-    let (_last_dc, mcu) = decode_blocks(last_dc, comps, bs, dct).unwrap();
-
-    let block_y = &mcu.data[0];
-    let block_cb = &mcu.data[1];
-    let block_cr = &mcu.data[2];
-    let mut buffer = vec![0; (mcu.width * 8) * (mcu.height * 8) * 4];
+    // 平面格式的各分量尺寸不同，不能按 MCU 输出
+    if format == OutputFormat::YCbCr {
+        return Err(JpegErrorType::UnsupportedFormat(format).to_string().into());
+    }
+    let (_last_dc, mcu) = decode_blocks(last_dc, comps, bs, dct)?;
 
-    for y0 in 0..mcu.height {
-        for x0 in 0..mcu.width {
-            let block_base = ((y0 * (8 * mcu.width) * 8) + (x0 * 8)) * 4;
+    // 先把各分量放大到 MCU 的尺寸，再逐行做颜色转换
+    // 只输出灰度时不需要色度分量
+    let planes: Vec<Vec<f32>> = match format {
+        OutputFormat::Gray8 => vec![upsample(&mcu.data[0], mcu.width, mcu.height)],
+        _ => mcu
+            .data
+            .iter()
+            .map(|block| upsample(block, mcu.width, mcu.height))
+            .collect(),
+    };
 
-            let idx_y = Coordinate {
-                x: x0 % block_y.width,
-                y: y0 % block_y.height,
-            };
-            let idx_cb = Coordinate {
-                x: x0 % block_cb.width,
-                y: y0 % block_cb.height,
-            };
-            let idx_cr = Coordinate {
-                x: x0 % block_cr.width,
-                y: y0 % block_cr.height,
-            };
+    let width = mcu.width * 8;
+    let height = mcu.height * 8;
+    let bpp = format.bytes_per_pixel();
+    let mut buffer = vec![0; width * height * bpp];
+    let mut rgba = [0u8; 32];
 
-            let y = &block_y.data[idx_y.y][idx_y.x];
-            let cb = &block_cb.data[idx_cb.y][idx_cb.x];
-            let cr = &block_cr.data[idx_cr.y][idx_cr.x];
-
-            let y_factor_x = mcu.width / block_y.width;
-            let y_factor_y = mcu.height / block_y.height;
-            let cb_factor_x = mcu.width / block_cb.width;
-            let cb_factor_y = mcu.height / block_cb.height;
-            let cr_factor_x = mcu.width / block_cr.width;
-            let cr_factor_y = mcu.height / block_cr.height;
-
-            let y_width = 8 / y_factor_x;
-            let y_height = 8 / y_factor_y;
-            let cb_width = 8 / cb_factor_x;
-            let cb_height = 8 / cb_factor_y;
-            let cr_width = 8 / cr_factor_x;
-            let cr_height = 8 / cr_factor_y;
-
-            let y_off_x = (x0 * y_width) % 8;
-            let y_off_y = y0 * y_height;
-            let cb_off_x = (x0 * cb_width) % 8;
-            let cb_off_y = y0 * cb_height;
-            let cr_off_x = (x0 * cr_width) % 8;
-            let cr_off_y = y0 * cr_height;
-
-            for y1 in 0..8 {
-                let offset = block_base + (y1 * mcu.width * 8) * 4;
-                ycbcr2rgb(
-                    &y[(y_off_y + y1 / y_factor_y) % 8][y_off_x..(y_off_x + y_width)], y_factor_x,
-                    &cb[(cb_off_y + y1 / cb_factor_y) % 8][cb_off_x..(cb_off_x + cb_width)], cb_factor_x,
-                    &cr[(cr_off_y + y1 / cr_factor_y) % 8][cr_off_x..(cr_off_x + cr_width)], cr_factor_x,
-                    &mut buffer[offset..(offset + 32)]);
-                // for x1 in 0..8 {
-                //     let (r, g, b) = ycbcr2rgb(
-                //         y[(y_off_y + y1 / y_factor_y) % 8][(y_off_x + x1 / y_factor_x) % 8],
-                //         cb[(cb_off_y + y1 / cb_factor_y) % 8][(cb_off_x + x1 / cb_factor_x) % 8],
-                //         cr[(cr_off_y + y1 / cr_factor_y) % 8][(cr_off_x + x1 / cr_factor_x) % 8],
-                //     );
-                //     buffer[offset + 0] = r;
-                //     buffer[offset + 1] = g;
-                //     buffer[offset + 2] = b;
-                //     buffer[offset + 3] = 0xff;
-                //     offset += 4;
-                // }
+    for y in 0..height {
+        for x in (0..width).step_by(8) {
+            let idx = y * width + x;
+            let out = &mut buffer[(idx * bpp)..((idx + 8) * bpp)];
+            if let OutputFormat::Gray8 = format {
+                gray2luma(&planes[0][idx..(idx + 8)], out);
+                continue;
+            }
+            match planes.len() {
+                1 => gray2rgb(&planes[0][idx..(idx + 8)], &mut rgba),
+                3 => ycbcr2rgb(
+                    &planes[0][idx..(idx + 8)],
+                    &planes[1][idx..(idx + 8)],
+                    &planes[2][idx..(idx + 8)],
+                    &mut rgba,
+                ),
+                n => return Err(format!("不支持的分量数: {}", n).into()),
             }
+            format.pack_rgba(&rgba, out).map_err(|e| e.to_string())?;
         }
     }
     Ok((_last_dc, buffer))
 }
 
-pub fn decode_image(
+fn mcu_count(frame: &Frame) -> (usize, usize) {
+    let (max_x, max_y) = frame.max_factors();
+    (
+        (frame.get_width() as usize).div_ceil(max_x * 8),
+        (frame.get_height() as usize).div_ceil(max_y * 8),
+    )
+}
+
+// 每解码 restart_interval 个 MCU 后跳过 RSTn 标记并重置 DC 预测值
+fn check_restart<R: Read + Seek>(
+    bs: &mut BitStream<BufReader<R>>,
+    restart_interval: Option<u16>,
+    cnt: &mut u16,
+    last_dc: &mut [isize],
+) -> Result<(), Box<dyn error::Error>> {
+    if let Some(ri) = restart_interval {
+        *cnt += 1;
+        if *cnt >= ri {
+            bs.align_byte();
+            bs.read(8)
+                .map_err(|e| format!("读取 RST 标记失败: {:?}", e))?;
+            last_dc.fill(0);
+            *cnt = 0;
+        }
+    }
+    Ok(())
+}
+
+// 按 MCU 行解码为交错的像素格式，在行之间保留 DC 预测值和复位标记的计数
+pub struct McuRowDecoder {
+    comps: FxHashMap<u8, Rc<Component>>,
+    dct: DCT,
+    format: OutputFormat,
+    restart_interval: Option<u16>,
+    last_dc: Vec<isize>,
+    cnt: u16,
+    width: usize,
+    mcu_width: usize,
+    mcu_height: usize,
+    x_cnt: usize,
+    // 还没有解码的 MCU 个数，最后一个 MCU 之后没有复位标记
+    remaining: usize,
+}
+
+// McuRowDecoder 在两个 MCU 之间的状态
+#[derive(Clone)]
+pub struct McuRowState {
+    last_dc: Vec<isize>,
+    cnt: u16,
+    remaining: usize,
+}
+
+impl McuRowDecoder {
+    pub fn new(
+        frame: &Frame,
+        comps: FxHashMap<u8, Rc<Component>>,
+        restart_interval: Option<u16>,
+        dct: DCT,
+        format: OutputFormat,
+    ) -> Self {
+        let (max_x, max_y) = frame.max_factors();
+        let (x_cnt, y_cnt) = mcu_count(frame);
+        Self {
+            last_dc: vec![0isize; comps.len()],
+            comps,
+            dct,
+            format,
+            restart_interval,
+            cnt: 0,
+            width: frame.get_width() as usize,
+            mcu_width: max_x * 8,
+            mcu_height: max_y * 8,
+            x_cnt,
+            remaining: x_cnt * y_cnt,
+        }
+    }
+
+    // 一行 MCU 覆盖的像素行数
+    pub fn mcu_height(&self) -> usize {
+        self.mcu_height
+    }
+
+    // 一行 MCU 的个数
+    pub fn mcus_per_row(&self) -> usize {
+        self.x_cnt
+    }
+
+    // DC 预测值和复位标记的计数，解码失败后用 set_state 回到 MCU 开始前的状态
+    pub fn state(&self) -> McuRowState {
+        McuRowState {
+            last_dc: self.last_dc.clone(),
+            cnt: self.cnt,
+            remaining: self.remaining,
+        }
+    }
+
+    pub fn set_state(&mut self, state: McuRowState) {
+        self.last_dc = state.last_dc;
+        self.cnt = state.cnt;
+        self.remaining = state.remaining;
+    }
+
+    // 解码下一行 MCU，out 的行数可以少于 MCU 高度（图像底边），多出的部分丢弃
+    pub fn decode_row<R: Read + Seek>(
+        &mut self,
+        bs: &mut BitStream<BufReader<R>>,
+        out: &mut [u8],
+    ) -> Result<(), Box<dyn error::Error>> {
+        for x1 in 0..self.x_cnt {
+            self.decode_mcu_at(bs, x1, out)?;
+        }
+        Ok(())
+    }
+
+    // 解码当前行的第 x1 个 MCU，写入 out 中对应的列
+    pub fn decode_mcu_at<R: Read + Seek>(
+        &mut self,
+        bs: &mut BitStream<BufReader<R>>,
+        x1: usize,
+        out: &mut [u8],
+    ) -> Result<(), Box<dyn error::Error>> {
+        let bpp = self.format.bytes_per_pixel();
+        let stride = self.width * bpp;
+        let rows = (out.len() / stride).min(self.mcu_height);
+
+        let mcu;
+        (self.last_dc, mcu) = decode_mcu(
+            std::mem::take(&mut self.last_dc),
+            self.comps.clone(),
+            bs,
+            &self.dct,
+            self.format,
+        )?;
+        // 图像边缘的 MCU 只复制图像范围内的部分
+        let copy_width = self.mcu_width.min(self.width - x1 * self.mcu_width) * bpp;
+        for y2 in 0..rows {
+            let offset1 = y2 * stride + x1 * self.mcu_width * bpp;
+            let offset2 = y2 * self.mcu_width * bpp;
+            out[offset1..(offset1 + copy_width)]
+                .copy_from_slice(&mcu[offset2..(offset2 + copy_width)]);
+        }
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining == 0 {
+            return Ok(());
+        }
+        check_restart(bs, self.restart_interval, &mut self.cnt, &mut self.last_dc)
+    }
+}
+
+pub fn decode_image<R: Read + Seek>(
     frame: &Frame,
     comps: FxHashMap<u8, Rc<Component>>,
-    bs: &mut BitStream<BufReader<File>>,
+    bs: &mut BitStream<BufReader<R>>,
     restart_interval: Option<u16>,
     dct: DCT,
+    format: OutputFormat,
 ) -> Result<Vec<u8>, Box<dyn error::Error>> {
-    let mut last_dc = vec![0isize; 3];
+    if let OutputFormat::YCbCr = format {
+        return decode_planar(frame, comps, bs, restart_interval, dct);
+    }
 
     let width = frame.get_width() as usize;
     let height = frame.get_height() as usize;
+    let stride = width * format.bytes_per_pixel();
 
-    let mut buffer = vec![Default::default(); width * height * 4];
+    let mut buffer = vec![Default::default(); stride * height];
 
-    let mut mcu_width = 0;
-    let mut mcu_height = 0;
-    for (_, comp) in frame.components.iter() {
-        if comp.get_factor_x() as usize > mcu_width {
-            mcu_width = comp.get_factor_x() as usize;
+    let mut rows = McuRowDecoder::new(frame, comps, restart_interval, dct, format);
+    for band in buffer.chunks_mut(rows.mcu_height() * stride) {
+        rows.decode_row(bs, band)?;
+    }
+
+    Ok(buffer)
+}
+
+// decode_dc 中各分量平面 (f32) 和输出图像占用的字节数
+pub fn reduced_bytes(frame: &Frame) -> usize {
+    let (max_x, max_y) = frame.max_factors();
+    let (x_cnt, y_cnt) = mcu_count(frame);
+    let plane = (x_cnt * max_x).saturating_mul(y_cnt * max_y).saturating_mul(4);
+    let width = (frame.get_width() as usize).div_ceil(8);
+    let height = (frame.get_height() as usize).div_ceil(8);
+    plane
+        .saturating_mul(frame.components.len())
+        .saturating_add(width * height * 4)
+}
+
+// 只解码每个块的 DC 系数（块的平均值），得到宽高为原图 1/8 的 RGBA 图像，不需要 IDCT
+pub fn decode_dc<R: Read + Seek>(
+    frame: &Frame,
+    comps: FxHashMap<u8, Rc<Component>>,
+    bs: &mut BitStream<BufReader<R>>,
+    restart_interval: Option<u16>,
+) -> Result<(usize, usize, Vec<u8>), Box<dyn error::Error>> {
+    let (max_x, max_y) = frame.max_factors();
+    let (x_cnt, y_cnt) = mcu_count(frame);
+    let sorted = mcu::sorted_components(&comps);
+    if sorted.len() != 1 && sorted.len() != 3 {
+        return Err(format!("不支持的分量数: {}", sorted.len()).into());
+    }
+
+    // 每个亮度块对应一个像素，色度块按采样因子放大
+    let plane_width = x_cnt * max_x;
+    let mut planes = vec![vec![0f32; plane_width * y_cnt * max_y]; sorted.len()];
+    let mut last_dc = vec![0isize; comps.len()];
+    let mut cnt = 0;
+
+    for y1 in 0..y_cnt {
+        for x1 in 0..x_cnt {
+            let codes;
+            (last_dc, codes) = mcu::decode_mcu_coefficients(last_dc, &comps, bs)?;
+            for ((comp, blocks), plane) in sorted.iter().zip(codes).zip(planes.iter_mut()) {
+                let sx = max_x / comp.get_factor_x() as usize;
+                let sy = max_y / comp.get_factor_y() as usize;
+                let q = comp.get_dqt().table[[0, 0]];
+                for (by, row) in blocks.iter().enumerate() {
+                    for (bx, code) in row.iter().enumerate() {
+                        let value = (code[0] * q) as f32 / 8.0;
+                        for py in 0..sy {
+                            let y = y1 * max_y + by * sy + py;
+                            let x = x1 * max_x + bx * sx;
+                            plane[y * plane_width + x..][..sx].fill(value);
+                        }
+                    }
+                }
+            }
+            if y1 + 1 < y_cnt || x1 + 1 < x_cnt {
+                check_restart(bs, restart_interval, &mut cnt, &mut last_dc)?;
+            }
         }
-        if comp.get_factor_y() as usize > mcu_height {
-            mcu_height = comp.get_factor_y() as usize;
+    }
+
+    let width = (frame.get_width() as usize).div_ceil(8);
+    let height = (frame.get_height() as usize).div_ceil(8);
+    let mut buffer = vec![0; width * height * 4];
+    let mut rgba = [0u8; 32];
+    for y in 0..height {
+        for x in (0..width).step_by(8) {
+            // 颜色转换每次处理 8 个像素，行尾不足的部分补 0
+            let n = 8.min(width - x);
+            let lane = |plane: &[f32]| {
+                let mut v = [0f32; 8];
+                v[..n].copy_from_slice(&plane[y * plane_width + x..][..n]);
+                v
+            };
+            match &planes[..] {
+                [l] => gray2rgb(&lane(l), &mut rgba),
+                [l, cb, cr] => ycbcr2rgb(&lane(l), &lane(cb), &lane(cr), &mut rgba),
+                _ => unreachable!(),
+            }
+            buffer[(y * width + x) * 4..][..n * 4].copy_from_slice(&rgba[..n * 4]);
         }
     }
-    mcu_width *= 8;
-    mcu_height *= 8;
+    Ok((width, height, buffer))
+}
+
+// 不做上采样和颜色转换，直接把各分量的样本写入各自的平面
+fn decode_planar<R: Read + Seek>(
+    frame: &Frame,
+    comps: FxHashMap<u8, Rc<Component>>,
+    bs: &mut BitStream<BufReader<R>>,
+    restart_interval: Option<u16>,
+    dct: DCT,
+) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    let mut last_dc = vec![0isize; comps.len()];
 
-    let x_cnt = (width + mcu_width - 1) / mcu_width;
-    let y_cnt = (height + mcu_height - 1) / mcu_height;
+    // 各平面直接写入同一个缓冲区，不再另外拼接
+    let dims = plane_dimensions(frame);
+    let mut buffer = vec![0; dims.iter().map(|(w, h)| w * h).sum()];
+    let mut planes: Vec<&mut [u8]> = Vec::with_capacity(dims.len());
+    let mut rest = &mut buffer[..];
+    for (w, h) in &dims {
+        let (plane, tail) = rest.split_at_mut(w * h);
+        planes.push(plane);
+        rest = tail;
+    }
+    let (x_cnt, y_cnt) = mcu_count(frame);
 
     let mut cnt = 0;
 
     for y1 in 0..y_cnt {
         for x1 in 0..x_cnt {
             let mcu;
-            (last_dc, mcu) = decode_mcu(last_dc, comps.clone(), bs, &dct)?;
-            let mcu_base = ((y1 * mcu_height * width) + (x1 * mcu_width)) * 4;
-            for y2 in 0..mcu_height {
-                if y1 * mcu_height + y2 >= height {
-                    break;
-                }
-                let mut offset1 = mcu_base + y2 * width * 4;
-                let mut offset2 = y2 * mcu_width * 4;
-                for x2 in 0..mcu_width {
-                    if x1 * mcu_width + x2 >= width {
-                        break;
+            (last_dc, mcu) = decode_blocks(last_dc, comps.clone(), bs, &dct)?;
+            for ((block, plane), (pw, ph)) in mcu.data.iter().zip(planes.iter_mut()).zip(&dims) {
+                for by in 0..block.height {
+                    for bx in 0..block.width {
+                        let px = (x1 * block.width + bx) * 8;
+                        let py = (y1 * block.height + by) * 8;
+                        for (y, row) in block.data[by][bx].iter().enumerate() {
+                            if py + y >= *ph {
+                                break;
+                            }
+                            for (x, sample) in row.iter().enumerate() {
+                                if px + x >= *pw {
+                                    break;
+                                }
+                                plane[(py + y) * pw + px + x] = (128.0 + sample).round() as u8;
+                            }
+                        }
                     }
-                    buffer[offset1 + 0] = mcu[offset2 + 0];
-                    buffer[offset1 + 1] = mcu[offset2 + 1];
-                    buffer[offset1 + 2] = mcu[offset2 + 2];
-                    buffer[offset1 + 3] = mcu[offset2 + 3];
-                    offset1 += 4;
-                    offset2 += 4;
                 }
             }
-            if let Some(ri) = restart_interval {
-                cnt += 1;
-                if cnt >= ri {
-                    bs.align_byte();
-                    bs.read(8).unwrap();
-                    last_dc = [0].repeat(3);
-                    cnt = 0;
-                }
+            if y1 + 1 < y_cnt || x1 + 1 < x_cnt {
+                check_restart(bs, restart_interval, &mut cnt, &mut last_dc)?;
             }
         }
     }
 
     Ok(buffer)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, SeekFrom};
+
+    use super::*;
+    use crate::{
+        decode_jpeg, encode::write_jpeg, get_jpeg_coefficients, open_jpeg, try_read_header,
+    };
+
+    #[test]
+    fn test_decode_mcu_formats() {
+        let decode = |format| {
+            let (mut reader, header) = open_jpeg("tests/data/baseline_420.jpg").unwrap();
+            reader.seek(SeekFrom::Start(header.scan_start)).unwrap();
+            let comps = Component::new(
+                &header.frame,
+                header.dqt_map,
+                header.dc_map,
+                header.ac_map,
+                header.scan,
+            )
+            .unwrap();
+            let mut bs = BitStream::new(&mut reader);
+            decode_mcu(vec![0; 3], comps, &mut bs, &DCT::new(), format).map(|(_, mcu)| mcu)
+        };
+        // 4:2:0 的 MCU 是 16x16
+        assert_eq!(decode(OutputFormat::Gray8).unwrap().len(), 16 * 16);
+        let rgba = decode(OutputFormat::Rgba8).unwrap();
+        let bgra = decode(OutputFormat::Bgra8).unwrap();
+        assert_eq!(bgra.len(), 16 * 16 * 4);
+        for (b, r) in bgra.chunks_exact(4).zip(rgba.chunks_exact(4)) {
+            assert_eq!(b, [r[2], r[1], r[0], r[3]]);
+        }
+        assert!(decode(OutputFormat::YCbCr).is_err());
+    }
+
+    #[test]
+    fn test_planar_layout() {
+        // 各平面按分量 id 依次存放，保持各自的采样分辨率，Y 平面和 Gray8 输出相同
+        for name in ["baseline_420", "odd_422", "grayscale", "grayscale_odd"] {
+            let path = format!("tests/data/{}.jpg", name);
+            let (_, header) = open_jpeg(&path).unwrap();
+            let dims = plane_dimensions(&header.frame);
+            let (width, height, gray) = decode_jpeg(path.clone(), OutputFormat::Gray8).unwrap();
+            let (_, _, planes) = decode_jpeg(path, OutputFormat::YCbCr).unwrap();
+            assert_eq!(planes.len(), dims.iter().map(|(w, h)| w * h).sum::<usize>());
+            assert_eq!(dims[0], (width, height));
+            assert_eq!(planes[..width * height], gray, "{}", name);
+        }
+    }
+
+    // 6 个 MCU，每 3 个一个复位标记，最后一个 MCU 之后紧跟 EOI
+    pub(super) fn restart_at_end() -> Vec<u8> {
+        let coefs = get_jpeg_coefficients("tests/data/baseline_420.jpg".to_string()).unwrap();
+        let mut data = Vec::new();
+        write_jpeg(&mut data, 48, 32, &coefs, &[], Some(3)).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_planar_restart_at_end() {
+        let data = restart_at_end();
+        let mut reader = BufReader::new(Cursor::new(&data));
+        let header = try_read_header(&mut reader).unwrap();
+        reader.seek(SeekFrom::Start(header.scan_start)).unwrap();
+        let comps = Component::new(
+            &header.frame,
+            header.dqt_map,
+            header.dc_map,
+            header.ac_map,
+            header.scan,
+        )
+        .unwrap();
+        let mut bs = BitStream::new(&mut reader);
+        let planes = decode_planar(
+            &header.frame,
+            comps,
+            &mut bs,
+            header.restart_interval,
+            DCT::new(),
+        );
+        let (_, _, expected) = decode_jpeg(
+            "tests/data/baseline_420.jpg".to_string(),
+            OutputFormat::YCbCr,
+        )
+        .unwrap();
+        assert_eq!(planes.unwrap(), expected);
+        // EOI 没有被当作复位标记读掉
+        assert_eq!(reader.stream_position().unwrap(), data.len() as u64 - 2);
+    }
+}
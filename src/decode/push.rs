@@ -0,0 +1,335 @@
+use std::{
+    cell::Cell,
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    bitstream::BitStream,
+    component::Component,
+    limits::{Limits, Usage},
+    segment::{Segment, SegmentErrorKind, SegmentType},
+    HeaderBuilder, JpegErrorType, JpegHeader,
+};
+
+use super::{dct::DCT, McuRowDecoder, OutputFormat};
+
+// feed 返回的事件，按在文件中出现的顺序排列
+#[derive(Debug)]
+pub enum PushEvent {
+    // 解析完 SOS 之前的所有段，之后开始输出像素
+    Header { width: usize, height: usize },
+    // APPn 或 COM 段
+    Metadata(Segment),
+    // 从第 first 行开始的若干整行像素
+    Rows { first: usize, pixels: Vec<u8> },
+    // 一次扫描的熵编码数据结束
+    ScanComplete,
+    // 读到 EOI
+    Done,
+}
+
+enum State {
+    // 等待完整的标记段
+    Markers,
+    // 逐个 MCU 解码熵编码数据
+    Entropy,
+    // 扫描结束后寻找下一个标记
+    AfterScan,
+    Done,
+}
+
+// 已经收到的数据，读到末尾时做标记，用来区分数据还没到达和数据损坏
+struct PartialReader<'a> {
+    inner: Cursor<&'a [u8]>,
+    exhausted: &'a Cell<bool>,
+}
+
+impl Read for PartialReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.exhausted.set(true);
+        }
+        Ok(n)
+    }
+}
+
+impl Seek for PartialReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+struct ScanState {
+    rows: McuRowDecoder,
+    // 上次停下时当前字节中未读完的位
+    bits: (u8, usize),
+    // 当前 MCU 行的像素
+    buffer: Vec<u8>,
+    // 当前 MCU 行中下一个要解码的 MCU
+    x: usize,
+    // 已输出的行数
+    line: usize,
+    height: usize,
+    stride: usize,
+}
+
+// 推送式解码器，数据分块到达时逐块调用 feed，不需要可以 seek 的完整文件
+// 数据不足时停在标记段或 MCU 的开头，下次 feed 时从那里继续
+pub struct PushDecoder {
+    format: OutputFormat,
+    limits: Limits,
+    usage: Usage,
+    state: State,
+    // 尚未处理的数据
+    data: Vec<u8>,
+    // data[0] 在文件中的偏移
+    offset: u64,
+    builder: Option<HeaderBuilder>,
+    scan: Option<ScanState>,
+}
+
+impl PushDecoder {
+    // 平面格式 (YCbCr) 的各分量分辨率不同，不能逐行输出
+    pub fn new(format: OutputFormat, limits: Limits) -> Result<Self, JpegErrorType> {
+        if format == OutputFormat::YCbCr {
+            return Err(JpegErrorType::UnsupportedFormat(format));
+        }
+        Ok(Self {
+            format,
+            limits,
+            usage: Usage::default(),
+            state: State::Markers,
+            data: Vec::new(),
+            offset: 0,
+            builder: Some(HeaderBuilder::new(limits)),
+            scan: None,
+        })
+    }
+
+    // 追加收到的数据，返回这些数据能产生的所有事件
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<PushEvent>, JpegErrorType> {
+        self.data.extend_from_slice(data);
+        let mut events = Vec::new();
+        loop {
+            let progressed = match self.state {
+                State::Markers => self.parse_marker(&mut events)?,
+                State::Entropy => self.decode_entropy(&mut events)?,
+                State::AfterScan => self.find_marker(),
+                State::Done => false,
+            };
+            if !progressed {
+                break;
+            }
+        }
+        Ok(events)
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    // 数据全部送完后调用，没有读到 EOI 时返回 Truncated
+    pub fn finish(&self) -> Result<(), JpegErrorType> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err(JpegErrorType::Truncated)
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.data.drain(..n);
+        self.offset += n as u64;
+    }
+
+    fn parse_marker(&mut self, events: &mut Vec<PushEvent>) -> Result<bool, JpegErrorType> {
+        // 标记前面可以有任意个 0xFF 填充字节
+        let fill = self.data.iter().take_while(|&&b| b == 0xFF).count();
+        if fill > 1 {
+            self.consume(fill - 1);
+        }
+        if self.data.len() < 2 {
+            return Ok(false);
+        }
+        if self.data[0] != 0xFF {
+            return Err(SegmentErrorKind::InvalidSegment.into());
+        }
+        let segment_type =
+            SegmentType::from_marker(self.data[1]).ok_or(SegmentErrorKind::InvalidSegmentType)?;
+
+        let (length, data) = match segment_type {
+            SegmentType::SOI | SegmentType::EOI => (0, vec![]),
+            _ => {
+                if self.data.len() < 4 {
+                    return Ok(false);
+                }
+                let length = u16::from_be_bytes([self.data[2], self.data[3]]);
+                if length < 2 {
+                    return Err(SegmentErrorKind::InvalidSegmentLength.into());
+                }
+                if self.data.len() < 2 + length as usize {
+                    return Ok(false);
+                }
+                (length, self.data[4..2 + length as usize].to_vec())
+            }
+        };
+        let segment = Segment {
+            segment_type,
+            offset: self.offset,
+            length,
+            data,
+        };
+        self.consume(2 + length as usize);
+        self.limits
+            .check_segment(&mut self.usage, &segment)
+            .map_err(JpegErrorType::LimitExceeded)?;
+
+        match segment.segment_type {
+            SegmentType::EOI => {
+                if self.scan.is_none() {
+                    return Err(JpegErrorType::Corrupt("缺少 SOS 段".to_string()));
+                }
+                self.data.clear();
+                self.state = State::Done;
+                events.push(PushEvent::Done);
+                return Ok(false);
+            }
+            SegmentType::APPn(_) | SegmentType::COM => {
+                events.push(PushEvent::Metadata(segment.clone()));
+            }
+            _ => {}
+        }
+
+        // 扫描之后的段只检查是否又出现了扫描
+        let Some(builder) = self.builder.as_mut() else {
+            if let SegmentType::SOS(_, _) = segment.segment_type {
+                return Err(JpegErrorType::UnsupportedScanCount(2));
+            }
+            return Ok(true);
+        };
+        let is_scan = matches!(segment.segment_type, SegmentType::SOS(_, _));
+        builder.add(segment)?;
+        if is_scan {
+            let header = self.builder.take().unwrap().finish()?;
+            self.start_scan(header, events)?;
+        }
+        Ok(true)
+    }
+
+    fn start_scan(
+        &mut self,
+        header: JpegHeader,
+        events: &mut Vec<PushEvent>,
+    ) -> Result<(), JpegErrorType> {
+        let frame = header.frame;
+        let width = frame.get_width() as usize;
+        let height = frame.get_height() as usize;
+        let stride = width * self.format.bytes_per_pixel();
+
+        let comps = Component::new(
+            &frame,
+            header.dqt_map,
+            header.dc_map,
+            header.ac_map,
+            header.scan,
+        )
+        .map_err(|e| JpegErrorType::Corrupt(format!("{:?}", e)))?;
+        let rows = McuRowDecoder::new(
+            &frame,
+            comps,
+            header.restart_interval,
+            DCT::new(),
+            self.format,
+        );
+
+        // 只需要一行 MCU 的缓冲区
+        let bytes = stride * rows.mcu_height();
+        self.limits
+            .check_memory(bytes)
+            .map_err(JpegErrorType::LimitExceeded)?;
+
+        events.push(PushEvent::Header { width, height });
+        self.scan = Some(ScanState {
+            rows,
+            bits: (0, 0),
+            buffer: vec![0; bytes],
+            x: 0,
+            line: 0,
+            height,
+            stride,
+        });
+        self.state = State::Entropy;
+        Ok(())
+    }
+
+    fn decode_entropy(&mut self, events: &mut Vec<PushEvent>) -> Result<bool, JpegErrorType> {
+        let scan = self.scan.as_mut().unwrap();
+        let exhausted = Cell::new(false);
+        let mut reader = BufReader::new(PartialReader {
+            inner: Cursor::new(&self.data[..]),
+            exhausted: &exhausted,
+        });
+        let mut bs = BitStream::new(&mut reader);
+        bs.restore(scan.bits);
+
+        // 最后一个完整解码的 MCU 之后的位置
+        let mut consumed = 0;
+        while scan.line < scan.height {
+            let rows = scan.rows.mcu_height().min(scan.height - scan.line);
+            let out = &mut scan.buffer[..rows * scan.stride];
+            let state = scan.rows.state();
+            exhausted.set(false);
+            match scan.rows.decode_mcu_at(&mut bs, scan.x, out) {
+                Ok(()) => {}
+                // 数据不够解码这个 MCU，回到它开始之前的状态等待更多数据
+                Err(_) if exhausted.get() => {
+                    scan.rows.set_state(state);
+                    break;
+                }
+                Err(e) => return Err(JpegErrorType::Corrupt(e.to_string())),
+            }
+            consumed = bs
+                .position()
+                .map_err(|e| JpegErrorType::Corrupt(format!("{:?}", e)))?;
+            scan.bits = bs.save();
+
+            scan.x += 1;
+            if scan.x == scan.rows.mcus_per_row() {
+                events.push(PushEvent::Rows {
+                    first: scan.line,
+                    pixels: out.to_vec(),
+                });
+                scan.x = 0;
+                scan.line += rows;
+            }
+        }
+
+        let complete = scan.line == scan.height;
+        self.consume(consumed);
+        if complete {
+            events.push(PushEvent::ScanComplete);
+            self.state = State::AfterScan;
+        }
+        Ok(complete)
+    }
+
+    fn find_marker(&mut self) -> bool {
+        // 熵编码数据在遇到 RSTn 和 0xFF00 以外的第一个标记时结束
+        let pos = self.data.windows(2).position(|w| {
+            w[0] == 0xFF && w[1] != 0x00 && w[1] != 0xFF && !(0xD0..=0xD7).contains(&w[1])
+        });
+        match pos {
+            Some(pos) => {
+                self.consume(pos);
+                self.state = State::Markers;
+                true
+            }
+            None => {
+                // 最后一个字节可能是标记的开头
+                self.consume(self.data.len().saturating_sub(1));
+                false
+            }
+        }
+    }
+}
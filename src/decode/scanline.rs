@@ -0,0 +1,127 @@
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+use crate::{bitstream::BitStream, component::Component, JpegErrorType, JpegHeader};
+
+use super::{dct::DCT, McuRowDecoder, OutputFormat};
+
+// 逐行输出像素的解码器，只保留一行 MCU 的输出缓冲区，适合处理很大的图像
+// reader 和 header 来自 open_jpeg，每次 read_scanlines 按顺序输出若干整行
+pub struct ScanlineDecoder<'a, R: Read + Seek> {
+    bs: BitStream<'a, BufReader<R>>,
+    rows: McuRowDecoder,
+    width: usize,
+    height: usize,
+    stride: usize,
+    // 当前 MCU 行解码出的像素，buffer_pos 之前的行已经输出
+    buffer: Vec<u8>,
+    buffer_rows: usize,
+    buffer_pos: usize,
+    // 已输出的行数
+    line: usize,
+}
+
+impl<'a, R: Read + Seek> ScanlineDecoder<'a, R> {
+    // 平面格式 (YCbCr) 的各分量分辨率不同，不能逐行输出
+    pub fn new(
+        reader: &'a mut BufReader<R>,
+        header: JpegHeader,
+        format: OutputFormat,
+    ) -> Result<Self, JpegErrorType> {
+        if format == OutputFormat::YCbCr {
+            return Err(JpegErrorType::UnsupportedFormat(format));
+        }
+        let frame = header.frame;
+        let width = frame.get_width() as usize;
+        let height = frame.get_height() as usize;
+        let stride = width * format.bytes_per_pixel();
+
+        let comps = Component::new(
+            &frame,
+            header.dqt_map,
+            header.dc_map,
+            header.ac_map,
+            header.scan,
+        )
+        .map_err(|e| JpegErrorType::Corrupt(format!("{:?}", e)))?;
+        let rows = McuRowDecoder::new(&frame, comps, header.restart_interval, DCT::new(), format);
+
+        // 只需要一行 MCU 的缓冲区
+        let bytes = stride * rows.mcu_height();
+        header
+            .limits
+            .check_memory(bytes)
+            .map_err(JpegErrorType::LimitExceeded)?;
+
+        reader
+            .seek(SeekFrom::Start(header.scan_start))
+            .map_err(JpegErrorType::IOError)?;
+
+        Ok(Self {
+            bs: BitStream::new(reader),
+            rows,
+            width,
+            height,
+            stride,
+            buffer: vec![0; bytes],
+            buffer_rows: 0,
+            buffer_pos: 0,
+            line: 0,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // 一行像素的字节数
+    pub fn bytes_per_line(&self) -> usize {
+        self.stride
+    }
+
+    // 已经输出的行数，等于 height() 时解码结束
+    pub fn output_line(&self) -> usize {
+        self.line
+    }
+
+    // 按顺序填充尽可能多的整行像素，返回写入的行数，全部输出后返回 0
+    // buf 至少要能放下一行像素，否则返回 InvalidInput
+    pub fn read_scanlines(&mut self, buf: &mut [u8]) -> Result<usize, JpegErrorType> {
+        if buf.len() < self.stride {
+            return Err(JpegErrorType::IOError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "缓冲区小于一行像素",
+            )));
+        }
+        let mut count = 0;
+        for line in buf.chunks_exact_mut(self.stride) {
+            if self.line == self.height {
+                break;
+            }
+            if self.buffer_pos == self.buffer_rows {
+                self.fill_buffer()?;
+            }
+            let offset = self.buffer_pos * self.stride;
+            line.copy_from_slice(&self.buffer[offset..offset + self.stride]);
+            self.buffer_pos += 1;
+            self.line += 1;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn fill_buffer(&mut self) -> Result<(), JpegErrorType> {
+        // 最后一行 MCU 可能超出图像底边
+        let rows = self.rows.mcu_height().min(self.height - self.line);
+        let bytes = rows * self.stride;
+        self.rows
+            .decode_row(&mut self.bs, &mut self.buffer[..bytes])
+            .map_err(|e| JpegErrorType::Corrupt(e.to_string()))?;
+        self.buffer_rows = rows;
+        self.buffer_pos = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use super::mcu::Block;
+
+// 把一个分量的所有块按采样因子放大到整个 MCU 的尺寸
+// 采样点 (x, y) 对应分量中的 (x * h / h_max, y * v / v_max)，
+// 因此对任意 1~4 的采样因子组合都成立（包括 3 这样不能整除 8 的情况）
+pub fn upsample(block: &Block, max_x: usize, max_y: usize) -> Vec<f32> {
+    let width = max_x * 8;
+    let height = max_y * 8;
+    let mut plane = vec![0f32; width * height];
+
+    for y in 0..height {
+        let sy = y * block.height / max_y;
+        let row = &block.data[sy / 8];
+        let line = &mut plane[(y * width)..((y + 1) * width)];
+        if block.width == max_x {
+            for bx in 0..block.width {
+                line[(bx * 8)..(bx * 8 + 8)].copy_from_slice(&row[bx][sy % 8]);
+            }
+        } else {
+            for (x, pixel) in line.iter_mut().enumerate() {
+                let sx = x * block.width / max_x;
+                *pixel = row[sx / 8][sy % 8][sx % 8];
+            }
+        }
+    }
+    plane
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_block(width: usize, height: usize) -> Block {
+        let mut data = vec![vec![[[0f32; 8]; 8]; width]; height];
+        for (by, row) in data.iter_mut().enumerate() {
+            for (bx, block) in row.iter_mut().enumerate() {
+                for (y, line) in block.iter_mut().enumerate() {
+                    for (x, v) in line.iter_mut().enumerate() {
+                        *v = ((by * 8 + y) * 100 + bx * 8 + x) as f32;
+                    }
+                }
+            }
+        }
+        Block {
+            width,
+            height,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_upsample_full() {
+        let block = make_block(2, 2);
+        let plane = upsample(&block, 2, 2);
+        for y in 0..16 {
+            for x in 0..16 {
+                assert_eq!(plane[y * 16 + x], (y * 100 + x) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_upsample_411() {
+        // 4:1:1 的色度分量: H=1，亮度 H=4
+        let block = make_block(1, 1);
+        let plane = upsample(&block, 4, 1);
+        for y in 0..8 {
+            for x in 0..32 {
+                assert_eq!(plane[y * 32 + x], (y * 100 + x / 4) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_upsample_440() {
+        // 4:4:0 只在垂直方向下采样
+        let block = make_block(1, 1);
+        let plane = upsample(&block, 1, 2);
+        for y in 0..16 {
+            for x in 0..8 {
+                assert_eq!(plane[y * 8 + x], (y / 2 * 100 + x) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_upsample_non_integral() {
+        // 亮度 H=3，色度 H=2
+        let block = make_block(2, 1);
+        let plane = upsample(&block, 3, 1);
+        for (x, &v) in plane.iter().enumerate().take(24) {
+            assert_eq!(v, (x * 2 / 3) as f32);
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use std::{fmt::Debug, fs::File, io::BufReader};
+use std::{
+    fmt::Debug,
+    io::{BufReader, Read, Seek},
+};
 
 use rustc_hash::FxHashMap;
 
@@ -41,6 +44,7 @@ pub enum HuffmanErrorType {
     BitStreamError(BitStreamErrorType),
     DecodeError(usize /* code */),
     TypeConversionError,
+    InvalidLength(usize),
 }
 
 impl Huffman {
@@ -48,6 +52,14 @@ impl Huffman {
         let mut length = [0; 16];
         let mut val: Vec<Vec<u8>> = Vec::with_capacity(16);
         let mut off = offset + 17;
+        // 码长计数和符号都必须在段内，符号最多 256 个
+        if data.len() < off {
+            return Err(HuffmanErrorType::InvalidLength(data.len()));
+        }
+        let total: usize = data[offset + 1..off].iter().map(|&n| n as usize).sum();
+        if total > 256 || data.len() < off + total {
+            return Err(HuffmanErrorType::InvalidLength(total));
+        }
         let mut code = 0usize;
         let mut map = FxHashMap::default();
         let mut table = HuffmanTable {
@@ -60,7 +72,7 @@ impl Huffman {
             let mut v = Vec::new();
             for j in 0..length[i] as usize {
                 v.push(data[off + j]);
-                if code >= (1 << (i + 2)) {
+                if code >= (1 << bit_length) {
                     return Err(HuffmanErrorType::InvalidCode(data[off + j], code));
                 }
                 map.insert(Binary::new(code, bit_length), data[off + j]);
@@ -93,7 +105,12 @@ impl Huffman {
         ))
     }
 
-    pub fn decode(&self, code: &mut BitStream<BufReader<File>>) -> Result<u8, HuffmanErrorType> {
+    // 码长为 i + 1 的码字个数
+    pub fn lengths(&self) -> &[u8; 16] {
+        &self._length
+    }
+
+    pub fn decode<R: Read + Seek>(&self, code: &mut BitStream<BufReader<R>>) -> Result<u8, HuffmanErrorType> {
         let value = code.try_read(16)
             .map_err(|e| HuffmanErrorType::BitStreamError(e))?;
 
@@ -121,12 +138,13 @@ impl Huffman {
 }
 
 #[cfg(test)]
-mod huffman_test {
+mod tests {
+    use std::io::Cursor;
+
     use super::*;
 
-    #[test]
-    fn huffman_test() {
-        let (huffman, _) = Huffman::parse(
+    fn parse_table() -> Huffman {
+        let (huffman, off) = Huffman::parse(
             &vec![
                 0x11, 0, 2, 2, 2, 1, 3, 2, 5, 2, 4, 5, 5, 0, 3, 0, 0, 1, 2, 0, 3, 4, 0x11, 0x21, 5,
                 0x12, 0x31, 0x13, 0x41, 6, 0x22, 0x32, 0x51, 0x61, 0x14, 0x71, 0x23, 0x81, 0x91,
@@ -135,6 +153,67 @@ mod huffman_test {
             0,
         )
         .unwrap();
-        dbg!(huffman.map);
+        assert_eq!(off, 53);
+        huffman
+    }
+
+    #[test]
+    fn test_parse() {
+        let huffman = parse_table();
+        assert_eq!(
+            huffman.lengths(),
+            &[0, 2, 2, 2, 1, 3, 2, 5, 2, 4, 5, 5, 0, 3, 0, 0]
+        );
+        assert_eq!(huffman.map.len(), 36);
+        // 范式码字：同一长度内依次加 1，换长度时左移一位
+        for (code, bit_length, symbol) in [
+            (0b00, 2, 0x01),
+            (0b01, 2, 0x02),
+            (0b100, 3, 0x00),
+            (0b11100, 5, 0x21),
+            (0b11111100, 8, 0x61),
+            (0b111111010, 9, 0x14),
+            (0b111111111110, 12, 0xf0),
+            (0b11111111111100, 14, 0x24),
+            (0b11111111111110, 14, 0xf1),
+        ] {
+            assert_eq!(
+                huffman.map.get(&Binary::new(code, bit_length)),
+                Some(&symbol)
+            );
+        }
+        // 不超过 8 位的码字可以直接查表
+        assert!(matches!(
+            huffman.table.table[0b11100000],
+            HuffmanTableValue::Defined(0x21, 5)
+        ));
+        assert!(matches!(
+            huffman.table.table[0b11111111],
+            HuffmanTableValue::Undefined
+        ));
+    }
+
+    #[test]
+    fn test_decode() {
+        let huffman = parse_table();
+        // 00 100 11100 1111011 111111010 11111111111100，后面补 0
+        let data = vec![0x27, 0x3d, 0xfe, 0xbf, 0xfc, 0x00, 0x00];
+        let mut reader = BufReader::new(Cursor::new(data));
+        let mut bs = BitStream::new(&mut reader);
+        for symbol in [0x01, 0x00, 0x21, 0x41, 0x14, 0x24] {
+            assert_eq!(huffman.decode(&mut bs).unwrap(), symbol);
+        }
+    }
+
+    #[test]
+    fn test_invalid_code() {
+        // 长度为 1 的码字只有两个
+        let mut data = vec![0x00, 3];
+        data.extend_from_slice(&[0; 15]);
+        data.extend_from_slice(&[0x0a, 0x0b, 0x0c]);
+        assert!(matches!(
+            Huffman::parse(&data, 0),
+            Err(HuffmanErrorType::InvalidCode(0x0c, 2))
+        ));
     }
 }
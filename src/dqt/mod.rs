@@ -5,6 +5,8 @@ use std::rc::Rc;
 
 use crate::zigzag::ZigZagScan;
 
+pub mod quality;
+
 #[derive(Debug)]
 pub struct Dqt {
     id: u8,
@@ -37,23 +39,47 @@ impl fmt::Display for Dqt {
     }
 }
 
+#[derive(Debug)]
+pub enum DqtErrorType {
+    InvalidLength(usize),
+    InvalidPrecision(u8),
+    ShapeError(ShapeError),
+}
+
 impl Dqt {
     pub fn new(
         map: &mut FxHashMap<u8, Rc<Dqt>>,
         length: u16,
         data: Vec<u8>,
-    ) -> Result<(), ShapeError> {
-        let mut len = length - 2;
+    ) -> Result<(), DqtErrorType> {
+        let len = (length as usize)
+            .checked_sub(2)
+            .filter(|&len| len <= data.len())
+            .ok_or(DqtErrorType::InvalidLength(length as usize))?;
         let mut offset = 0;
 
-        while len > 0 {
-            let precision = data[offset + 0] >> 4;
-            let num = data[offset + 0] & 0x0f;
-            let mut table = Vec::with_capacity(64);
-            for i in 0..64 {
-                table.push(data[offset + i + 1] as isize);
+        while offset < len {
+            let precision = data[offset] >> 4;
+            let num = data[offset] & 0x0f;
+            if precision > 1 {
+                return Err(DqtErrorType::InvalidPrecision(precision));
             }
-            let arr = Array2::from_shape_vec((8, 8), table)?;
+            // 16 位精度的表每个值占 2 字节（大端序）
+            let size = 64 * (precision as usize + 1);
+            let values = data
+                .get(offset + 1..offset + 1 + size)
+                .filter(|_| offset + 1 + size <= len)
+                .ok_or(DqtErrorType::InvalidLength(len))?;
+            let table = if precision == 0 {
+                values.iter().map(|&q| q as isize).collect()
+            } else {
+                values
+                    .chunks(2)
+                    .map(|q| u16::from_be_bytes([q[0], q[1]]) as isize)
+                    .collect()
+            };
+            let arr =
+                Array2::from_shape_vec((8, 8), table).map_err(DqtErrorType::ShapeError)?;
             map.insert(
                 num,
                 Rc::new(Self {
@@ -63,8 +89,7 @@ impl Dqt {
                 }),
             );
 
-            len -= 1 + 64 * (precision as u16 + 1);
-            offset += 1 + 64 * (precision as usize + 1);
+            offset += 1 + size;
         }
         Ok(())
     }
@@ -72,4 +97,32 @@ impl Dqt {
     pub fn id(&self) -> u8 {
         self.id
     }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    // 转置后的量化表，用于在 DCT 域上转置图像
+    pub fn transposed(&self) -> Self {
+        let mut table = Array2::zeros((8, 8));
+        let zigzag: Vec<(usize, usize)> = ZigZagScan::new(8).collect();
+        for (i, (x, y)) in zigzag.iter().enumerate() {
+            let j = zigzag.iter().position(|p| *p == (*y, *x)).unwrap();
+            table[[j / 8, j % 8]] = self.table[[i / 8, i % 8]];
+        }
+        Self {
+            id: self.id,
+            precision: self.precision,
+            table: Rc::new(table),
+        }
+    }
+
+    // 表按文件中的 Z 字形顺序存放，这里转换为自然顺序（行优先）
+    pub fn natural_table(&self) -> [u16; 64] {
+        let mut table = [0u16; 64];
+        for (i, (x, y)) in ZigZagScan::new(8).enumerate() {
+            table[y * 8 + x] = self.table[[i / 8, i % 8]] as u16;
+        }
+        table
+    }
 }
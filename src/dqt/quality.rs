@@ -0,0 +1,375 @@
+use std::rc::Rc;
+
+use rustc_hash::FxHashMap;
+
+use crate::component::frame::Frame;
+
+use super::Dqt;
+
+// ITU T.81 附录 K 的标准量化表（自然顺序）
+pub const STD_LUMINANCE_TABLE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99, //
+];
+
+pub const STD_CHROMINANCE_TABLE: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, //
+    18, 21, 26, 66, 99, 99, 99, 99, //
+    24, 26, 56, 99, 99, 99, 99, 99, //
+    47, 66, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+];
+
+// 其他编码器可选的基础量化表（自然顺序），同样按 IJG 方式缩放，取自 mozjpeg 的 jcparam.c
+const FLAT_TABLE: [u16; 64] = [
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+    16, 16, 16, 16, 16, 16, 16, 16, //
+];
+
+const MS_SSIM_LUMINANCE_TABLE: [u16; 64] = [
+    12, 17, 20, 21, 30, 34, 56, 63, //
+    18, 20, 20, 26, 28, 51, 61, 55, //
+    19, 20, 21, 26, 33, 58, 69, 55, //
+    26, 26, 26, 30, 46, 87, 86, 66, //
+    31, 33, 36, 40, 46, 96, 100, 73, //
+    40, 35, 46, 62, 81, 100, 111, 91, //
+    46, 66, 76, 86, 102, 121, 120, 101, //
+    68, 90, 90, 96, 113, 102, 105, 103, //
+];
+
+const MS_SSIM_CHROMINANCE_TABLE: [u16; 64] = [
+    8, 12, 15, 15, 86, 96, 96, 98, //
+    13, 13, 15, 26, 90, 96, 99, 98, //
+    12, 15, 18, 96, 99, 99, 99, 99, //
+    17, 16, 90, 96, 99, 99, 99, 99, //
+    96, 96, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+];
+
+const PSNR_HVS_LUMINANCE_TABLE: [u16; 64] = [
+    9, 10, 12, 14, 27, 32, 51, 62, //
+    11, 12, 14, 19, 27, 44, 59, 73, //
+    12, 14, 18, 25, 42, 59, 79, 78, //
+    17, 18, 25, 42, 61, 92, 87, 92, //
+    23, 28, 42, 75, 79, 112, 112, 99, //
+    40, 42, 59, 84, 88, 124, 132, 111, //
+    42, 64, 78, 95, 105, 126, 125, 99, //
+    70, 75, 100, 102, 116, 100, 107, 98, //
+];
+
+const PSNR_HVS_CHROMINANCE_TABLE: [u16; 64] = [
+    9, 10, 17, 19, 62, 89, 91, 97, //
+    12, 13, 18, 29, 84, 91, 88, 98, //
+    14, 19, 29, 93, 95, 95, 98, 97, //
+    20, 26, 84, 88, 95, 95, 98, 94, //
+    26, 86, 91, 93, 97, 99, 98, 99, //
+    99, 100, 98, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    97, 97, 99, 99, 99, 99, 97, 99, //
+];
+
+const ROBIDOUX_TABLE: [u16; 64] = [
+    16, 16, 16, 18, 25, 37, 56, 85, //
+    16, 17, 20, 27, 34, 40, 53, 75, //
+    16, 20, 24, 31, 43, 62, 91, 135, //
+    18, 27, 31, 40, 53, 74, 106, 156, //
+    25, 34, 43, 53, 69, 94, 131, 189, //
+    37, 40, 62, 74, 94, 124, 169, 238, //
+    56, 53, 91, 106, 131, 169, 226, 311, //
+    85, 75, 135, 156, 189, 238, 311, 418, //
+];
+
+const KLEIN_TABLE: [u16; 64] = [
+    10, 12, 14, 19, 26, 38, 57, 86, //
+    12, 18, 21, 28, 35, 41, 54, 76, //
+    14, 21, 25, 32, 44, 63, 92, 136, //
+    19, 28, 32, 41, 54, 75, 107, 157, //
+    26, 35, 44, 54, 70, 95, 132, 190, //
+    38, 41, 63, 75, 95, 125, 170, 239, //
+    57, 54, 92, 107, 132, 170, 227, 312, //
+    86, 76, 136, 157, 190, 239, 312, 419, //
+];
+
+const WATSON_TABLE: [u16; 64] = [
+    7, 8, 10, 14, 23, 44, 95, 241, //
+    8, 8, 11, 15, 25, 47, 102, 255, //
+    10, 11, 13, 19, 31, 58, 127, 255, //
+    14, 15, 19, 27, 44, 83, 181, 255, //
+    23, 25, 31, 44, 72, 136, 255, 255, //
+    44, 47, 58, 83, 136, 255, 255, 255, //
+    95, 102, 127, 181, 255, 255, 255, 255, //
+    241, 255, 255, 255, 255, 255, 255, 255, //
+];
+
+const AHUMADA_TABLE: [u16; 64] = [
+    15, 11, 11, 12, 15, 19, 25, 32, //
+    11, 13, 10, 10, 12, 15, 19, 24, //
+    11, 10, 14, 14, 16, 18, 22, 27, //
+    12, 10, 14, 18, 21, 24, 28, 33, //
+    15, 12, 16, 21, 26, 31, 36, 42, //
+    19, 15, 18, 24, 31, 38, 45, 53, //
+    25, 19, 22, 28, 36, 45, 55, 65, //
+    32, 24, 27, 33, 42, 53, 65, 77, //
+];
+
+const PETERSON_TABLE: [u16; 64] = [
+    14, 10, 11, 14, 19, 25, 34, 45, //
+    10, 11, 11, 12, 15, 20, 26, 33, //
+    11, 11, 15, 18, 21, 25, 31, 38, //
+    14, 12, 18, 24, 28, 33, 39, 47, //
+    19, 15, 21, 28, 36, 43, 51, 59, //
+    25, 20, 25, 33, 43, 54, 64, 74, //
+    34, 26, 31, 39, 51, 64, 77, 91, //
+    45, 33, 38, 47, 59, 74, 91, 108, //
+];
+
+struct KnownTables {
+    name: &'static str,
+    luminance: &'static [u16; 64],
+    chrominance: &'static [u16; 64],
+}
+
+const KNOWN_TABLES: [KnownTables; 8] = [
+    KnownTables {
+        name: "flat",
+        luminance: &FLAT_TABLE,
+        chrominance: &FLAT_TABLE,
+    },
+    KnownTables {
+        name: "mozjpeg MS-SSIM",
+        luminance: &MS_SSIM_LUMINANCE_TABLE,
+        chrominance: &MS_SSIM_CHROMINANCE_TABLE,
+    },
+    KnownTables {
+        name: "mozjpeg PSNR-HVS",
+        luminance: &PSNR_HVS_LUMINANCE_TABLE,
+        chrominance: &PSNR_HVS_CHROMINANCE_TABLE,
+    },
+    KnownTables {
+        name: "ImageMagick (Robidoux)",
+        luminance: &ROBIDOUX_TABLE,
+        chrominance: &ROBIDOUX_TABLE,
+    },
+    KnownTables {
+        name: "Klein, Silverstein and Carney",
+        luminance: &KLEIN_TABLE,
+        chrominance: &KLEIN_TABLE,
+    },
+    KnownTables {
+        name: "DCTune (Watson)",
+        luminance: &WATSON_TABLE,
+        chrominance: &WATSON_TABLE,
+    },
+    KnownTables {
+        name: "Ahumada, Watson and Peterson",
+        luminance: &AHUMADA_TABLE,
+        chrominance: &AHUMADA_TABLE,
+    },
+    KnownTables {
+        name: "Peterson, Ahumada and Watson",
+        luminance: &PETERSON_TABLE,
+        chrominance: &PETERSON_TABLE,
+    },
+];
+
+// 与缩放后的标准表的平均相对误差低于此值时，认为是近似的标准表
+const APPROXIMATE_THRESHOLD: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Luminance,
+    Chrominance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableMatch {
+    // 与按 IJG 方式缩放的标准表完全相同
+    Standard,
+    // 与缩放后的标准表接近，可能是其他编码器的舍入方式不同
+    Approximate,
+    // 与按 IJG 方式缩放的某个已知基础表完全相同，质量是该表的质量因子
+    Known(&'static str),
+    // 不属于以上任何一种的自定义表
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QualityEstimate {
+    pub table_id: u8,
+    pub kind: TableKind,
+    // IJG 等效质量 (1~100)
+    pub quality: u8,
+    pub table_match: TableMatch,
+    // 与最接近的缩放标准表的平均相对误差
+    pub error: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QualityReport {
+    pub luma: Option<QualityEstimate>,
+    pub chroma: Option<QualityEstimate>,
+}
+
+// 按 IJG (libjpeg) 的方法用质量因子缩放标准表
+pub fn scaled_table(base: &[u16; 64], quality: u8, precision: u8) -> [u16; 64] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    let max = if precision == 0 { 255 } else { 32767 };
+    let mut table = [0u16; 64];
+    for (t, &b) in table.iter_mut().zip(base.iter()) {
+        *t = ((b as u32 * scale + 50) / 100).clamp(1, max) as u16;
+    }
+    table
+}
+
+pub fn estimate_table_quality(dqt: &Dqt, kind: TableKind) -> QualityEstimate {
+    let base = match kind {
+        TableKind::Luminance => &STD_LUMINANCE_TABLE,
+        TableKind::Chrominance => &STD_CHROMINANCE_TABLE,
+    };
+    let table = dqt.natural_table();
+
+    let mut best = (1, f32::MAX);
+    for quality in 1..=100 {
+        let scaled = scaled_table(base, quality, dqt.precision());
+        let error = table
+            .iter()
+            .zip(scaled.iter())
+            .map(|(&t, &s)| (t as f32 - s as f32).abs() / s as f32)
+            .sum::<f32>()
+            / 64.0;
+        if error < best.1 {
+            best = (quality, error);
+        }
+    }
+
+    let (mut quality, error) = best;
+    let known = || {
+        KNOWN_TABLES.iter().find_map(|known| {
+            let base = match kind {
+                TableKind::Luminance => known.luminance,
+                TableKind::Chrominance => known.chrominance,
+            };
+            (1..=100)
+                .find(|&q| scaled_table(base, q, dqt.precision()) == table)
+                .map(|q| (known.name, q))
+        })
+    };
+    let table_match = if error == 0.0 {
+        TableMatch::Standard
+    } else if let Some((name, q)) = known() {
+        quality = q;
+        TableMatch::Known(name)
+    } else if error < APPROXIMATE_THRESHOLD {
+        TableMatch::Approximate
+    } else {
+        TableMatch::Custom
+    };
+    QualityEstimate {
+        table_id: dqt.id(),
+        kind,
+        quality,
+        table_match,
+        error,
+    }
+}
+
+// 按分量 id 排序，第一个分量（Y）使用的表作为亮度表，下一个使用不同表的分量作为色度表
+pub fn estimate_quality(frame: &Frame, dqt_map: &FxHashMap<u8, Rc<Dqt>>) -> QualityReport {
+    let mut ids: Vec<&u8> = frame.components.keys().collect();
+    ids.sort();
+    let qids: Vec<u8> = ids.iter().map(|id| frame.components[*id].get_qid()).collect();
+
+    let luma = qids
+        .first()
+        .and_then(|qid| dqt_map.get(qid))
+        .map(|dqt| estimate_table_quality(dqt, TableKind::Luminance));
+    let chroma = qids
+        .iter()
+        .skip(1)
+        .find(|qid| Some(**qid) != qids.first().copied())
+        .and_then(|qid| dqt_map.get(qid))
+        .map(|dqt| estimate_table_quality(dqt, TableKind::Chrominance));
+    QualityReport { luma, chroma }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zigzag::ZigZagScan;
+
+    fn make_dqt(table: &[u16; 64]) -> Rc<Dqt> {
+        let mut data = vec![0];
+        for (x, y) in ZigZagScan::new(8) {
+            data.push(table[y * 8 + x] as u8);
+        }
+        let mut map = FxHashMap::default();
+        Dqt::new(&mut map, data.len() as u16 + 2, data).unwrap();
+        map[&0].clone()
+    }
+
+    #[test]
+    fn test_standard_tables() {
+        for quality in [10, 50, 75, 90, 100] {
+            let dqt = make_dqt(&scaled_table(&STD_LUMINANCE_TABLE, quality, 0));
+            let estimate = estimate_table_quality(&dqt, TableKind::Luminance);
+            assert_eq!(estimate.table_match, TableMatch::Standard);
+            // 高质量时不同质量因子可能得到相同的表
+            let expected = scaled_table(&STD_LUMINANCE_TABLE, estimate.quality, 0);
+            assert_eq!(expected, dqt.natural_table());
+        }
+        let dqt = make_dqt(&scaled_table(&STD_CHROMINANCE_TABLE, 75, 0));
+        let estimate = estimate_table_quality(&dqt, TableKind::Chrominance);
+        assert_eq!(estimate.quality, 75);
+        assert_eq!(estimate.table_match, TableMatch::Standard);
+    }
+
+    #[test]
+    fn test_known_tables() {
+        for quality in [30, 75, 95] {
+            let dqt = make_dqt(&scaled_table(&ROBIDOUX_TABLE, quality, 0));
+            let estimate = estimate_table_quality(&dqt, TableKind::Luminance);
+            assert_eq!(
+                estimate.table_match,
+                TableMatch::Known("ImageMagick (Robidoux)")
+            );
+            let expected = scaled_table(&ROBIDOUX_TABLE, estimate.quality, 0);
+            assert_eq!(expected, dqt.natural_table());
+        }
+        let dqt = make_dqt(&scaled_table(&MS_SSIM_CHROMINANCE_TABLE, 80, 0));
+        let estimate = estimate_table_quality(&dqt, TableKind::Chrominance);
+        assert_eq!(estimate.table_match, TableMatch::Known("mozjpeg MS-SSIM"));
+        assert_eq!(estimate.quality, 80);
+    }
+
+    #[test]
+    fn test_custom_table() {
+        let mut table = [0u16; 64];
+        for (i, t) in table.iter_mut().enumerate() {
+            *t = 64 - i as u16;
+        }
+        let estimate = estimate_table_quality(&make_dqt(&table), TableKind::Luminance);
+        assert_eq!(estimate.table_match, TableMatch::Custom);
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{self, Write};
+
+pub struct BitWriter<'a, W: Write> {
+    writer: &'a mut W,
+    cur_byte: u8,
+    bit_len: usize,
+}
+
+impl<'a, W: Write> BitWriter<'a, W> {
+    pub fn new(writer: &'a mut W) -> Self {
+        Self {
+            writer,
+            cur_byte: 0,
+            bit_len: 0,
+        }
+    }
+
+    // 写入 value 的低 n 位（高位在前）
+    pub fn write(&mut self, value: usize, n: usize) -> io::Result<()> {
+        for i in (0..n).rev() {
+            self.cur_byte = (self.cur_byte << 1) | ((value >> i) & 1) as u8;
+            self.bit_len += 1;
+            if self.bit_len == 8 {
+                self.emit()?;
+            }
+        }
+        Ok(())
+    }
+
+    // 用 1 填充到字节边界
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.bit_len > 0 {
+            let pad = 8 - self.bit_len;
+            self.write((1 << pad) - 1, pad)?;
+        }
+        Ok(())
+    }
+
+    // 写入 RSTn 等标记前必须先对齐，标记本身不做字节填充
+    pub fn write_marker(&mut self, marker: u8) -> io::Result<()> {
+        self.flush()?;
+        self.writer.write_all(&[0xFF, marker])
+    }
+
+    fn emit(&mut self) -> io::Result<()> {
+        // 熵编码数据中的 0xFF 后面要补一个 0x00
+        if self.cur_byte == 0xFF {
+            self.writer.write_all(&[0xFF, 0x00])?;
+        } else {
+            self.writer.write_all(&[self.cur_byte])?;
+        }
+        self.cur_byte = 0;
+        self.bit_len = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stuffing() {
+        let mut buf = Vec::new();
+        let mut bw = BitWriter::new(&mut buf);
+        bw.write(0xFF, 8).unwrap();
+        bw.write(0b101, 3).unwrap();
+        bw.flush().unwrap();
+        assert_eq!(buf, vec![0xFF, 0x00, 0b1011_1111]);
+    }
+}
@@ -0,0 +1,181 @@
+// 根据符号出现次数生成最优的哈夫曼表（ITU T.81 附录 K.2）
+pub struct HuffmanEncoder {
+    // 码长为 i + 1 的码字个数
+    pub bits: [u8; 16],
+    // 按码长从短到长排列的符号
+    pub values: Vec<u8>,
+    // 每个符号对应的 (码字, 码长)
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanEncoder {
+    pub fn new(freq: &[u32; 256]) -> Self {
+        let (bits, values) = code_lengths(freq);
+
+        let mut codes = [(0u16, 0u8); 256];
+        let mut code = 0u16;
+        let mut k = 0;
+        for (i, &n) in bits.iter().enumerate() {
+            for _ in 0..n {
+                codes[values[k] as usize] = (code, i as u8 + 1);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+
+        Self {
+            bits,
+            values,
+            codes,
+        }
+    }
+
+    pub fn code(&self, symbol: u8) -> (u16, u8) {
+        self.codes[symbol as usize]
+    }
+}
+
+fn code_lengths(freq: &[u32; 256]) -> ([u8; 16], Vec<u8>) {
+    // 第 256 个符号是保留的，保证不会出现全 1 的码字
+    let mut freq: Vec<u64> = freq.iter().map(|&f| f as u64).collect();
+    freq.push(1);
+    let mut code_size = [0usize; 257];
+    let mut others = [-1isize; 257];
+
+    loop {
+        // 找出频率最小的两个符号 v1、v2（v1 <= v2）
+        let mut v1 = None;
+        let mut v2 = None;
+        for i in 0..257 {
+            if freq[i] == 0 {
+                continue;
+            }
+            match v1 {
+                Some(j) if freq[i] > freq[j] => match v2 {
+                    Some(k) if freq[i] > freq[k] => {}
+                    _ => v2 = Some(i),
+                },
+                _ => {
+                    v2 = v1;
+                    v1 = Some(i);
+                }
+            }
+        }
+        let (v1, v2) = match (v1, v2) {
+            (Some(v1), Some(v2)) => (v1, v2),
+            _ => break,
+        };
+
+        freq[v1] += freq[v2];
+        freq[v2] = 0;
+
+        let mut i = v1;
+        code_size[i] += 1;
+        while others[i] >= 0 {
+            i = others[i] as usize;
+            code_size[i] += 1;
+        }
+        others[i] = v2 as isize;
+
+        let mut i = v2;
+        code_size[i] += 1;
+        while others[i] >= 0 {
+            i = others[i] as usize;
+            code_size[i] += 1;
+        }
+    }
+
+    let mut bits = [0usize; 33];
+    for &size in code_size.iter() {
+        if size > 0 {
+            bits[size] += 1;
+        }
+    }
+
+    // 把码长限制在 16 位以内
+    let mut i = 32;
+    while i > 16 {
+        while bits[i] > 0 {
+            let mut j = i - 2;
+            while bits[j] == 0 {
+                j -= 1;
+            }
+            bits[i] -= 2;
+            bits[i - 1] += 1;
+            bits[j + 1] += 2;
+            bits[j] -= 1;
+        }
+        i -= 1;
+    }
+    // 去掉保留符号，没有使用任何符号时保留符号也没有分配码长
+    while i > 0 && bits[i] == 0 {
+        i -= 1;
+    }
+    if i > 0 {
+        bits[i] -= 1;
+    }
+
+    let mut result = [0u8; 16];
+    for (i, n) in result.iter_mut().enumerate() {
+        *n = bits[i + 1] as u8;
+    }
+
+    let mut values = Vec::new();
+    for size in 1..=32 {
+        for (symbol, &s) in code_size.iter().enumerate().take(256) {
+            if s == size {
+                values.push(symbol as u8);
+            }
+        }
+    }
+    (result, values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_lengths() {
+        let mut freq = [0u32; 256];
+        // 斐波那契分布会产生很深的树，测试码长限制
+        let mut a = 1;
+        let mut b = 1;
+        for f in freq.iter_mut().take(30) {
+            *f = a;
+            (a, b) = (b, a + b);
+        }
+        let huff = HuffmanEncoder::new(&freq);
+        assert_eq!(huff.values.len(), 30);
+        assert_eq!(huff.bits.iter().map(|&n| n as usize).sum::<usize>(), 30);
+
+        // 满足 Kraft 不等式，且没有全 1 的码字
+        let kraft: f64 = huff
+            .bits
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| n as f64 / (1u32 << (i + 1)) as f64)
+            .sum();
+        assert!(kraft < 1.0);
+        for symbol in 0..30 {
+            let (code, len) = huff.code(symbol);
+            assert!((1..=16).contains(&len));
+            assert_ne!(code as u32, (1u32 << len) - 1);
+        }
+    }
+
+    #[test]
+    fn test_unused_table() {
+        // 分量的 AC 系数全为 0 时哈夫曼表可能一个符号都没用到
+        let huff = HuffmanEncoder::new(&[0u32; 256]);
+        assert_eq!(huff.bits, [0; 16]);
+        assert!(huff.values.is_empty());
+
+        let mut freq = [0u32; 256];
+        freq[7] = 3;
+        let huff = HuffmanEncoder::new(&freq);
+        assert_eq!(huff.bits[0], 1);
+        assert_eq!(huff.values, vec![7]);
+    }
+}
@@ -0,0 +1,296 @@
+use std::io::{self, Write};
+
+use bitwriter::BitWriter;
+use huffman::HuffmanEncoder;
+
+use crate::{
+    decode::coefficient::ComponentCoefficients,
+    segment::{Segment, SegmentType},
+    zigzag::ZigZagScan,
+};
+
+pub mod bitwriter;
+pub mod huffman;
+
+// Z 字形顺序的第 i 个系数在自然顺序中的位置
+fn zigzag_order() -> [usize; 64] {
+    let mut order = [0; 64];
+    for (i, (x, y)) in ZigZagScan::new(8).enumerate() {
+        order[i] = y * 8 + x;
+    }
+    order
+}
+
+// 数值的位长度（DC 差值和 AC 系数的类别）
+fn category(value: isize) -> usize {
+    (usize::BITS - value.unsigned_abs().leading_zeros()) as usize
+}
+
+// 负数按反码存储
+fn extra_bits(value: isize, size: usize) -> usize {
+    if value < 0 {
+        (value - 1) as usize & ((1 << size) - 1)
+    } else {
+        value as usize
+    }
+}
+
+// 第一个分量使用 0 号表，其余分量共享 1 号表
+fn table_id(comp_idx: usize) -> usize {
+    if comp_idx == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+trait BlockSink {
+    fn dc(&mut self, table: usize, size: usize, value: isize) -> io::Result<()>;
+    fn ac(&mut self, table: usize, symbol: u8, size: usize, value: isize) -> io::Result<()>;
+    fn restart(&mut self, _marker: u8) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct FrequencyCounter {
+    dc: [[u32; 256]; 2],
+    ac: [[u32; 256]; 2],
+}
+
+impl BlockSink for FrequencyCounter {
+    fn dc(&mut self, table: usize, size: usize, _value: isize) -> io::Result<()> {
+        self.dc[table][size] += 1;
+        Ok(())
+    }
+
+    fn ac(&mut self, table: usize, symbol: u8, _size: usize, _value: isize) -> io::Result<()> {
+        self.ac[table][symbol as usize] += 1;
+        Ok(())
+    }
+}
+
+struct EntropyWriter<'a, W: Write> {
+    bw: BitWriter<'a, W>,
+    dc: Vec<HuffmanEncoder>,
+    ac: Vec<HuffmanEncoder>,
+}
+
+impl<'a, W: Write> BlockSink for EntropyWriter<'a, W> {
+    fn dc(&mut self, table: usize, size: usize, value: isize) -> io::Result<()> {
+        let (code, len) = self.dc[table].code(size as u8);
+        self.bw.write(code as usize, len as usize)?;
+        if size > 0 {
+            self.bw.write(extra_bits(value, size), size)?;
+        }
+        Ok(())
+    }
+
+    fn ac(&mut self, table: usize, symbol: u8, size: usize, value: isize) -> io::Result<()> {
+        let (code, len) = self.ac[table].code(symbol);
+        self.bw.write(code as usize, len as usize)?;
+        if size > 0 {
+            self.bw.write(extra_bits(value, size), size)?;
+        }
+        Ok(())
+    }
+
+    fn restart(&mut self, marker: u8) -> io::Result<()> {
+        self.bw.write_marker(marker)
+    }
+}
+
+fn encode_block<S: BlockSink>(
+    sink: &mut S,
+    table: usize,
+    block: &[i16; 64],
+    last_dc: &mut isize,
+    order: &[usize; 64],
+) -> io::Result<()> {
+    let dc = block[0] as isize;
+    let diff = dc - *last_dc;
+    *last_dc = dc;
+    sink.dc(table, category(diff), diff)?;
+
+    let mut run = 0;
+    for &idx in order.iter().skip(1) {
+        let value = block[idx] as isize;
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            // ZRL: 16 个 0
+            sink.ac(table, 0xF0, 0, 0)?;
+            run -= 16;
+        }
+        let size = category(value);
+        sink.ac(table, ((run << 4) | size) as u8, size, value)?;
+        run = 0;
+    }
+    if run > 0 {
+        // EOB
+        sink.ac(table, 0x00, 0, 0)?;
+    }
+    Ok(())
+}
+
+fn encode_scan<S: BlockSink>(
+    sink: &mut S,
+    width: usize,
+    height: usize,
+    comps: &[ComponentCoefficients],
+    restart_interval: Option<u16>,
+) -> io::Result<()> {
+    let order = zigzag_order();
+    let mut last_dc = vec![0isize; comps.len()];
+
+    // 只有一个分量时是非交错扫描，每个 MCU 只有一个块
+    let (x_cnt, y_cnt, factors): (usize, usize, Vec<(usize, usize)>) = if comps.len() == 1 {
+        (width.div_ceil(8), height.div_ceil(8), vec![(1, 1)])
+    } else {
+        let max_x = comps.iter().map(|c| c.factor_x as usize).max().unwrap();
+        let max_y = comps.iter().map(|c| c.factor_y as usize).max().unwrap();
+        (
+            width.div_ceil(max_x * 8),
+            height.div_ceil(max_y * 8),
+            comps
+                .iter()
+                .map(|c| (c.factor_x as usize, c.factor_y as usize))
+                .collect(),
+        )
+    };
+
+    let mut cnt = 0;
+    let mut rst = 0;
+    for y1 in 0..y_cnt {
+        for x1 in 0..x_cnt {
+            if let Some(ri) = restart_interval {
+                if cnt == ri {
+                    sink.restart(0xD0 + rst)?;
+                    rst = (rst + 1) % 8;
+                    last_dc.iter_mut().for_each(|dc| *dc = 0);
+                    cnt = 0;
+                }
+                cnt += 1;
+            }
+            for (idx, (comp, (h, v))) in comps.iter().zip(&factors).enumerate() {
+                for by in 0..*v {
+                    for bx in 0..*h {
+                        let block = comp.block(x1 * h + bx, y1 * v + by);
+                        encode_block(sink, table_id(idx), block, &mut last_dc[idx], &order)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_segment<W: Write>(w: &mut W, marker: u8, data: &[u8]) -> io::Result<()> {
+    w.write_all(&[0xFF, marker])?;
+    w.write_all(&((data.len() + 2) as u16).to_be_bytes())?;
+    w.write_all(data)
+}
+
+// 把量化系数重新编码为基线 JPEG，使用原来的量化表和重新优化的哈夫曼表
+// metadata 中的 APPn 和 COM 段原样写在 SOI 之后
+pub fn write_jpeg<W: Write>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    comps: &[ComponentCoefficients],
+    metadata: &[Segment],
+    restart_interval: Option<u16>,
+) -> io::Result<()> {
+    // SOI
+    w.write_all(&[0xFF, 0xD8])?;
+
+    for seg in metadata {
+        if let SegmentType::APPn(_) | SegmentType::COM = seg.segment_type {
+            seg.write_to(w)?;
+        }
+    }
+
+    // DQT
+    let mut written = Vec::new();
+    let mut extended = false;
+    for comp in comps {
+        let dqt = &comp.dqt;
+        if written.contains(&dqt.id()) {
+            continue;
+        }
+        written.push(dqt.id());
+        let mut data = vec![(dqt.precision() << 4) | dqt.id()];
+        for i in 0..64 {
+            let value = dqt.table[[i / 8, i % 8]] as u16;
+            if dqt.precision() == 0 {
+                data.push(value as u8);
+            } else {
+                extended = true;
+                data.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        write_segment(w, 0xDB, &data)?;
+    }
+
+    // SOF0，16 位量化表需要使用 SOF1
+    let mut data = vec![8];
+    data.extend_from_slice(&(height as u16).to_be_bytes());
+    data.extend_from_slice(&(width as u16).to_be_bytes());
+    data.push(comps.len() as u8);
+    for comp in comps {
+        let hv = if comps.len() == 1 {
+            0x11
+        } else {
+            (comp.factor_x << 4) | comp.factor_y
+        };
+        data.extend_from_slice(&[comp.id, hv, comp.dqt.id()]);
+    }
+    write_segment(w, if extended { 0xC1 } else { 0xC0 }, &data)?;
+
+    // 第一遍统计符号频率，生成哈夫曼表
+    let mut counter = FrequencyCounter {
+        dc: [[0; 256]; 2],
+        ac: [[0; 256]; 2],
+    };
+    encode_scan(&mut counter, width, height, comps, restart_interval)?;
+    let tables = if comps.len() == 1 { 1 } else { 2 };
+    let dc: Vec<HuffmanEncoder> = (0..tables).map(|i| HuffmanEncoder::new(&counter.dc[i])).collect();
+    let ac: Vec<HuffmanEncoder> = (0..tables).map(|i| HuffmanEncoder::new(&counter.ac[i])).collect();
+
+    // DHT
+    for (class, huffs) in [(0u8, &dc), (1u8, &ac)] {
+        for (id, huff) in huffs.iter().enumerate() {
+            let mut data = vec![(class << 4) | id as u8];
+            data.extend_from_slice(&huff.bits);
+            data.extend_from_slice(&huff.values);
+            write_segment(w, 0xC4, &data)?;
+        }
+    }
+
+    // DRI
+    if let Some(ri) = restart_interval {
+        write_segment(w, 0xDD, &ri.to_be_bytes())?;
+    }
+
+    // SOS
+    let mut data = vec![comps.len() as u8];
+    for (idx, comp) in comps.iter().enumerate() {
+        let id = table_id(idx) as u8;
+        data.extend_from_slice(&[comp.id, (id << 4) | id]);
+    }
+    data.extend_from_slice(&[0, 63, 0]);
+    write_segment(w, 0xDA, &data)?;
+
+    // 第二遍写入熵编码数据
+    let mut writer = EntropyWriter {
+        bw: BitWriter::new(w),
+        dc,
+        ac,
+    };
+    encode_scan(&mut writer, width, height, comps, restart_interval)?;
+    writer.bw.flush()?;
+
+    // EOI
+    w.write_all(&[0xFF, 0xD9])
+}
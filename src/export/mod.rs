@@ -0,0 +1,130 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::decode::OutputFormat;
+
+pub mod pixels;
+mod png;
+
+pub use png::write_png;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Pgm,
+    Bmp,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pgm" => Some(ImageFormat::Pgm),
+            "bmp" => Some(ImageFormat::Bmp),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        Self::from_name(Path::new(path).extension()?.to_str()?)
+    }
+
+    // 解码时使用的像素格式，PGM 只能是灰度，PPM 只能是 RGB
+    pub fn output_format(&self, gray: bool) -> OutputFormat {
+        match self {
+            ImageFormat::Pgm => OutputFormat::Gray8,
+            ImageFormat::Ppm => OutputFormat::Rgb8,
+            _ if gray => OutputFormat::Gray8,
+            _ => OutputFormat::Rgb8,
+        }
+    }
+}
+
+// pixels 是每像素 channels 字节（1: 灰度，3: RGB）的行优先数据
+pub fn write_image<W: Write>(
+    w: &mut W,
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    match format {
+        ImageFormat::Png => write_png(w, width, height, channels, pixels),
+        ImageFormat::Ppm | ImageFormat::Pgm => write_pnm(w, width, height, channels, pixels),
+        ImageFormat::Bmp => write_bmp(w, width, height, channels, pixels),
+    }
+}
+
+// 二进制 PGM (P5) 或 PPM (P6)
+pub fn write_pnm<W: Write>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    let magic = if channels == 1 { "P5" } else { "P6" };
+    write!(w, "{}\n{} {}\n255\n", magic, width, height)?;
+    w.write_all(&pixels[..width * height * channels])
+}
+
+// 灰度图写成带调色板的 8 位 BMP，RGB 写成 24 位 BMP
+pub fn write_bmp<W: Write>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    // 每行按 4 字节对齐
+    let stride = (width * channels).div_ceil(4) * 4;
+    let palette = if channels == 1 { 256 * 4 } else { 0 };
+    let data_offset = 14 + 40 + palette;
+    let file_size = data_offset + stride * height;
+
+    // BITMAPFILEHEADER
+    w.write_all(b"BM")?;
+    w.write_all(&(file_size as u32).to_le_bytes())?;
+    w.write_all(&[0; 4])?;
+    w.write_all(&(data_offset as u32).to_le_bytes())?;
+
+    // BITMAPINFOHEADER
+    w.write_all(&40u32.to_le_bytes())?;
+    w.write_all(&(width as i32).to_le_bytes())?;
+    w.write_all(&(height as i32).to_le_bytes())?;
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&(channels as u16 * 8).to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&((stride * height) as u32).to_le_bytes())?;
+    // 72 DPI
+    w.write_all(&2835i32.to_le_bytes())?;
+    w.write_all(&2835i32.to_le_bytes())?;
+    w.write_all(&(if channels == 1 { 256u32 } else { 0 }).to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+
+    if channels == 1 {
+        for i in 0..=255u8 {
+            w.write_all(&[i, i, i, 0])?;
+        }
+    }
+
+    // 行从下往上存放，颜色顺序为 BGR
+    let mut row = vec![0u8; stride];
+    for y in (0..height).rev() {
+        let src = &pixels[y * width * channels..(y + 1) * width * channels];
+        if channels == 1 {
+            row[..width].copy_from_slice(src);
+        } else {
+            for (dst, rgb) in row.chunks_exact_mut(3).zip(src.chunks_exact(3)) {
+                dst.copy_from_slice(&[rgb[2], rgb[1], rgb[0]]);
+            }
+        }
+        w.write_all(&row)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::transform::Transform;
+
+// 按 factor 缩小图像，每个输出像素取对应区域的平均值，边缘不足的区域按实际像素数平均
+pub fn downscale(
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+    factor: usize,
+) -> (usize, usize, Vec<u8>) {
+    if factor <= 1 {
+        return (width, height, pixels.to_vec());
+    }
+    let new_width = width.div_ceil(factor);
+    let new_height = height.div_ceil(factor);
+    let mut result = Vec::with_capacity(new_width * new_height * channels);
+    let mut sum = vec![0u32; channels];
+    for y in 0..new_height {
+        for x in 0..new_width {
+            sum.iter_mut().for_each(|s| *s = 0);
+            let ys = y * factor..((y + 1) * factor).min(height);
+            let xs = x * factor..((x + 1) * factor).min(width);
+            let count = (ys.len() * xs.len()) as u32;
+            for sy in ys {
+                for sx in xs.clone() {
+                    let idx = (sy * width + sx) * channels;
+                    for (s, &p) in sum.iter_mut().zip(&pixels[idx..idx + channels]) {
+                        *s += p as u32;
+                    }
+                }
+            }
+            result.extend(sum.iter().map(|&s| ((s + count / 2) / count) as u8));
+        }
+    }
+    (new_width, new_height, result)
+}
+
+// 输出像素 (x, y) 对应的原像素坐标
+type SourceFn = Box<dyn Fn(usize, usize) -> (usize, usize)>;
+
+// 对解码后的像素做几何变换，裁剪不需要按 MCU 对齐
+pub fn transform_pixels(
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+    transform: Transform,
+) -> (usize, usize, Vec<u8>) {
+    let (w, h) = (width, height);
+    let (new_width, new_height, src): (usize, usize, SourceFn) = match transform {
+        Transform::FlipHorizontal => (w, h, Box::new(move |x, y| (w - 1 - x, y))),
+        Transform::FlipVertical => (w, h, Box::new(move |x, y| (x, h - 1 - y))),
+        Transform::Transpose => (h, w, Box::new(move |x, y| (y, x))),
+        Transform::Transverse => (h, w, Box::new(move |x, y| (w - 1 - y, h - 1 - x))),
+        Transform::Rotate90 => (h, w, Box::new(move |x, y| (y, h - 1 - x))),
+        Transform::Rotate180 => (w, h, Box::new(move |x, y| (w - 1 - x, h - 1 - y))),
+        Transform::Rotate270 => (h, w, Box::new(move |x, y| (w - 1 - y, x))),
+        Transform::Crop {
+            x: left,
+            y: top,
+            width: cw,
+            height: ch,
+        } => {
+            let left = left.min(w);
+            let top = top.min(h);
+            (
+                cw.min(w - left),
+                ch.min(h - top),
+                Box::new(move |x, y| (left + x, top + y)),
+            )
+        }
+    };
+
+    let mut result = Vec::with_capacity(new_width * new_height * channels);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let (sx, sy) = src(x, y);
+            let idx = (sy * width + sx) * channels;
+            result.extend_from_slice(&pixels[idx..idx + channels]);
+        }
+    }
+    (new_width, new_height, result)
+}
+
+// 按 EXIF 方向标签把图像转正
+pub fn apply_orientation(
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+    orientation: u16,
+) -> (usize, usize, Vec<u8>) {
+    match Transform::from_orientation(orientation) {
+        Some(t) => transform_pixels(width, height, channels, pixels, t),
+        None => (width, height, pixels.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_pixels() {
+        // 3x2 的灰度图
+        let pixels = [1, 2, 3, 4, 5, 6];
+        let (w, h, rotated) = transform_pixels(3, 2, 1, &pixels, Transform::Rotate90);
+        assert_eq!((w, h), (2, 3));
+        assert_eq!(rotated, vec![4, 1, 5, 2, 6, 3]);
+        let (_, _, back) = transform_pixels(w, h, 1, &rotated, Transform::Rotate270);
+        assert_eq!(back, pixels);
+        let (_, _, transverse) = transform_pixels(3, 2, 1, &pixels, Transform::Transverse);
+        assert_eq!(transverse, vec![6, 3, 5, 2, 4, 1]);
+
+        let (w, h, small) = downscale(3, 2, 1, &pixels, 2);
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(small, vec![3, 5]);
+    }
+}
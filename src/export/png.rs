@@ -0,0 +1,124 @@
+use std::io::{self, Write};
+
+// 存储块最多 65535 字节
+const MAX_STORED_BLOCK: usize = 65535;
+
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    for (n, t) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *t = c;
+    }
+    table
+}
+
+fn crc32(table: &[u32; 256], data: &[&[u8]]) -> u32 {
+    let mut c = 0xFFFFFFFFu32;
+    for bytes in data {
+        for &b in bytes.iter() {
+            c = table[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+        }
+    }
+    c ^ 0xFFFFFFFF
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    // 5552 是保证 b 不溢出的最大块长度
+    for chunk in data.chunks(5552) {
+        for &d in chunk {
+            a += d as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+// 不压缩的 zlib 数据流，只使用存储块
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    out.extend_from_slice(&[0x78, 0x01]);
+    for i in 0..blocks {
+        let start = i * MAX_STORED_BLOCK;
+        let block = &data[start..(start + MAX_STORED_BLOCK).min(data.len())];
+        let last = (i + 1 == blocks) as u8;
+        let len = block.len() as u16;
+        out.push(last);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn write_chunk<W: Write>(
+    w: &mut W,
+    table: &[u32; 256],
+    name: &[u8; 4],
+    data: &[u8],
+) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(name)?;
+    w.write_all(data)?;
+    w.write_all(&crc32(table, &[name, data]).to_be_bytes())
+}
+
+// channels 为 1 时输出灰度 PNG，为 3 时输出 RGB PNG
+pub fn write_png<W: Write>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    channels: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    let table = crc32_table();
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    let color_type = if channels == 1 { 0 } else { 2 };
+    ihdr.extend_from_slice(&[8, color_type, 0, 0, 0]);
+    write_chunk(w, &table, b"IHDR", &ihdr)?;
+
+    // 每行前面加一个过滤类型字节（0 表示不过滤）
+    let stride = width * channels;
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    for row in pixels.chunks(stride).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(w, &table, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(w, &table, b"IEND", &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        let table = crc32_table();
+        assert_eq!(crc32(&table, &[b"IEND"]), 0xAE426082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+
+        let data = vec![7u8; MAX_STORED_BLOCK + 10];
+        let zlib = zlib_stored(&data);
+        // 头部 2 字节，两个块头各 5 字节，校验和 4 字节
+        assert_eq!(zlib.len(), data.len() + 2 + 10 + 4);
+        assert_eq!(zlib[2], 0);
+        assert_eq!(zlib[2 + 5 + MAX_STORED_BLOCK], 1);
+    }
+}
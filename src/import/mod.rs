@@ -0,0 +1,169 @@
+use std::{fs, io};
+
+mod inflate;
+mod png;
+
+pub use png::read_png;
+
+// 跳过空白和 # 开头的注释，读取一个十进制数
+fn pnm_number(data: &[u8], pos: &mut usize) -> io::Result<usize> {
+    loop {
+        match data.get(*pos) {
+            Some(b'#') => {
+                while data.get(*pos).is_some_and(|&c| c != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(c) if c.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while data.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&data[start..*pos])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "无效的 PNM 头"))
+}
+
+// 读取 8 位的二进制 PGM (P5) 或 PPM (P6)
+pub fn read_pnm(data: &[u8]) -> io::Result<(usize, usize, usize, Vec<u8>)> {
+    let channels = match data.get(..2) {
+        Some(b"P5") => 1,
+        Some(b"P6") => 3,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "只支持二进制 PGM/PPM",
+            ))
+        }
+    };
+    let mut pos = 2;
+    let width = pnm_number(data, &mut pos)?;
+    let height = pnm_number(data, &mut pos)?;
+    if pnm_number(data, &mut pos)? != 255 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "只支持 8 位 PNM",
+        ));
+    }
+    // 最大值后面只有一个空白字符
+    pos += 1;
+    let pixels = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .and_then(|n| n.checked_add(pos))
+        .and_then(|end| data.get(pos..end))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "图像数据不完整"))?;
+    Ok((width, height, channels, pixels.to_vec()))
+}
+
+// 读取未压缩的 24 位或 8 位（调色板）BMP
+pub fn read_bmp(data: &[u8]) -> io::Result<(usize, usize, usize, Vec<u8>)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    if data.len() < 54 || &data[..2] != b"BM" {
+        return Err(invalid("不是 BMP 文件"));
+    }
+    let u32_at = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+    let offset = u32_at(10) as usize;
+    let header_size = u32_at(14) as usize;
+    let width = u32_at(18) as i32;
+    let height = u32_at(22) as i32;
+    let bits = u16::from_le_bytes([data[28], data[29]]);
+    if u32_at(30) != 0 || width <= 0 || height == 0 || !(bits == 8 || bits == 24) {
+        return Err(invalid("只支持未压缩的 8 位或 24 位 BMP"));
+    }
+    // 高度为负数时行从上往下存放
+    let (width, top_down, height) = (width as usize, height < 0, height.unsigned_abs() as usize);
+    let palette = data
+        .get(14usize.saturating_add(header_size).min(offset)..offset)
+        .ok_or_else(|| invalid("像素数据的偏移超出文件"))?;
+    let channels = if bits == 24 { 3 } else { 1 };
+    let stride = (width * channels).div_ceil(4) * 4;
+    // 文件中必须有全部的行，这样后面按像素数分配内存时不会溢出
+    match stride
+        .checked_mul(height)
+        .and_then(|n| n.checked_add(offset))
+    {
+        Some(end) if end <= data.len() => {}
+        _ => return Err(invalid("图像数据不完整")),
+    }
+    // 调色板是灰度时输出单通道
+    let gray = bits == 8
+        && palette
+            .chunks_exact(4)
+            .all(|c| c[0] == c[1] && c[1] == c[2]);
+    let out_channels = if bits == 8 && gray { 1 } else { 3 };
+
+    let mut pixels = Vec::with_capacity(width * height * out_channels);
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y };
+        let line = data
+            .get(offset + row * stride..offset + row * stride + width * channels)
+            .ok_or_else(|| invalid("图像数据不完整"))?;
+        if bits == 24 {
+            for bgr in line.chunks_exact(3) {
+                pixels.extend_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+            }
+        } else {
+            for &idx in line {
+                let entry = palette
+                    .get(idx as usize * 4..idx as usize * 4 + 3)
+                    .ok_or_else(|| invalid("调色板索引越界"))?;
+                if gray {
+                    pixels.push(entry[0]);
+                } else {
+                    pixels.extend_from_slice(&[entry[2], entry[1], entry[0]]);
+                }
+            }
+        }
+    }
+    Ok((width, height, out_channels, pixels))
+}
+
+// 按文件头判断是 PNG、BMP 还是 PNM
+pub fn read_image(path: &str) -> io::Result<(usize, usize, usize, Vec<u8>)> {
+    let data = fs::read(path)?;
+    if data.starts_with(b"\x89PNG") {
+        read_png(&data)
+    } else if data.starts_with(b"BM") {
+        read_bmp(&data)
+    } else {
+        read_pnm(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_sizes() {
+        // 尺寸相乘溢出
+        assert!(read_pnm(b"P6 9999999999 9999999999 255\n").is_err());
+        assert!(read_pnm(b"P5 2 2 255\n\x01\x02\x03").is_err());
+        assert_eq!(
+            read_pnm(b"P5 2 1 255\n\x01\x02").unwrap(),
+            (2, 1, 1, vec![1, 2])
+        );
+
+        let bmp = |offset: u32, width: u32, height: u32| {
+            let mut data = vec![0u8; 60];
+            data[..2].copy_from_slice(b"BM");
+            data[10..14].copy_from_slice(&offset.to_le_bytes());
+            data[14..18].copy_from_slice(&40u32.to_le_bytes());
+            data[18..22].copy_from_slice(&width.to_le_bytes());
+            data[22..26].copy_from_slice(&height.to_le_bytes());
+            data[28..30].copy_from_slice(&24u16.to_le_bytes());
+            data
+        };
+        // 像素偏移超出文件
+        assert!(read_bmp(&bmp(0xFFFF, 1, 1)).is_err());
+        // 声称的尺寸远大于文件
+        assert!(read_bmp(&bmp(54, 1 << 31, 1 << 31)).is_err());
+        assert!(read_bmp(&bmp(54, 0x7FFF_FFFF, 0x7FFF_FFFF)).is_err());
+        assert_eq!(read_bmp(&bmp(54, 1, 1)).unwrap(), (1, 1, 3, vec![0, 0, 0]));
+    }
+}
@@ -0,0 +1,149 @@
+use std::io;
+
+use super::inflate::zlib_decompress;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// 还原每行的过滤，bpp 是每像素字节数
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> io::Result<Vec<u8>> {
+    let mut out = vec![0u8; stride * height];
+    for y in 0..height {
+        let line = raw
+            .get(y * (stride + 1)..(y + 1) * (stride + 1))
+            .ok_or_else(|| invalid("图像数据不完整"))?;
+        let (filter, line) = (line[0], &line[1..]);
+        let (prev, cur) = out.split_at_mut(y * stride);
+        let prev = if y == 0 {
+            None
+        } else {
+            Some(&prev[(y - 1) * stride..])
+        };
+        let cur = &mut cur[..stride];
+        for x in 0..stride {
+            let a = if x >= bpp { cur[x - bpp] } else { 0 };
+            let b = prev.map_or(0, |p| p[x]);
+            let c = match prev {
+                Some(p) if x >= bpp => p[x - bpp],
+                _ => 0,
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(invalid("无效的过滤类型")),
+            };
+            cur[x] = line[x].wrapping_add(predictor);
+        }
+    }
+    Ok(out)
+}
+
+// 读取非隔行扫描的 PNG，返回宽、高、通道数（1 或 3）和像素，透明通道会被丢弃
+pub fn read_png(data: &[u8]) -> io::Result<(usize, usize, usize, Vec<u8>)> {
+    if data.len() < 8 || &data[..8] != b"\x89PNG\r\n\x1a\n" {
+        return Err(invalid("不是 PNG 文件"));
+    }
+    let mut pos = 8;
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut idat = Vec::new();
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let name = &data[pos + 4..pos + 8];
+        let body = len
+            .checked_add(pos + 8)
+            .and_then(|end| data.get(pos + 8..end))
+            .ok_or_else(|| invalid("PNG 块不完整"))?;
+        match name {
+            b"IHDR" if len >= 13 => header = Some(body.to_vec()),
+            b"PLTE" => palette = body.to_vec(),
+            b"IDAT" => idat.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // 跳过 CRC
+        pos += 12 + len;
+    }
+    let header = header.ok_or_else(|| invalid("缺少 IHDR"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    if interlace != 0 {
+        return Err(invalid("不支持隔行扫描的 PNG"));
+    }
+    // 每像素的样本数
+    let samples = match (color_type, depth) {
+        (0, 8 | 16) => 1,
+        (2, 8 | 16) => 3,
+        (3, 8) => 1,
+        (4, 8 | 16) => 2,
+        (6, 8 | 16) => 4,
+        _ => return Err(invalid("不支持的 PNG 颜色类型或位深度")),
+    };
+    let bytes = depth as usize / 8;
+    let bpp = samples * bytes;
+    let raw = zlib_decompress(&idat)?;
+    // 每行前面有一个过滤类型字节，解压出的数据不够时不按头里的大小分配内存
+    let stride = width
+        .checked_mul(bpp)
+        .ok_or_else(|| invalid("图像尺寸过大"))?;
+    match (stride + 1).checked_mul(height) {
+        Some(len) if len <= raw.len() => {}
+        _ => return Err(invalid("图像数据不完整")),
+    }
+    let data = unfilter(&raw, stride, height, bpp)?;
+
+    let channels = if color_type == 0 || color_type == 4 {
+        1
+    } else {
+        3
+    };
+    let mut pixels = Vec::with_capacity(width * height * channels);
+    for px in data.chunks_exact(bpp) {
+        if color_type == 3 {
+            let idx = px[0] as usize * 3;
+            let rgb = palette
+                .get(idx..idx + 3)
+                .ok_or_else(|| invalid("调色板索引越界"))?;
+            pixels.extend_from_slice(rgb);
+            continue;
+        }
+        // 16 位样本只取高 8 位
+        for c in 0..channels {
+            pixels.push(px[c * bytes]);
+        }
+    }
+    Ok((width, height, channels, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::write_png;
+
+    #[test]
+    fn test_round_trip() {
+        let pixels: Vec<u8> = (0..5 * 3 * 3).map(|i| (i * 7) as u8).collect();
+        let mut png = Vec::new();
+        write_png(&mut png, 5, 3, 3, &pixels).unwrap();
+        assert_eq!(read_png(&png).unwrap(), (5, 3, 3, pixels));
+    }
+}
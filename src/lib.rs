@@ -1,138 +1,430 @@
 use std::{
+    fmt,
     fs::File,
-    io::{BufReader, Seek},
-    path::Path,
+    io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write},
     rc::Rc,
 };
 
-use application::InterchangeFormat;
+use application::{exif::Exif, InterchangeFormat};
 use bitstream::BitStream;
 use component::{
-    frame::{Frame, FrameErrorType},
+    frame::{Frame, FrameErrorType, FrameType, FrameTypeCoding},
     scan::Scan,
     Component,
 };
-use decode::dct::DCT;
-use dht::{huffman::HuffmanErrorType, HuffmanTable};
+use decode::{
+    coefficient::{coefficient_bytes, decode_coefficients, ComponentCoefficients},
+    dct::DCT,
+    OutputFormat,
+};
+use dht::HuffmanTable;
 use dqt::Dqt;
+use limits::{LimitErrorType, Limits};
 use rustc_hash::FxHashMap;
-use segment::{Segment, SegmentType};
+use segment::{Segment, SegmentErrorKind, SegmentType};
+use transform::{Transform, TransformErrorType};
 
 pub mod application;
 pub mod bitstream;
+pub mod compare;
 pub mod component;
 pub mod decode;
 pub mod dht;
 pub mod dqt;
+pub mod encode;
+pub mod export;
+pub mod import;
+pub mod limits;
 pub mod segment;
+pub mod transform;
 pub mod ui;
 pub mod zigzag;
 
+#[deprecated(note = "解码失败时会 panic，请使用返回 Result 的 decode_jpeg")]
 pub async fn get_jpeg_image_async(path: String) -> (usize, usize, Vec<u8>) {
-    get_jpeg_image(path)
+    decode_jpeg(path, OutputFormat::Rgba8).unwrap_or_else(|e| panic!("{}", e))
 }
 
+#[deprecated(note = "解码失败时会 panic，请使用返回 Result 的 decode_jpeg")]
 pub fn get_jpeg_image(path: String) -> (usize, usize, Vec<u8>) {
-    let jpg_file = File::open(path).expect("打开文件失败！");
-    // let jpg_file = File::open("s11138117.jpg").expect("打开文件失败！");
-    let mut reader = BufReader::new(jpg_file);
-    let segs = match Segment::from_file(&mut reader) {
-        Ok(segs) => segs,
-        Err(e) => {
-            panic!("解析失败: {:?}", e);
+    decode_jpeg(path, OutputFormat::Rgba8).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub struct JpegHeader {
+    pub interchange_formats: Vec<InterchangeFormat>,
+    // APPn 和 COM 段
+    pub metadata: Vec<Segment>,
+    pub frame: Frame,
+    pub dqt_map: FxHashMap<u8, Rc<Dqt>>,
+    pub dc_map: FxHashMap<u8, Rc<HuffmanTable>>,
+    pub ac_map: FxHashMap<u8, Rc<HuffmanTable>>,
+    pub scan: Scan,
+    pub scan_start: u64,
+    pub scan_end: u64,
+    pub restart_interval: Option<u16>,
+    // 解析头部时使用的限制，解码时继续用于检查内存
+    pub limits: Limits,
+}
+
+#[derive(Debug)]
+pub enum JpegErrorType {
+    IOError(io::Error),
+    // 文件在找到 EOI 之前就结束了
+    Truncated,
+    // 渐进式、无损、算术编码或非 8 位精度的图像
+    UnsupportedFrameType(u8),
+    UnsupportedPrecision(u8),
+    // 分成多次扫描的顺序编码图像
+    UnsupportedScanCount(usize),
+    // 接口不支持所要求的输出格式，如逐行输出平面格式
+    UnsupportedFormat(OutputFormat),
+    // 超出 Limits 中的某项限制
+    LimitExceeded(LimitErrorType),
+    Corrupt(String),
+}
+
+impl fmt::Display for JpegErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JpegErrorType::IOError(e) => write!(f, "读取文件失败: {}", e),
+            JpegErrorType::Truncated => write!(f, "文件不完整"),
+            JpegErrorType::UnsupportedFrameType(n) => write!(f, "不支持的帧类型: SOF{}", n),
+            JpegErrorType::UnsupportedPrecision(p) => write!(f, "不支持的采样精度: {} 位", p),
+            JpegErrorType::UnsupportedScanCount(n) => write!(f, "不支持多次扫描: 共 {} 个扫描", n),
+            JpegErrorType::UnsupportedFormat(format) => write!(f, "不支持的输出格式: {:?}", format),
+            JpegErrorType::LimitExceeded(msg) => write!(f, "超出限制: {}", msg),
+            JpegErrorType::Corrupt(msg) => write!(f, "文件已损坏: {}", msg),
         }
-    };
+    }
+}
 
-    let mut _if = Vec::new();
-    let mut dqt_map = FxHashMap::default();
-    let mut dc_map = FxHashMap::default();
-    let mut ac_map = FxHashMap::default();
-    let mut frame = None;
-    let mut scan = None;
-    let mut st = None;
-    let mut ed = None;
-    let mut restart_interval = None;
-
-    for ele in segs {
-        match ele.segment_type {
-            SegmentType::SOI => {
-                continue;
+impl From<SegmentErrorKind> for JpegErrorType {
+    fn from(e: SegmentErrorKind) -> Self {
+        match e {
+            SegmentErrorKind::IOError(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                JpegErrorType::Truncated
             }
+            SegmentErrorKind::IOError(e) => JpegErrorType::IOError(e),
+            SegmentErrorKind::LimitExceeded(e) => JpegErrorType::LimitExceeded(e),
+            e => JpegErrorType::Corrupt(format!("{:?}", e)),
+        }
+    }
+}
+
+impl JpegHeader {
+    // EXIF 中的方向标签 (1~8)
+    pub fn orientation(&self) -> Option<u16> {
+        self.interchange_formats.iter().find_map(|f| match f {
+            InterchangeFormat::EXIF(exif) => exif.get_orientation(),
+            _ => None,
+        })
+    }
+}
+
+#[deprecated(note = "解析失败时会 panic，请使用返回 Result 的 try_read_header")]
+pub fn read_header<R: Read + Seek>(reader: &mut BufReader<R>) -> JpegHeader {
+    match try_read_header(reader) {
+        Ok(header) => header,
+        Err(e) => panic!("解析失败: {}", e),
+    }
+}
+
+pub fn try_read_header<R: Read + Seek>(reader: &mut BufReader<R>) -> Result<JpegHeader, JpegErrorType> {
+    try_read_header_with_limits(reader, &Limits::default())
+}
+
+pub fn try_read_header_with_limits<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    limits: &Limits,
+) -> Result<JpegHeader, JpegErrorType> {
+    let mut builder = HeaderBuilder::new(*limits);
+    for seg in Segment::from_file_with_limits(reader, limits)? {
+        builder.add(seg)?;
+    }
+    builder.finish()
+}
+
+// 逐段收集头部信息，读取整个文件和推送式解码共用
+pub(crate) struct HeaderBuilder {
+    limits: Limits,
+    interchange_formats: Vec<InterchangeFormat>,
+    metadata: Vec<Segment>,
+    dqt_map: FxHashMap<u8, Rc<Dqt>>,
+    dc_map: FxHashMap<u8, Rc<HuffmanTable>>,
+    ac_map: FxHashMap<u8, Rc<HuffmanTable>>,
+    frame: Option<Frame>,
+    scan: Option<Scan>,
+    scan_range: Option<(u64, u64)>,
+    restart_interval: Option<u16>,
+    scan_count: usize,
+}
+
+impl HeaderBuilder {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            interchange_formats: Vec::new(),
+            metadata: Vec::new(),
+            dqt_map: FxHashMap::default(),
+            dc_map: FxHashMap::default(),
+            ac_map: FxHashMap::default(),
+            frame: None,
+            scan: None,
+            scan_range: None,
+            restart_interval: None,
+            scan_count: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, ele: Segment) -> Result<(), JpegErrorType> {
+        if let SegmentType::APPn(_) | SegmentType::COM = ele.segment_type {
+            self.metadata.push(ele.clone());
+        }
+        match ele.segment_type {
             SegmentType::APPn(n) => match n {
                 0..=1 => {
-                    _if.push(InterchangeFormat::new(n, &ele));
+                    self.interchange_formats.push(InterchangeFormat::new(n, &ele));
                 }
                 _ => {
                     // println!("不支持的段类型: APP{} !", n);
                 }
             },
             SegmentType::DQT => {
-                match Dqt::new(&mut dqt_map, ele.length, ele.data) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        panic!("In DQT: NDArray: Shape Error! {:?}", e)
-                    }
-                };
+                Dqt::new(&mut self.dqt_map, ele.length, ele.data)
+                    .map_err(|e| JpegErrorType::Corrupt(format!("DQT: {:?}", e)))?;
             }
             SegmentType::DHT => {
-                match HuffmanTable::new(&mut dc_map, &mut ac_map, ele.length, ele.data) {
-                    Ok(_) => {}
-                    Err(e) => match e {
-                        HuffmanErrorType::BitStreamError(_) => {
-                            panic!("In Huffman: BitStream Read Error!")
-                        }
-                        HuffmanErrorType::DecodeError(bs) => {
-                            panic!("In Huffman: Decode Error! BitStream:{}", bs)
-                        }
-                        HuffmanErrorType::InvalidCode(symbol, code) => {
-                            panic!("In Huffman: Invalid Code: Symbol:{}, Code:{}", symbol, code)
-                        }
-                        HuffmanErrorType::TypeConversionError => {
-                            panic!("In Huffman: Type Conversion Error!")
-                        }
-                    },
-                };
+                HuffmanTable::new(&mut self.dc_map, &mut self.ac_map, ele.length, ele.data)
+                    .map_err(|e| JpegErrorType::Corrupt(format!("DHT: {:?}", e)))?;
             }
             SegmentType::SOFn(n) => {
-                frame = match Frame::new(n, ele.data) {
-                    Ok(f) => Some(f),
-                    Err(e) => match e {
-                        FrameErrorType::InvalidFrameType(n) => {
-                            panic!("In Frame: Invalid Frame Type:{}", n)
-                        }
-                    },
+                let f = match Frame::new(n, ele.data) {
+                    Ok(f) => f,
+                    Err(FrameErrorType::InvalidFrameType(n)) => {
+                        return Err(JpegErrorType::UnsupportedFrameType(n))
+                    }
+                    Err(e) => return Err(JpegErrorType::Corrupt(format!("SOF: {:?}", e))),
                 };
+                match f.get_type() {
+                    FrameType::BaselineDCT
+                    | FrameType::ExtendedDCT(FrameTypeCoding::HuffmanCoding) => {}
+                    _ => return Err(JpegErrorType::UnsupportedFrameType(n)),
+                }
+                if f.get_precision() != 8 {
+                    return Err(JpegErrorType::UnsupportedPrecision(f.get_precision()));
+                }
+                self.limits
+                    .check_frame(&f)
+                    .map_err(JpegErrorType::LimitExceeded)?;
+                self.frame = Some(f);
             }
             SegmentType::SOS(start, end) => {
-                scan = Some(Scan::new(ele.data));
-                st = Some(start);
-                ed = Some(end);
+                self.scan_count += 1;
+                self.scan = Some(
+                    Scan::new(ele.data)
+                        .map_err(|e| JpegErrorType::Corrupt(format!("SOS: {:?}", e)))?,
+                );
+                self.scan_range = Some((start, end));
             }
             SegmentType::DRI => {
-                restart_interval = Some(u16::from_be_bytes([ele.data[0], ele.data[1]]));
+                let ri = match ele.data[..] {
+                    [a, b, ..] => u16::from_be_bytes([a, b]),
+                    _ => return Err(JpegErrorType::Corrupt("DRI 段长度不足".to_string())),
+                };
+                // 0 表示不使用复位标记
+                self.restart_interval = Some(ri).filter(|&ri| ri > 0);
             }
             _ => {
                 // println!("不支持的段类型!");
             }
         }
+        Ok(())
     }
 
-    let frame = frame.unwrap();
-    let scan = scan.unwrap();
-    let st = st.unwrap();
-    let ed = ed.unwrap();
+    pub(crate) fn finish(self) -> Result<JpegHeader, JpegErrorType> {
+        let missing = |name: &str| JpegErrorType::Corrupt(format!("缺少 {} 段", name));
+        let frame = self.frame.ok_or_else(|| missing("SOF"))?;
+        let scan = self.scan.ok_or_else(|| missing("SOS"))?;
+        let (scan_start, scan_end) = self.scan_range.ok_or_else(|| missing("SOS"))?;
+        if self.scan_count > 1 {
+            return Err(JpegErrorType::UnsupportedScanCount(self.scan_count));
+        }
+        // 只支持一次扫描包含所有分量的交错编码
+        let mut frame_ids: Vec<&u8> = frame.components.keys().collect();
+        let mut scan_ids: Vec<&u8> = scan.components.keys().collect();
+        frame_ids.sort();
+        scan_ids.sort();
+        if frame_ids != scan_ids {
+            return Err(JpegErrorType::Corrupt("扫描分量与帧分量不一致".to_string()));
+        }
+        // 交错扫描中每个 MCU 最多 10 个块
+        let blocks: usize = frame
+            .components
+            .values()
+            .map(|c| c.get_factor_x() as usize * c.get_factor_y() as usize)
+            .sum();
+        if frame.components.len() > 1 && blocks > 10 {
+            return Err(JpegErrorType::Corrupt(format!("每个 MCU 有 {} 个块", blocks)));
+        }
+
+        Ok(JpegHeader {
+            interchange_formats: self.interchange_formats,
+            metadata: self.metadata,
+            frame,
+            dqt_map: self.dqt_map,
+            dc_map: self.dc_map,
+            ac_map: self.ac_map,
+            scan,
+            scan_start,
+            scan_end,
+            restart_interval: self.restart_interval,
+            limits: self.limits,
+        })
+    }
+}
 
-    reader.seek(std::io::SeekFrom::Start(st)).unwrap();
-    let mut bs = BitStream::new(&mut reader);
+// 打开文件并解析头部，返回的 reader 可以继续用于解码
+pub fn open_jpeg(path: &str) -> Result<(BufReader<File>, JpegHeader), JpegErrorType> {
+    open_jpeg_with_limits(path, &Limits::default())
+}
+
+pub fn open_jpeg_with_limits(
+    path: &str,
+    limits: &Limits,
+) -> Result<(BufReader<File>, JpegHeader), JpegErrorType> {
+    let jpg_file = File::open(path).map_err(JpegErrorType::IOError)?;
+    let mut reader = BufReader::new(jpg_file);
+    let header = try_read_header_with_limits(&mut reader, limits)?;
+    Ok((reader, header))
+}
+
+pub fn decode_with_header<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    header: JpegHeader,
+    format: OutputFormat,
+) -> Result<(usize, usize, Vec<u8>), JpegErrorType> {
+    let frame = header.frame;
 
     let width = frame.get_width() as usize;
     let height = frame.get_height() as usize;
+    // 输出缓冲区的大小，平面格式按各分量的采样分辨率计算
+    let bytes = match format {
+        OutputFormat::YCbCr => decode::plane_dimensions(&frame)
+            .iter()
+            .map(|(w, h)| w * h)
+            .sum(),
+        _ => width * height * format.bytes_per_pixel(),
+    };
+    header
+        .limits
+        .check_memory(bytes)
+        .map_err(JpegErrorType::LimitExceeded)?;
+
+    reader
+        .seek(std::io::SeekFrom::Start(header.scan_start))
+        .map_err(JpegErrorType::IOError)?;
+    let mut bs = BitStream::new(reader);
 
     let dct = DCT::new();
-    let comps = Component::new(&frame, dqt_map, dc_map, ac_map, scan).unwrap();
-    (
-        width,
-        height,
-        decode::decode_image(&frame, comps, &mut bs, restart_interval, dct).unwrap(),
-    )
+    let comps = Component::new(&frame, header.dqt_map, header.dc_map, header.ac_map, header.scan)
+        .map_err(|e| JpegErrorType::Corrupt(format!("{:?}", e)))?;
+    let pixels = decode::decode_image(&frame, comps, &mut bs, header.restart_interval, dct, format)
+        .map_err(|e| JpegErrorType::Corrupt(e.to_string()))?;
+    Ok((width, height, pixels))
+}
+
+// 只解码 DC 系数，输出宽高为原图 1/8（向上取整）的 RGBA 图像，用于快速生成缩略图
+pub fn decode_reduced_with_header<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    header: JpegHeader,
+) -> Result<(usize, usize, Vec<u8>), JpegErrorType> {
+    let frame = header.frame;
+    header
+        .limits
+        .check_memory(decode::reduced_bytes(&frame))
+        .map_err(JpegErrorType::LimitExceeded)?;
+    reader
+        .seek(std::io::SeekFrom::Start(header.scan_start))
+        .map_err(JpegErrorType::IOError)?;
+    let mut bs = BitStream::new(reader);
+
+    let comps = Component::new(&frame, header.dqt_map, header.dc_map, header.ac_map, header.scan)
+        .map_err(|e| JpegErrorType::Corrupt(format!("{:?}", e)))?;
+    decode::decode_dc(&frame, comps, &mut bs, header.restart_interval)
+        .map_err(|e| JpegErrorType::Corrupt(e.to_string()))
+}
+
+// 只做熵解码，得到各分量的量化系数，用于无损变换和查看系数
+pub fn decode_coefficients_with_header<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    header: JpegHeader,
+) -> Result<Vec<ComponentCoefficients>, JpegErrorType> {
+    let frame = header.frame;
+    header
+        .limits
+        .check_memory(coefficient_bytes(&frame))
+        .map_err(JpegErrorType::LimitExceeded)?;
+    reader
+        .seek(std::io::SeekFrom::Start(header.scan_start))
+        .map_err(JpegErrorType::IOError)?;
+    let mut bs = BitStream::new(reader);
+
+    let comps = Component::new(&frame, header.dqt_map, header.dc_map, header.ac_map, header.scan)
+        .map_err(|e| JpegErrorType::Corrupt(format!("{:?}", e)))?;
+    decode_coefficients(&frame, comps, &mut bs, header.restart_interval)
+        .map_err(|e| JpegErrorType::Corrupt(e.to_string()))
+}
+
+// 解码内存中的 JPEG 数据，数据不完整或损坏时返回错误而不是 panic
+pub fn decode_jpeg_bytes(
+    data: &[u8],
+    format: OutputFormat,
+) -> Result<(usize, usize, Vec<u8>), JpegErrorType> {
+    decode_jpeg_bytes_with_limits(data, format, &Limits::default())
+}
+
+pub fn decode_jpeg_bytes_with_limits(
+    data: &[u8],
+    format: OutputFormat,
+    limits: &Limits,
+) -> Result<(usize, usize, Vec<u8>), JpegErrorType> {
+    let mut reader = BufReader::new(Cursor::new(data));
+    let header = try_read_header_with_limits(&mut reader, limits)?;
+    decode_with_header(&mut reader, header, format)
+}
+
+pub fn decode_jpeg(path: String, format: OutputFormat) -> Result<(usize, usize, Vec<u8>), JpegErrorType> {
+    let (mut reader, header) = open_jpeg(&path)?;
+    decode_with_header(&mut reader, header, format)
+}
+
+#[deprecated(note = "解码失败时会 panic，请使用返回 Result 的 decode_jpeg")]
+pub fn get_jpeg_image_with_format(path: String, format: OutputFormat) -> (usize, usize, Vec<u8>) {
+    match decode_jpeg(path, format) {
+        Ok(image) => image,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn get_jpeg_coefficients(path: String) -> Result<Vec<ComponentCoefficients>, JpegErrorType> {
+    let (mut reader, header) = open_jpeg(&path)?;
+    decode_coefficients_with_header(&mut reader, header)
+}
+
+pub fn transform_jpeg(input: String, output: String, t: Transform) -> Result<(), TransformErrorType> {
+    let (mut reader, header) = open_jpeg(&input).map_err(TransformErrorType::Decode)?;
+    let width = header.frame.get_width() as usize;
+    let height = header.frame.get_height() as usize;
+    let mut metadata = header.metadata.clone();
+    // 旋转和翻转后原来的方向标签不再适用，裁剪不改变方向
+    if t.to_orientation().is_some() {
+        Exif::reset_orientation(&mut metadata);
+    }
+    let coefs = decode_coefficients_with_header(&mut reader, header).map_err(TransformErrorType::Decode)?;
+    let (width, height, coefs) = transform::transform(width, height, &coefs, t)?;
+
+    let file = File::create(output).map_err(TransformErrorType::IOError)?;
+    let mut writer = BufWriter::new(file);
+    encode::write_jpeg(&mut writer, width, height, &coefs, &metadata, None).map_err(TransformErrorType::IOError)?;
+    writer.flush().map_err(TransformErrorType::IOError)
 }
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::{
+    component::frame::Frame,
+    segment::{Segment, SegmentType},
+};
+
+// 解码不可信的图片时使用的资源限制，超出限制时返回错误而不是尝试分配内存
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // 宽 x 高
+    pub max_pixels: usize,
+    // 输出缓冲区的字节数
+    pub max_memory: usize,
+    // SOS 段的个数
+    pub max_scans: usize,
+    // 所有标记段的个数
+    pub max_markers: usize,
+    // APPn 和 COM 段的总字节数
+    pub max_metadata_bytes: usize,
+}
+
+// 解析过程中累计的段数、扫描数和元数据字节数
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    markers: usize,
+    scans: usize,
+    metadata_bytes: usize,
+}
+
+#[derive(Debug)]
+pub enum LimitErrorType {
+    Pixels(usize /* 实际值 */, usize /* 上限 */),
+    Memory(usize, usize),
+    Scans(usize),
+    Markers(usize),
+    MetadataBytes(usize),
+}
+
+impl fmt::Display for LimitErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitErrorType::Pixels(n, max) => write!(f, "图像有 {} 像素，上限为 {}", n, max),
+            LimitErrorType::Memory(n, max) => {
+                write!(f, "需要 {} 字节内存，上限为 {}", n, max)
+            }
+            LimitErrorType::Scans(max) => write!(f, "扫描个数超过 {}", max),
+            LimitErrorType::Markers(max) => write!(f, "标记段个数超过 {}", max),
+            LimitErrorType::MetadataBytes(max) => write!(f, "元数据超过 {} 字节", max),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_pixels: 1 << 28,
+            max_memory: 1 << 30,
+            max_scans: 100,
+            max_markers: 4096,
+            max_metadata_bytes: 16 << 20,
+        }
+    }
+}
+
+impl Limits {
+    // 不做任何限制，只用于可信的输入
+    pub fn unlimited() -> Self {
+        Self {
+            max_pixels: usize::MAX,
+            max_memory: usize::MAX,
+            max_scans: usize::MAX,
+            max_markers: usize::MAX,
+            max_metadata_bytes: usize::MAX,
+        }
+    }
+
+    pub fn check_frame(&self, frame: &Frame) -> Result<(), LimitErrorType> {
+        let pixels = frame.get_width() as usize * frame.get_height() as usize;
+        if pixels > self.max_pixels {
+            return Err(LimitErrorType::Pixels(pixels, self.max_pixels));
+        }
+        Ok(())
+    }
+
+    // 每读到一个段调用一次
+    pub fn check_segment(
+        &self,
+        usage: &mut Usage,
+        segment: &Segment,
+    ) -> Result<(), LimitErrorType> {
+        usage.markers += 1;
+        if usage.markers > self.max_markers {
+            return Err(LimitErrorType::Markers(self.max_markers));
+        }
+        match segment.segment_type {
+            SegmentType::SOS(_, _) => {
+                usage.scans += 1;
+                if usage.scans > self.max_scans {
+                    return Err(LimitErrorType::Scans(self.max_scans));
+                }
+            }
+            SegmentType::APPn(_) | SegmentType::COM => {
+                usage.metadata_bytes += segment.data.len();
+                if usage.metadata_bytes > self.max_metadata_bytes {
+                    return Err(LimitErrorType::MetadataBytes(self.max_metadata_bytes));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn check_memory(&self, bytes: usize) -> Result<(), LimitErrorType> {
+        if bytes > self.max_memory {
+            return Err(LimitErrorType::Memory(bytes, self.max_memory));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_frame() {
+        // 100x50，一个分量
+        let frame = Frame::new(0, vec![8, 0, 50, 0, 100, 1, 1, 0x11, 0]).unwrap();
+        let limits = Limits {
+            max_pixels: 5000,
+            ..Limits::default()
+        };
+        assert!(limits.check_frame(&frame).is_ok());
+        let limits = Limits {
+            max_pixels: 4999,
+            ..Limits::default()
+        };
+        assert!(matches!(
+            limits.check_frame(&frame),
+            Err(LimitErrorType::Pixels(5000, 4999))
+        ));
+        assert!(Limits::unlimited().check_memory(usize::MAX).is_ok());
+    }
+}
@@ -1,6 +1,10 @@
+use std::{env, path::PathBuf};
+
 use iced::{Application, Settings};
 use my_tiny_jpeg_decoder::ui::App;
 
 fn main() -> iced::Result {
-    App::run(Settings::default())
+    // 作为"打开方式"使用时文件路径通过命令行传入
+    let paths: Vec<PathBuf> = env::args_os().skip(1).map(PathBuf::from).collect();
+    App::run(Settings::with_flags(paths))
 }
@@ -1,8 +1,11 @@
 use std::{
-    fs::File,
-    io::{self, BufReader, Read, Seek},
+    io::{self, BufReader, Read, Seek, Write},
 };
 
+use crate::limits::{LimitErrorType, Limits, Usage};
+
+pub mod rewrite;
+
 #[derive(Debug, Clone, Copy)]
 pub enum SegmentType {
     SOI,
@@ -16,9 +19,46 @@ pub enum SegmentType {
     EOI,
 }
 
+impl SegmentType {
+    // SOS 段的熵编码数据范围需要读到数据后才能确定，这里为 (0, 0)
+    pub fn from_marker(marker: u8) -> Option<Self> {
+        match marker {
+            0xD8 => Some(SegmentType::SOI),
+            0xDB => Some(SegmentType::DQT),
+            0xFE => Some(SegmentType::COM),
+            0xC4 => Some(SegmentType::DHT),
+            0xDA => Some(SegmentType::SOS(0, 0)),
+            0xDD => Some(SegmentType::DRI),
+            0xD9 => Some(SegmentType::EOI),
+            // C4 (DHT)、C8 (JPG)、CC (DAC) 不是帧头
+            n if (0xC0..=0xCF).contains(&n) && ![0xC4, 0xC8, 0xCC].contains(&n) => {
+                Some(SegmentType::SOFn(n - 0xC0))
+            }
+            n if (0xE0..0xF0).contains(&n) => Some(SegmentType::APPn(n - 0xE0)),
+            _ => None,
+        }
+    }
+
+    pub fn marker(&self) -> u8 {
+        match self {
+            SegmentType::SOI => 0xD8,
+            SegmentType::APPn(n) => 0xE0 + n,
+            SegmentType::DQT => 0xDB,
+            SegmentType::SOFn(n) => 0xC0 + n,
+            SegmentType::DHT => 0xC4,
+            SegmentType::DRI => 0xDD,
+            SegmentType::SOS(_, _) => 0xDA,
+            SegmentType::COM => 0xFE,
+            SegmentType::EOI => 0xD9,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Segment {
     pub segment_type: SegmentType,
+    // 标记在文件中的偏移，新建的段为 0
+    pub offset: u64,
     pub length: u16,
     pub data: Vec<u8>,
 }
@@ -29,10 +69,16 @@ pub enum SegmentErrorKind {
     InvalidSegment,
     InvalidSegmentType,
     InvalidSegmentLength,
+    LimitExceeded(LimitErrorType),
+    // 要写出的数据放不进一个段（或 ICC 配置文件超过 255 段）
+    DataTooLarge(usize),
 }
 
+// 长度字段是 16 位，并且包含它自身的 2 字节
+pub const MAX_SEGMENT_DATA: usize = 65535 - 2;
+
 impl Segment {
-    fn new(reader: &mut BufReader<File>, offset: usize) -> Result<Self, SegmentErrorKind> {
+    fn new<R: Read + Seek>(reader: &mut BufReader<R>, offset: usize) -> Result<Self, SegmentErrorKind> {
         reader
             .seek(io::SeekFrom::Start(offset as u64))
             .map_err(|e| SegmentErrorKind::IOError(e))?;
@@ -50,19 +96,25 @@ impl Segment {
 
         // 判断类型
         let segment_type = match buffer[1] {
-            0xD8 => SegmentType::SOI,
-            0xDB => SegmentType::DQT,
-            0xFE => SegmentType::COM,
-            0xC4 => SegmentType::DHT,
             0xDA => {
+                // 跳过 SOS 头部，找到熵编码数据的范围
+                let mut len = [0u8; 2];
+                reader
+                    .read_exact(&mut len)
+                    .map_err(|e| SegmentErrorKind::IOError(e))?;
+                let header_len = u16::from_be_bytes(len) as i64;
+                if header_len < 2 {
+                    return Err(SegmentErrorKind::InvalidSegmentLength);
+                }
                 reader
-                    .seek(io::SeekFrom::Current(12))
+                    .seek(io::SeekFrom::Current(header_len - 2))
                     .map_err(|e| SegmentErrorKind::IOError(e))?;
                 let scandata_start = reader
                     .stream_position()
                     .map_err(|e| SegmentErrorKind::IOError(e))?;
                 let scandata_end;
 
+                // 熵编码数据在遇到 RSTn 和 0xFF00 以外的第一个标记时结束
                 loop {
                     let mut buffer = [0u8; 1];
                     reader
@@ -72,7 +124,7 @@ impl Segment {
                         reader
                             .read_exact(&mut buffer)
                             .map_err(|e| SegmentErrorKind::IOError(e))?;
-                        if buffer[0] == 0xD9 {
+                        if buffer[0] != 0x00 && !(0xD0..=0xD7).contains(&buffer[0]) {
                             scandata_end = reader
                                 .stream_position()
                                 .map_err(|e| SegmentErrorKind::IOError(e))?
@@ -82,25 +134,16 @@ impl Segment {
                     }
                 }
                 reader
-                    .seek(io::SeekFrom::Start(scandata_start - 12))
+                    .seek(io::SeekFrom::Start(scandata_start - header_len as u64))
                     .map_err(|e| SegmentErrorKind::IOError(e))?;
                 SegmentType::SOS(scandata_start, scandata_end)
             }
-            0xDD => SegmentType::DRI,
-            0xD9 => SegmentType::EOI,
-            n => {
-                if n >= 0xC0 && n < 0xC4 {
-                    SegmentType::SOFn(n - 0xC0)
-                } else if n >= 0xE0 && n < 0xF0 {
-                    SegmentType::APPn(n - 0xE0)
-                } else {
-                    return Err(SegmentErrorKind::InvalidSegmentType);
-                }
-            }
+            n => SegmentType::from_marker(n).ok_or(SegmentErrorKind::InvalidSegmentType)?,
         };
         if let SegmentType::SOI = segment_type {
             return Ok(Self {
                 segment_type: segment_type,
+                offset: offset as u64,
                 length: 0,
                 data: vec![],
             });
@@ -108,6 +151,7 @@ impl Segment {
         if let SegmentType::EOI = segment_type {
             return Ok(Self {
                 segment_type: segment_type,
+                offset: offset as u64,
                 length: 0,
                 data: vec![],
             });
@@ -117,7 +161,7 @@ impl Segment {
             .read_exact(&mut buffer)
             .map_err(|e| SegmentErrorKind::IOError(e))?;
         let length = u16::from_be_bytes([buffer[0], buffer[1]]);
-        if length > 65533 {
+        if length < 2 {
             return Err(SegmentErrorKind::InvalidSegmentLength);
         }
 
@@ -128,17 +172,75 @@ impl Segment {
 
         Ok(Self {
             segment_type: segment_type,
+            offset: offset as u64,
             length: length,
             data: data,
         })
     }
 
-    pub fn from_file(reader: &mut BufReader<File>) -> Result<Vec<Self>, SegmentErrorKind> {
+    pub fn with_data(segment_type: SegmentType, data: Vec<u8>) -> Result<Self, SegmentErrorKind> {
+        if data.len() > MAX_SEGMENT_DATA {
+            return Err(SegmentErrorKind::DataTooLarge(data.len()));
+        }
+        Ok(Self {
+            segment_type,
+            offset: 0,
+            length: data.len() as u16 + 2,
+            data,
+        })
+    }
+
+    // APPn 段开头以 0 结尾的标识字符串，如 "JFIF"、"Exif"、"ICC_PROFILE"
+    pub fn identifier(&self) -> &[u8] {
+        match self.segment_type {
+            SegmentType::APPn(_) => {
+                let end = self.data.iter().position(|&b| b == 0).unwrap_or(0);
+                &self.data[..end]
+            }
+            _ => &[],
+        }
+    }
+
+    // 序列化为标记、长度和数据，SOS 段只包含头部，不包含熵编码数据
+    // data 是公开的，创建之后仍可能被改得过长，所以这里再检查一次
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SegmentErrorKind> {
+        let mut bytes = vec![0xFF, self.segment_type.marker()];
+        if let SegmentType::SOI | SegmentType::EOI = self.segment_type {
+            return Ok(bytes);
+        }
+        if self.data.len() > MAX_SEGMENT_DATA {
+            return Err(SegmentErrorKind::DataTooLarge(self.data.len()));
+        }
+        bytes.extend_from_slice(&(self.data.len() as u16 + 2).to_be_bytes());
+        bytes.extend_from_slice(&self.data);
+        Ok(bytes)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let bytes = self.to_bytes().map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", e))
+        })?;
+        writer.write_all(&bytes)
+    }
+
+    pub fn from_file<R: Read + Seek>(reader: &mut BufReader<R>) -> Result<Vec<Self>, SegmentErrorKind> {
+        Self::from_file_with_limits(reader, &Limits::default())
+    }
+
+    // 读取所有段，段数、扫描数和元数据大小超出限制时提前返回
+    pub fn from_file_with_limits<R: Read + Seek>(
+        reader: &mut BufReader<R>,
+        limits: &Limits,
+    ) -> Result<Vec<Self>, SegmentErrorKind> {
         let mut i = 0;
         let mut segments = Vec::new();
         let mut offset = 0;
+        let mut usage = Usage::default();
         loop {
             let segment = Self::new(reader, offset)?;
+            limits
+                .check_segment(&mut usage, &segment)
+                .map_err(SegmentErrorKind::LimitExceeded)?;
             // println!(
             //     "Segment {}: {{Type:{:?},Length:{}}}",
             //     i, segment.segment_type, segment.length
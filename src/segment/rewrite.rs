@@ -0,0 +1,222 @@
+use std::{
+    io::{self, BufReader, Read, Seek, Write},
+};
+
+use crate::application::exif::Exif;
+
+use super::{Segment, SegmentErrorKind, SegmentType, MAX_SEGMENT_DATA};
+
+pub enum SegmentEdit {
+    // 删除某种类型的所有段，只对 APPn 和 COM 有效
+    Strip(SegmentType),
+    // 删除类型和标识都相同的段，如 APP1 "Exif"，只对 APPn 和 COM 有效
+    StripIdentified(SegmentType, Vec<u8>),
+    // 只删除 Exif 中的 GPS 信息，保留方向、相机和日期等其他标签
+    StripGps,
+    Insert(Segment),
+    // 替换类型和标识都相同的段，没有的话就插入
+    Replace(Segment),
+}
+
+fn is_metadata(seg_type: SegmentType) -> bool {
+    matches!(seg_type, SegmentType::APPn(_) | SegmentType::COM)
+}
+
+fn same_type(a: SegmentType, b: SegmentType) -> bool {
+    a.marker() == b.marker()
+}
+
+// 插入到 SOI 和开头的 APPn 段之后
+fn insert(segs: &mut Vec<Segment>, new: &Segment) {
+    let pos = segs
+        .iter()
+        .position(|seg| !matches!(seg.segment_type, SegmentType::SOI | SegmentType::APPn(_)))
+        .unwrap_or(segs.len());
+    segs.insert(pos, new.clone());
+}
+
+fn apply(mut segs: Vec<Segment>, edits: &[SegmentEdit]) -> Vec<Segment> {
+    for edit in edits {
+        match edit {
+            SegmentEdit::Strip(seg_type) => {
+                segs.retain(|seg| {
+                    !(is_metadata(seg.segment_type) && same_type(seg.segment_type, *seg_type))
+                });
+            }
+            SegmentEdit::StripIdentified(seg_type, id) => {
+                segs.retain(|seg| {
+                    !(is_metadata(seg.segment_type)
+                        && same_type(seg.segment_type, *seg_type)
+                        && seg.identifier() == &id[..])
+                });
+            }
+            SegmentEdit::StripGps => {
+                for seg in &mut segs {
+                    if matches!(seg.segment_type, SegmentType::APPn(1))
+                        && seg.identifier() == b"Exif"
+                    {
+                        if let Some(data) = Exif::strip_gps(&seg.data) {
+                            seg.data = data;
+                        }
+                    }
+                }
+            }
+            SegmentEdit::Insert(new) => insert(&mut segs, new),
+            SegmentEdit::Replace(new) => {
+                let matched = |seg: &Segment| {
+                    same_type(seg.segment_type, new.segment_type)
+                        && seg.identifier() == new.identifier()
+                };
+                match segs.iter().position(matched) {
+                    Some(pos) => {
+                        segs.retain(|seg| !matched(seg));
+                        segs.insert(pos, new.clone());
+                    }
+                    None => insert(&mut segs, new),
+                }
+            }
+        }
+    }
+    segs
+}
+
+// 按 edits 修改元数据段后重新写出整个文件，熵编码数据原样复制，不重新压缩
+pub fn rewrite<W: Write, R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    writer: &mut W,
+    edits: &[SegmentEdit],
+) -> Result<(), SegmentErrorKind> {
+    let segs = apply(Segment::from_file(reader)?, edits);
+
+    for seg in segs {
+        writer
+            .write_all(&seg.to_bytes()?)
+            .map_err(SegmentErrorKind::IOError)?;
+        if let SegmentType::SOS(start, end) = seg.segment_type {
+            reader
+                .seek(io::SeekFrom::Start(start))
+                .map_err(SegmentErrorKind::IOError)?;
+            io::copy(&mut reader.by_ref().take(end - start), writer)
+                .map_err(SegmentErrorKind::IOError)?;
+        }
+    }
+    Ok(())
+}
+
+// 把 ICC 配置文件拆分成 APP2 段，每段最多 65519 字节，序号只有一个字节，最多 255 段
+pub fn icc_segments(profile: &[u8]) -> Result<Vec<Segment>, SegmentErrorKind> {
+    const HEADER: &[u8] = b"ICC_PROFILE\0";
+    // 段长度最大 65535，减去长度字段本身、标识和序号
+    let chunk_size = MAX_SEGMENT_DATA - HEADER.len() - 2;
+    let count = profile.len().div_ceil(chunk_size);
+    if count > 255 {
+        return Err(SegmentErrorKind::DataTooLarge(profile.len()));
+    }
+    profile
+        .chunks(chunk_size)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut data = HEADER.to_vec();
+            data.extend_from_slice(&[i as u8 + 1, count as u8]);
+            data.extend_from_slice(chunk);
+            Segment::with_data(SegmentType::APPn(2), data)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(n: u8, data: &[u8]) -> Segment {
+        Segment::with_data(SegmentType::APPn(n), data.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let segs = vec![
+            Segment::with_data(SegmentType::SOI, vec![]).unwrap(),
+            app(0, b"JFIF\0\x01\x02"),
+            app(1, b"Exif\0\0gps"),
+            app(1, b"http://ns.adobe.com/xap/1.0/\0xmp"),
+            Segment::with_data(SegmentType::DQT, vec![0; 65]).unwrap(),
+            Segment::with_data(SegmentType::EOI, vec![]).unwrap(),
+        ];
+        let segs = apply(
+            segs,
+            &[
+                SegmentEdit::StripIdentified(SegmentType::APPn(1), b"Exif".to_vec()),
+                SegmentEdit::Insert(
+                    Segment::with_data(SegmentType::COM, b"hello".to_vec()).unwrap(),
+                ),
+                SegmentEdit::Replace(app(1, b"http://ns.adobe.com/xap/1.0/\0new")),
+            ],
+        );
+        let markers: Vec<u8> = segs.iter().map(|seg| seg.segment_type.marker()).collect();
+        assert_eq!(markers, vec![0xD8, 0xE0, 0xE1, 0xFE, 0xDB, 0xD9]);
+        assert_eq!(segs[2].data, b"http://ns.adobe.com/xap/1.0/\0new");
+        assert_eq!(segs[3].to_bytes().unwrap(), b"\xFF\xFE\x00\x07hello");
+
+        // 只删除元数据段，其他段的标识为空，不会被空标识匹配到
+        let segs = apply(
+            segs,
+            &[SegmentEdit::StripIdentified(SegmentType::DQT, vec![])],
+        );
+        assert_eq!(segs.len(), 6);
+
+        // 没有 GPS 信息的 Exif 段保持不变
+        let exif = app(1, &Exif::orientation_only(6));
+        let segs = apply(vec![exif.clone()], &[SegmentEdit::StripGps]);
+        assert_eq!(segs[0].data, exif.data);
+    }
+
+    #[test]
+    fn test_rewrite_keeps_scan_data() {
+        let path = "tests/data/restart_420.jpg";
+        let input = std::fs::read(path).unwrap();
+        let mut reader = BufReader::new(std::io::Cursor::new(&input));
+        let mut output = Vec::new();
+        let edits = [
+            SegmentEdit::Insert(app(1, b"Exif\0\0data")),
+            SegmentEdit::Strip(SegmentType::APPn(0)),
+        ];
+        rewrite(&mut reader, &mut output, &edits).unwrap();
+
+        // 熵编码数据（包括 RST 标记）逐字节相同
+        let scans = |data: &[u8]| -> Vec<Vec<u8>> {
+            let mut reader = BufReader::new(std::io::Cursor::new(data));
+            Segment::from_file(&mut reader)
+                .unwrap()
+                .iter()
+                .filter_map(|seg| match seg.segment_type {
+                    SegmentType::SOS(start, end) => {
+                        Some(data[start as usize..end as usize].to_vec())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        let before = scans(&input);
+        assert!(!before.is_empty());
+        assert_eq!(scans(&output), before);
+        assert_ne!(output, input);
+    }
+
+    #[test]
+    fn test_too_large() {
+        let data = vec![0u8; MAX_SEGMENT_DATA + 1];
+        assert!(matches!(
+            Segment::with_data(SegmentType::COM, data.clone()),
+            Err(SegmentErrorKind::DataTooLarge(_))
+        ));
+        let mut seg = Segment::with_data(SegmentType::COM, vec![]).unwrap();
+        seg.data = data;
+        assert!(seg.to_bytes().is_err());
+        assert!(seg.write_to(&mut Vec::new()).is_err());
+
+        // 每段 65519 字节，255 段刚好放得下
+        let chunk = MAX_SEGMENT_DATA - 14;
+        assert_eq!(icc_segments(&vec![0; chunk * 255]).unwrap().len(), 255);
+        assert!(icc_segments(&vec![0; chunk * 255 + 1]).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use std::{io, rc::Rc};
+
+use crate::{decode::coefficient::ComponentCoefficients, dqt::Dqt, JpegErrorType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    Transverse,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // 起点会向下对齐到 MCU 边界
+    Crop {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl Transform {
+    // EXIF 方向标签对应的、把图像转正所需的变换，1 和无效值返回 None
+    pub fn from_orientation(orientation: u16) -> Option<Self> {
+        match orientation {
+            2 => Some(Transform::FlipHorizontal),
+            3 => Some(Transform::Rotate180),
+            4 => Some(Transform::FlipVertical),
+            5 => Some(Transform::Transpose),
+            6 => Some(Transform::Rotate90),
+            7 => Some(Transform::Transverse),
+            8 => Some(Transform::Rotate270),
+            _ => None,
+        }
+    }
+
+    // from_orientation 的逆映射，裁剪没有对应的方向标签
+    pub fn to_orientation(&self) -> Option<u16> {
+        match self {
+            Transform::FlipHorizontal => Some(2),
+            Transform::Rotate180 => Some(3),
+            Transform::FlipVertical => Some(4),
+            Transform::Transpose => Some(5),
+            Transform::Rotate90 => Some(6),
+            Transform::Transverse => Some(7),
+            Transform::Rotate270 => Some(8),
+            Transform::Crop { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TransformErrorType {
+    // 图像小于一个 MCU，无法无损翻转
+    ImageTooSmall,
+    InvalidCrop,
+    // 读取或解码输入文件失败
+    Decode(JpegErrorType),
+    // 写入输出文件失败
+    IOError(io::Error),
+}
+
+// 在 DCT 域上做无损变换，返回变换后的宽、高和系数
+// 翻转方向上不完整的 MCU 无法无损处理，会被裁掉（相当于 jpegtran -trim）
+pub fn transform(
+    width: usize,
+    height: usize,
+    comps: &[ComponentCoefficients],
+    transform: Transform,
+) -> Result<(usize, usize, Vec<ComponentCoefficients>), TransformErrorType> {
+    let image = (width, height, comps.to_vec());
+    let image = match transform {
+        Transform::FlipHorizontal => flip_horizontal(image)?,
+        Transform::FlipVertical => flip_vertical(image)?,
+        Transform::Transpose => transpose(image),
+        Transform::Transverse => flip_vertical(flip_horizontal(transpose(image))?)?,
+        Transform::Rotate90 => flip_horizontal(transpose(image))?,
+        Transform::Rotate180 => flip_vertical(flip_horizontal(image)?)?,
+        Transform::Rotate270 => flip_vertical(transpose(image))?,
+        Transform::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => crop(image, x, y, width, height)?,
+    };
+    Ok(image)
+}
+
+type Image = (usize, usize, Vec<ComponentCoefficients>);
+
+fn max_factors(comps: &[ComponentCoefficients]) -> (usize, usize) {
+    if comps.len() == 1 {
+        // 单分量是非交错扫描，MCU 就是一个块
+        return (1, 1);
+    }
+    let max_x = comps.iter().map(|c| c.factor_x as usize).max().unwrap_or(1);
+    let max_y = comps.iter().map(|c| c.factor_y as usize).max().unwrap_or(1);
+    (max_x, max_y)
+}
+
+fn factors(comp: &ComponentCoefficients, single: bool) -> (usize, usize) {
+    if single {
+        (1, 1)
+    } else {
+        (comp.factor_x as usize, comp.factor_y as usize)
+    }
+}
+
+// 按新的块网格重新排列，src 给出新块 (x, y) 对应的原块坐标，map 变换块内的系数
+fn remap(
+    comp: &ComponentCoefficients,
+    blocks_x: usize,
+    blocks_y: usize,
+    map: impl Fn(&[i16; 64]) -> [i16; 64],
+    src: impl Fn(usize, usize) -> (usize, usize),
+) -> ComponentCoefficients {
+    let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let (sx, sy) = src(x, y);
+            blocks.push(map(comp.block(sx, sy)));
+        }
+    }
+    ComponentCoefficients {
+        blocks_x,
+        blocks_y,
+        blocks,
+        ..comp.clone()
+    }
+}
+
+// 水平翻转：奇数列的系数取反
+fn flip_block_horizontal(block: &[i16; 64]) -> [i16; 64] {
+    let mut result = *block;
+    for (i, c) in result.iter_mut().enumerate() {
+        if i % 8 % 2 == 1 {
+            *c = -*c;
+        }
+    }
+    result
+}
+
+// 垂直翻转：奇数行的系数取反
+fn flip_block_vertical(block: &[i16; 64]) -> [i16; 64] {
+    let mut result = *block;
+    for (i, c) in result.iter_mut().enumerate() {
+        if i / 8 % 2 == 1 {
+            *c = -*c;
+        }
+    }
+    result
+}
+
+fn transpose_block(block: &[i16; 64]) -> [i16; 64] {
+    let mut result = [0; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            result[x * 8 + y] = block[y * 8 + x];
+        }
+    }
+    result
+}
+
+fn flip_horizontal((width, height, comps): Image) -> Result<Image, TransformErrorType> {
+    let (max_x, max_y) = max_factors(&comps);
+    let x_cnt = width / (max_x * 8);
+    let y_cnt = height.div_ceil(max_y * 8);
+    if x_cnt == 0 {
+        return Err(TransformErrorType::ImageTooSmall);
+    }
+    let single = comps.len() == 1;
+    let comps = comps
+        .iter()
+        .map(|comp| {
+            let (h, v) = factors(comp, single);
+            let blocks_x = x_cnt * h;
+            remap(
+                comp,
+                blocks_x,
+                y_cnt * v,
+                flip_block_horizontal,
+                |x, y| (blocks_x - 1 - x, y),
+            )
+        })
+        .collect();
+    Ok((x_cnt * max_x * 8, height, comps))
+}
+
+fn flip_vertical((width, height, comps): Image) -> Result<Image, TransformErrorType> {
+    let (max_x, max_y) = max_factors(&comps);
+    let x_cnt = width.div_ceil(max_x * 8);
+    let y_cnt = height / (max_y * 8);
+    if y_cnt == 0 {
+        return Err(TransformErrorType::ImageTooSmall);
+    }
+    let single = comps.len() == 1;
+    let comps = comps
+        .iter()
+        .map(|comp| {
+            let (h, v) = factors(comp, single);
+            let blocks_y = y_cnt * v;
+            remap(
+                comp,
+                x_cnt * h,
+                blocks_y,
+                flip_block_vertical,
+                |x, y| (x, blocks_y - 1 - y),
+            )
+        })
+        .collect();
+    Ok((width, y_cnt * max_y * 8, comps))
+}
+
+// 量化表也要一起转置，多个分量共用的表只转置一次
+fn transpose((width, height, comps): Image) -> Image {
+    let mut tables: Vec<(u8, Rc<Dqt>)> = Vec::new();
+    let comps = comps
+        .iter()
+        .map(|comp| {
+            let mut comp = remap(
+                comp,
+                comp.blocks_y,
+                comp.blocks_x,
+                transpose_block,
+                |x, y| (y, x),
+            );
+            (comp.factor_x, comp.factor_y) = (comp.factor_y, comp.factor_x);
+            let id = comp.dqt.id();
+            comp.dqt = match tables.iter().find(|(i, _)| *i == id) {
+                Some((_, dqt)) => dqt.clone(),
+                None => {
+                    let dqt = Rc::new(comp.dqt.transposed());
+                    tables.push((id, dqt.clone()));
+                    dqt
+                }
+            };
+            comp
+        })
+        .collect();
+    (height, width, comps)
+}
+
+fn crop(
+    (width, height, comps): Image,
+    x: usize,
+    y: usize,
+    crop_width: usize,
+    crop_height: usize,
+) -> Result<Image, TransformErrorType> {
+    let (max_x, max_y) = max_factors(&comps);
+    let x0 = x / (max_x * 8);
+    let y0 = y / (max_y * 8);
+    let left = x0 * max_x * 8;
+    let top = y0 * max_y * 8;
+    if left >= width || top >= height || crop_width == 0 || crop_height == 0 {
+        return Err(TransformErrorType::InvalidCrop);
+    }
+    // 起点向下对齐后，宽高要把对齐多出来的部分补上
+    let new_width = (crop_width + x - left).min(width - left);
+    let new_height = (crop_height + y - top).min(height - top);
+    let x_cnt = new_width.div_ceil(max_x * 8);
+    let y_cnt = new_height.div_ceil(max_y * 8);
+
+    let single = comps.len() == 1;
+    let comps = comps
+        .iter()
+        .map(|comp| {
+            let (h, v) = factors(comp, single);
+            remap(
+                comp,
+                x_cnt * h,
+                y_cnt * v,
+                |b| *b,
+                |x, y| (x0 * h + x, y0 * v + y),
+            )
+        })
+        .collect();
+    Ok((new_width, new_height, comps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_transforms() {
+        let mut block = [0i16; 64];
+        for (i, c) in block.iter_mut().enumerate() {
+            *c = i as i16 + 1;
+        }
+        assert_eq!(flip_block_horizontal(&flip_block_horizontal(&block)), block);
+        assert_eq!(flip_block_vertical(&flip_block_vertical(&block)), block);
+        assert_eq!(transpose_block(&transpose_block(&block)), block);
+
+        let flipped = flip_block_horizontal(&block);
+        assert_eq!(flipped[0], 1);
+        assert_eq!(flipped[1], -2);
+        assert_eq!(flipped[8], 9);
+        let transposed = transpose_block(&block);
+        assert_eq!(transposed[1], block[8]);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    path::{Path, PathBuf},
+    thread,
+};
+
+use iced::futures::channel::oneshot;
+use iced::widget::image;
+
+use crate::{decode::OutputFormat, decode_jpeg};
+
+// 解码后的图像
+#[derive(Debug, Clone)]
+pub struct CachedImage {
+    pub handle: image::Handle,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl CachedImage {
+    // RGBA 像素占用的字节数
+    fn bytes(&self) -> usize {
+        self.width * self.height * 4
+    }
+}
+
+// 按最近使用顺序淘汰的图像缓存，总字节数不超过 max_bytes
+pub struct ImageCache {
+    // 最近使用的在末尾
+    entries: Vec<(PathBuf, CachedImage)>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl ImageCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.iter().any(|(p, _)| p == path)
+    }
+
+    pub fn get(&mut self, path: &Path) -> Option<CachedImage> {
+        let i = self.entries.iter().position(|(p, _)| p == path)?;
+        let entry = self.entries.remove(i);
+        let image = entry.1.clone();
+        self.entries.push(entry);
+        Some(image)
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(i) = self.entries.iter().position(|(p, _)| p == path) {
+            self.bytes -= self.entries.remove(i).1.bytes();
+        }
+    }
+
+    // 比整个缓存还大的图像不缓存
+    pub fn insert(&mut self, path: PathBuf, image: CachedImage) {
+        self.remove(&path);
+        if image.bytes() > self.max_bytes {
+            return;
+        }
+        while self.bytes + image.bytes() > self.max_bytes {
+            self.bytes -= self.entries.remove(0).1.bytes();
+        }
+        self.bytes += image.bytes();
+        self.entries.push((path, image));
+    }
+}
+
+// 在单独的线程里完整解码，用于提前解码相邻的文件，失败时返回 None
+pub async fn load(path: PathBuf) -> (PathBuf, Option<CachedImage>) {
+    let (sender, receiver) = oneshot::channel();
+    let file = path.display().to_string();
+    thread::spawn(move || {
+        let image = decode_jpeg(file, OutputFormat::Rgba8)
+            .ok()
+            .map(|(width, height, pixels)| CachedImage {
+                handle: image::Handle::from_pixels(width as u32, height as u32, pixels),
+                width,
+                height,
+            });
+        let _ = sender.send(image);
+    });
+    (path, receiver.await.ok().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize) -> CachedImage {
+        CachedImage {
+            handle: image::Handle::from_pixels(width as u32, 1, vec![0; width * 4]),
+            width,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn test_evict_least_recent() {
+        let mut cache = ImageCache::new(40);
+        cache.insert("a".into(), image(4));
+        cache.insert("b".into(), image(4));
+        assert!(cache.get(Path::new("a")).is_some());
+        // 放入 c 时淘汰最久没有用过的 b
+        cache.insert("c".into(), image(3));
+        assert!(cache.contains(Path::new("a")));
+        assert!(!cache.contains(Path::new("b")));
+        assert!(cache.contains(Path::new("c")));
+        // 太大的图像不缓存
+        cache.insert("d".into(), image(11));
+        assert!(!cache.contains(Path::new("d")));
+    }
+}
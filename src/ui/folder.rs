@@ -0,0 +1,141 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Name,
+    // 按修改时间，时间相同的按文件名
+    Date,
+}
+
+// 当前图片所在目录中的所有 JPEG 文件
+pub struct Folder {
+    files: Vec<PathBuf>,
+    index: usize,
+    sort: SortOrder,
+}
+
+pub fn is_jpeg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+}
+
+fn list(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let file = entry?.path();
+        if file.is_file() && is_jpeg(&file) {
+            files.push(file);
+        }
+    }
+    Ok(files)
+}
+
+impl Folder {
+    // 列出 path 所在目录的 JPEG 文件，当前文件为 path
+    pub fn open(path: &Path, sort: SortOrder) -> io::Result<Self> {
+        // 相对路径的上级目录可能是空字符串
+        let path = &fs::canonicalize(path)?;
+        let mut files = list(path.parent().unwrap_or(Path::new("/")))?;
+        // 即使扩展名不对也能浏览打开的文件
+        if !files.iter().any(|f| f == path) {
+            files.push(path.to_path_buf());
+        }
+        let mut folder = Self {
+            files,
+            index: 0,
+            sort,
+        };
+        folder.set_sort(sort);
+        folder.index = folder.files.iter().position(|f| f == path).unwrap_or(0);
+        Ok(folder)
+    }
+
+    // 列出目录中的 JPEG 文件，当前文件为排序后的第一个
+    pub fn open_dir(dir: &Path, sort: SortOrder) -> io::Result<Self> {
+        let files = list(&fs::canonicalize(dir)?)?;
+        if files.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "目录中没有 JPEG 文件",
+            ));
+        }
+        let mut folder = Self {
+            files,
+            index: 0,
+            sort,
+        };
+        folder.set_sort(sort);
+        folder.index = 0;
+        Ok(folder)
+    }
+
+    // 重新排序，当前文件保持不变
+    pub fn set_sort(&mut self, sort: SortOrder) {
+        let current = self.files[self.index].clone();
+        self.sort = sort;
+        match sort {
+            SortOrder::Name => self.files.sort(),
+            SortOrder::Date => {
+                let modified = |f: &PathBuf| {
+                    fs::metadata(f)
+                        .and_then(|m| m.modified())
+                        .unwrap_or(SystemTime::UNIX_EPOCH)
+                };
+                self.files.sort_by_cached_key(|f| (modified(f), f.clone()));
+            }
+        }
+        self.index = self.files.iter().position(|f| *f == current).unwrap_or(0);
+    }
+
+    pub fn sort(&self) -> SortOrder {
+        self.sort
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // 切换到第 index 个文件
+    pub fn select(&mut self, index: usize) -> &Path {
+        self.index = index.min(self.files.len() - 1);
+        self.current()
+    }
+
+    pub fn current(&self) -> &Path {
+        &self.files[self.index]
+    }
+
+    // 当前文件的序号（从 1 开始）和文件总数
+    pub fn position(&self) -> (usize, usize) {
+        (self.index + 1, self.files.len())
+    }
+
+    // 移到下一个或上一个文件，到头后从另一端继续
+    pub fn step(&mut self, forward: bool) -> &Path {
+        let len = self.files.len();
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+        self.current()
+    }
+
+    // 前后相邻的文件，用于提前解码
+    pub fn neighbours(&self) -> Vec<&Path> {
+        let len = self.files.len();
+        let mut paths = vec![];
+        for i in [self.index + 1, self.index + len - 1] {
+            let path = self.files[i % len].as_path();
+            if i % len != self.index && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+}
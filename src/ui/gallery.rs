@@ -0,0 +1,204 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use iced::futures::channel::oneshot;
+use iced::widget::scrollable::Viewport;
+use iced::widget::{button, column, container, image, row, scrollable, text};
+use iced::{theme, Alignment, Element, Length, Size};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    application::InterchangeFormat, decode::OutputFormat, decode_jpeg_bytes,
+    decode_reduced_with_header, export::pixels::downscale, open_jpeg, JpegErrorType, JpegHeader,
+};
+
+// 缩略图的最大边长
+pub const THUMB_SIZE: usize = 160;
+const CELL_WIDTH: f32 = 176.0;
+const CELL_HEIGHT: f32 = 200.0;
+const SPACING: f32 = 8.0;
+// 同时生成缩略图的文件数
+const MAX_LOADING: usize = 4;
+
+// 目录中所有 JPEG 文件的缩略图网格，只为滚动到的文件生成缩略图
+pub struct Gallery {
+    // None 表示生成失败
+    thumbs: FxHashMap<PathBuf, Option<image::Handle>>,
+    loading: FxHashSet<PathBuf>,
+    // 滚动的距离和显示区域的大小
+    offset: f32,
+    size: Size,
+}
+
+impl Gallery {
+    pub fn new(size: Size) -> Self {
+        Self {
+            thumbs: FxHashMap::default(),
+            loading: FxHashSet::default(),
+            offset: 0.0,
+            size,
+        }
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    pub fn scroll(&mut self, viewport: Viewport) {
+        self.offset = viewport.absolute_offset().y;
+        self.size = viewport.bounds().size();
+    }
+
+    fn columns(&self) -> usize {
+        (((self.size.width + SPACING) / (CELL_WIDTH + SPACING)) as usize).max(1)
+    }
+
+    // 显示区域内的文件，多算一行，向下滚动时缩略图已经准备好
+    fn visible(&self, count: usize) -> Range<usize> {
+        let columns = self.columns();
+        let row_height = CELL_HEIGHT + SPACING;
+        let first = (self.offset / row_height) as usize;
+        let last = ((self.offset + self.size.height) / row_height) as usize + 1;
+        (first * columns).min(count)..((last + 1) * columns).min(count)
+    }
+
+    // 返回接下来要生成缩略图的文件，并记为正在生成
+    pub fn request(&mut self, files: &[PathBuf]) -> Vec<PathBuf> {
+        let mut paths = vec![];
+        for path in &files[self.visible(files.len())] {
+            if self.loading.len() >= MAX_LOADING {
+                break;
+            }
+            if !self.thumbs.contains_key(path) && self.loading.insert(path.clone()) {
+                paths.push(path.clone());
+            }
+        }
+        paths
+    }
+
+    pub fn loaded(&mut self, path: PathBuf, thumb: Option<image::Handle>) {
+        self.loading.remove(&path);
+        self.thumbs.insert(path, thumb);
+    }
+
+    // current 为查看器中的文件，显示为高亮
+    pub fn view<'a, Message: Clone + 'a>(
+        &'a self,
+        files: &'a [PathBuf],
+        current: usize,
+        on_open: fn(usize) -> Message,
+        on_scroll: fn(Viewport) -> Message,
+    ) -> Element<'a, Message> {
+        let mut grid = column![].spacing(SPACING).padding(SPACING);
+        for (i, chunk) in files.chunks(self.columns()).enumerate() {
+            let mut line = row![].spacing(SPACING);
+            for (j, path) in chunk.iter().enumerate() {
+                let index = i * self.columns() + j;
+                let style = if index == current {
+                    theme::Button::Primary
+                } else {
+                    theme::Button::Secondary
+                };
+                line = line.push(
+                    button(self.cell(path))
+                        .width(CELL_WIDTH)
+                        .height(CELL_HEIGHT)
+                        .style(style)
+                        .on_press(on_open(index)),
+                );
+            }
+            grid = grid.push(line);
+        }
+        scrollable(grid)
+            .on_scroll(on_scroll)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+
+    fn cell<'a, Message: 'a>(&self, path: &Path) -> Element<'a, Message> {
+        let thumb: Element<'a, Message> = match self.thumbs.get(path) {
+            Some(Some(handle)) => image(handle.clone())
+                .width(THUMB_SIZE as f32)
+                .height(THUMB_SIZE as f32)
+                .into(),
+            Some(None) => placeholder("Failed"),
+            None => placeholder("..."),
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        column![thumb, text(name).size(12)]
+            .spacing(4)
+            .align_items(Alignment::Center)
+            .width(Length::Fill)
+            .into()
+    }
+}
+
+fn placeholder<'a, Message: 'a>(label: &'a str) -> Element<'a, Message> {
+    container(text(label))
+        .width(THUMB_SIZE as f32)
+        .height(THUMB_SIZE as f32)
+        .center_x()
+        .center_y()
+        .into()
+}
+
+// 在单独的线程里生成缩略图，失败时返回 None
+pub async fn load_thumbnail(path: PathBuf) -> (PathBuf, Option<image::Handle>) {
+    let (sender, receiver) = oneshot::channel();
+    let file = path.display().to_string();
+    thread::spawn(move || {
+        let thumb = thumbnail(&file).ok().map(|(width, height, pixels)| {
+            image::Handle::from_pixels(width as u32, height as u32, pixels)
+        });
+        let _ = sender.send(thumb);
+    });
+    (path, receiver.await.ok().flatten())
+}
+
+// 优先使用文件中嵌入的缩略图，没有时只解码 DC 系数得到 1/8 大小的图像
+fn thumbnail(path: &str) -> Result<(usize, usize, Vec<u8>), JpegErrorType> {
+    let (mut reader, header) = open_jpeg(path)?;
+    let (width, height, pixels) = match embedded(&header) {
+        Some(image) => image,
+        None => decode_reduced_with_header(&mut reader, header)?,
+    };
+    let factor = width.max(height).div_ceil(THUMB_SIZE);
+    Ok(downscale(width, height, 4, &pixels, factor))
+}
+
+// EXIF 中的缩略图是 JPEG 数据，JFIF 中的是 RGB 像素
+fn embedded(header: &JpegHeader) -> Option<(usize, usize, Vec<u8>)> {
+    for format in &header.interchange_formats {
+        match format {
+            InterchangeFormat::EXIF(exif) => {
+                let image = exif
+                    .thumbnail()
+                    .and_then(|data| decode_jpeg_bytes(data, OutputFormat::Rgba8).ok());
+                if image.is_some() {
+                    return image;
+                }
+            }
+            InterchangeFormat::JFIF(jfif) => {
+                let (x, y) = jfif.thumbnail_size();
+                let (x, y) = (x as usize, y as usize);
+                let rgb = jfif.thumbnail();
+                if x > 0 && y > 0 && rgb.len() >= x * y * 3 {
+                    let pixels = rgb[..x * y * 3]
+                        .chunks_exact(3)
+                        .flat_map(|p| [p[0], p[1], p[2], 255])
+                        .collect();
+                    return Some((x, y, pixels));
+                }
+            }
+            InterchangeFormat::Unknown => {}
+        }
+    }
+    None
+}
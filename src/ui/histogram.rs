@@ -0,0 +1,233 @@
+use iced::advanced::image::Data;
+use iced::mouse;
+use iced::widget::canvas::{self, Frame, Geometry, Path, Stroke};
+use iced::widget::{column, image, row, text, Canvas};
+use iced::{Color, Element, Point, Rectangle, Renderer, Theme};
+
+const CHART_WIDTH: f32 = 256.0;
+const CHART_HEIGHT: f32 = 100.0;
+const VALUE_WIDTH: f32 = 50.0;
+
+const NAMES: [&str; 4] = ["R", "G", "B", "Y"];
+const COLORS: [Color; 4] = [
+    Color::from_rgb(0.9, 0.2, 0.2),
+    Color::from_rgb(0.2, 0.8, 0.2),
+    Color::from_rgb(0.3, 0.4, 1.0),
+    Color::from_rgb(0.5, 0.5, 0.5),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelStats {
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+}
+
+// R、G、B 和亮度 (BT.601) 四个通道的直方图
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bins: [[u32; 256]; 4],
+    stats: [ChannelStats; 4],
+    // 统计的区域，图像坐标
+    region: (usize, usize, usize, usize),
+}
+
+impl Histogram {
+    // 统计 RGBA 图像中 (x, y, width, height) 区域内的像素，区域会被限制在图像内，
+    // 空图像得到全为 0 的统计
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+        (x, y, w, h): (usize, usize, usize, usize),
+    ) -> Self {
+        let x = x.min(width);
+        let y = y.min(height);
+        let w = w.min(width - x);
+        let h = h.min(height - y);
+
+        let mut bins = [[0u32; 256]; 4];
+        for row in pixels.chunks_exact(width.max(1) * 4).skip(y).take(h) {
+            for p in row[x * 4..(x + w) * 4].chunks_exact(4) {
+                let luma = (299 * p[0] as u32 + 587 * p[1] as u32 + 114 * p[2] as u32 + 500) / 1000;
+                bins[0][p[0] as usize] += 1;
+                bins[1][p[1] as usize] += 1;
+                bins[2][p[2] as usize] += 1;
+                bins[3][luma as usize] += 1;
+            }
+        }
+
+        let stats = bins.map(|bin| {
+            let count: u64 = bin.iter().map(|&n| n as u64).sum();
+            let sum: u64 = bin
+                .iter()
+                .enumerate()
+                .map(|(v, &n)| v as u64 * n as u64)
+                .sum();
+            ChannelStats {
+                min: bin.iter().position(|&n| n > 0).unwrap_or(0) as u8,
+                max: bin.iter().rposition(|&n| n > 0).unwrap_or(0) as u8,
+                mean: if count == 0 {
+                    0.0
+                } else {
+                    sum as f64 / count as f64
+                },
+            }
+        });
+        Self {
+            bins,
+            stats,
+            region: (x, y, w, h),
+        }
+    }
+
+    // 从界面显示的 RGBA 图像统计
+    pub fn from_handle(
+        handle: &image::Handle,
+        region: Option<(usize, usize, usize, usize)>,
+    ) -> Option<Self> {
+        let Data::Rgba {
+            width,
+            height,
+            pixels,
+        } = handle.data()
+        else {
+            return None;
+        };
+        let (width, height) = (*width as usize, *height as usize);
+        // 还没有打开图像时是 0x0 的空图像
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let region = region.unwrap_or((0, 0, width, height));
+        Some(Self::new(width, height, pixels.as_ref(), region))
+    }
+
+    pub fn stats(&self) -> &[ChannelStats; 4] {
+        &self.stats
+    }
+
+    pub fn view<'a, Message: 'a>(&'a self, selected: bool) -> Element<'a, Message> {
+        let (x, y, w, h) = self.region;
+        let region = match selected {
+            true => format!("Selection {}, {}  {} x {}", x, y, w, h),
+            false => format!("Whole image  {} x {}", w, h),
+        };
+        let mut content = column![
+            text("Histogram").size(16),
+            Canvas::new(Chart { histogram: self })
+                .width(CHART_WIDTH)
+                .height(CHART_HEIGHT),
+            text(region).size(12),
+            row![
+                text("").width(VALUE_WIDTH / 2.0),
+                text("min").size(12).width(VALUE_WIDTH),
+                text("max").size(12).width(VALUE_WIDTH),
+                text("mean").size(12).width(VALUE_WIDTH),
+            ],
+        ]
+        .spacing(4)
+        .padding(8);
+        for (i, stats) in self.stats.iter().enumerate() {
+            content = content.push(row![
+                text(NAMES[i])
+                    .size(12)
+                    .width(VALUE_WIDTH / 2.0)
+                    .style(COLORS[i]),
+                text(stats.min).size(12).width(VALUE_WIDTH),
+                text(stats.max).size(12).width(VALUE_WIDTH),
+                text(format!("{:.1}", stats.mean))
+                    .size(12)
+                    .width(VALUE_WIDTH),
+            ]);
+        }
+        content.into()
+    }
+}
+
+// 四个通道的曲线画在一起，按所有通道中最高的一格归一化
+struct Chart<'a> {
+    histogram: &'a Histogram,
+}
+
+impl<Message> canvas::Program<Message> for Chart<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        frame.fill_rectangle(
+            Point::ORIGIN,
+            bounds.size(),
+            Color::from_rgba(0.0, 0.0, 0.0, 0.1),
+        );
+        let peak = self
+            .histogram
+            .bins
+            .iter()
+            .flat_map(|bin| bin.iter())
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1) as f32;
+        let step = bounds.width / 255.0;
+        for (bin, color) in self.histogram.bins.iter().zip(COLORS) {
+            let path = Path::new(|builder| {
+                for (v, &n) in bin.iter().enumerate() {
+                    let point =
+                        Point::new(v as f32 * step, bounds.height * (1.0 - n as f32 / peak));
+                    if v == 0 {
+                        builder.move_to(point);
+                    } else {
+                        builder.line_to(point);
+                    }
+                }
+            });
+            frame.stroke(&path, Stroke::default().with_color(color).with_width(1.0));
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_stats() {
+        // 2x2 的图像，右下角是白色
+        let mut pixels = vec![0u8; 16];
+        pixels[12..16].copy_from_slice(&[255, 255, 255, 255]);
+        pixels[0..4].copy_from_slice(&[10, 20, 30, 255]);
+
+        let all = Histogram::new(2, 2, &pixels, (0, 0, 2, 2));
+        assert_eq!(all.stats()[0].min, 0);
+        assert_eq!(all.stats()[0].max, 255);
+        assert_eq!(all.stats()[1].mean, (20.0 + 255.0) / 4.0);
+        assert_eq!(all.bins[3][255], 1);
+
+        // 超出图像的区域被裁掉
+        let corner = Histogram::new(2, 2, &pixels, (1, 1, 5, 5));
+        assert_eq!(corner.region, (1, 1, 1, 1));
+        assert_eq!(corner.stats()[2].min, 255);
+        assert_eq!(corner.stats()[3].mean, 255.0);
+    }
+
+    #[test]
+    fn test_empty_image() {
+        // 没有打开图像时不统计，也不能 panic
+        let empty = image::Handle::from_pixels(0, 0, vec![]);
+        assert!(Histogram::from_handle(&empty, None).is_none());
+        assert!(Histogram::from_handle(&empty, Some((0, 0, 5, 5))).is_none());
+
+        let stats = Histogram::new(0, 0, &[], (0, 0, 1, 1));
+        assert_eq!(stats.region, (0, 0, 0, 0));
+        assert_eq!(stats.stats()[0].mean, 0.0);
+    }
+}
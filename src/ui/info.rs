@@ -0,0 +1,310 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+};
+
+use iced::futures::channel::oneshot;
+use iced::widget::{column, row, scrollable, text};
+use iced::{Element, Font, Length};
+use rustc_hash::FxHashMap;
+
+use crate::{
+    application::{exif::Exif, jfif::JFIF, InterchangeFormat},
+    component::frame::Frame,
+    dqt::{
+        quality::{estimate_quality, QualityEstimate, TableMatch},
+        Dqt,
+    },
+    segment::{Segment, SegmentType},
+};
+
+const PANEL_WIDTH: f32 = 300.0;
+const KEY_WIDTH: f32 = 110.0;
+
+// 面板中的一组信息
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub title: &'static str,
+    pub fields: Vec<(String, String)>,
+}
+
+// 从文件的各个段中整理出的信息，按显示顺序排列
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub sections: Vec<Section>,
+}
+
+impl Section {
+    fn new(title: &'static str) -> Self {
+        Self {
+            title,
+            fields: vec![],
+        }
+    }
+
+    fn push(&mut self, key: &str, value: impl ToString) {
+        self.fields.push((key.to_string(), value.to_string()));
+    }
+}
+
+impl ImageInfo {
+    pub fn new(file_size: u64, segments: &[Segment]) -> Self {
+        let mut image = Section::new("Image");
+        image.push("File size", format_size(file_size));
+
+        let mut frame = None;
+        let mut dqt_map: FxHashMap<u8, Rc<Dqt>> = FxHashMap::default();
+        let mut restart_interval = None;
+        let mut scans = 0;
+        let mut jfif = Section::new("JFIF");
+        let mut exif = Section::new("EXIF");
+        let mut gps = Section::new("GPS");
+        let mut comments = Section::new("Comments");
+        for seg in segments {
+            match seg.segment_type {
+                SegmentType::SOFn(n) => frame = Frame::new(n, seg.data.clone()).ok(),
+                SegmentType::DQT => {
+                    let _ = Dqt::new(&mut dqt_map, seg.length, seg.data.clone());
+                }
+                SegmentType::DRI if seg.data.len() >= 2 => {
+                    restart_interval = Some(u16::from_be_bytes([seg.data[0], seg.data[1]]));
+                }
+                SegmentType::SOS(_, _) => scans += 1,
+                SegmentType::APPn(n @ (0 | 1)) => match InterchangeFormat::new(n, seg) {
+                    InterchangeFormat::JFIF(info) => jfif_fields(&mut jfif, &info),
+                    InterchangeFormat::EXIF(info) => exif_fields(&mut exif, &mut gps, &info),
+                    InterchangeFormat::Unknown => {}
+                },
+                SegmentType::COM => {
+                    comments.push("", String::from_utf8_lossy(&seg.data).trim_end());
+                }
+                _ => {}
+            }
+        }
+
+        let mut quantization = Section::new("Quantization");
+        if let Some(frame) = &frame {
+            image.push(
+                "Dimensions",
+                format!("{} x {}", frame.get_width(), frame.get_height()),
+            );
+            image.push("Frame type", frame.get_type());
+            image.push("Precision", format!("{} bit", frame.get_precision()));
+            image.push("Sampling", sampling(frame));
+
+            let report = estimate_quality(frame, &dqt_map);
+            if let Some(luma) = report.luma {
+                quantization.push("Quality (Y)", format_quality(&luma));
+            }
+            if let Some(chroma) = report.chroma {
+                quantization.push("Quality (C)", format_quality(&chroma));
+            }
+        }
+        image.push(
+            "Restart interval",
+            match restart_interval {
+                Some(n) if n > 0 => format!("{} MCUs", n),
+                _ => "none".to_string(),
+            },
+        );
+        image.push("Scans", scans);
+
+        let mut tables: Vec<&Rc<Dqt>> = dqt_map.values().collect();
+        tables.sort_by_key(|dqt| dqt.id());
+        for dqt in tables {
+            let rows: Vec<String> = dqt
+                .natural_table()
+                .chunks(8)
+                .map(|row| row.iter().map(|q| format!("{:>3}", q)).collect())
+                .collect();
+            let bits = if dqt.precision() == 0 { 8 } else { 16 };
+            quantization.push(
+                &format!("Table {} ({} bit)", dqt.id(), bits),
+                rows.join("\n"),
+            );
+        }
+
+        let sections = [image, quantization, jfif, exif, gps, comments]
+            .into_iter()
+            .filter(|section| !section.fields.is_empty())
+            .collect();
+        Self { sections }
+    }
+
+    pub fn view<'a, Message: 'a>(&'a self) -> Element<'a, Message> {
+        let mut content = column![].spacing(4).padding(8);
+        for section in &self.sections {
+            content = content.push(text(section.title).size(16));
+            for (key, value) in &section.fields {
+                // 多行的值（量化表）用等宽字体对齐
+                let value = match value.contains('\n') {
+                    true => text(value).size(12).font(Font::MONOSPACE),
+                    false => text(value).size(12),
+                };
+                content = content.push(row![text(key).size(12).width(KEY_WIDTH), value]);
+            }
+        }
+        scrollable(content)
+            .width(PANEL_WIDTH)
+            .height(Length::Fill)
+            .into()
+    }
+}
+
+// 读取文件时出错只显示错误信息
+pub fn error_view<'a, Message: 'a>(error: &'a str) -> Element<'a, Message> {
+    column![text("Info").size(16), text(error).size(12)]
+        .spacing(4)
+        .padding(8)
+        .width(PANEL_WIDTH)
+        .into()
+}
+
+fn jfif_fields(section: &mut Section, jfif: &JFIF) {
+    let (x, y) = jfif.density();
+    let units = match jfif.units() {
+        1 => " dpi",
+        2 => " dpcm",
+        _ => "",
+    };
+    section.push("Version", jfif.version());
+    section.push("Density", format!("{} x {}{}", x, y, units));
+    let (tx, ty) = jfif.thumbnail_size();
+    if tx > 0 && ty > 0 {
+        section.push("Thumbnail", format!("{} x {}", tx, ty));
+    }
+}
+
+fn exif_fields(section: &mut Section, gps: &mut Section, exif: &Exif) {
+    let camera = [exif.get_make(), exif.get_model()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !camera.is_empty() {
+        section.push("Camera", camera);
+    }
+    if let Some(date) = exif.get_date_time() {
+        section.push("Date", date);
+    }
+    if let Some((n, d)) = exif.get_exposure_time() {
+        // 小于一秒时显示为 1/125 s
+        let exposure = if n < d && d % n.max(1) == 0 {
+            format!("1/{} s", d / n.max(1))
+        } else {
+            format!("{:.1} s", n as f64 / d as f64)
+        };
+        section.push("Exposure", exposure);
+    }
+    if let Some(f) = exif.get_f_number() {
+        section.push("Aperture", format!("f/{:.1}", f));
+    }
+    if let Some(iso) = exif.get_iso() {
+        section.push("ISO", iso);
+    }
+    if let Some(focal) = exif.get_focal_length() {
+        section.push("Focal length", format!("{:.0} mm", focal));
+    }
+    if let Some(orientation) = exif.get_orientation() {
+        section.push("Orientation", orientation);
+    }
+    if let Some((lat, lon)) = exif.get_gps() {
+        gps.push("Latitude", format!("{:.6}", lat));
+        gps.push("Longitude", format!("{:.6}", lon));
+    }
+}
+
+// 常见的色度抽样写成 4:2:0 的形式，后面是各分量的采样因子
+fn sampling(frame: &Frame) -> String {
+    let mut comps: Vec<_> = frame.components.values().collect();
+    comps.sort_by_key(|c| c.get_id());
+    let factors = comps
+        .iter()
+        .map(|c| format!("{}x{}", c.get_factor_x(), c.get_factor_y()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (max_x, max_y) = frame.max_factors();
+    let name = match comps.get(1) {
+        Some(c) if comps.len() == 3 => {
+            let x = max_x / (c.get_factor_x() as usize).max(1);
+            let y = max_y / (c.get_factor_y() as usize).max(1);
+            match (x, y) {
+                (1, 1) => "4:4:4 ",
+                (2, 1) => "4:2:2 ",
+                (2, 2) => "4:2:0 ",
+                (1, 2) => "4:4:0 ",
+                (4, 1) => "4:1:1 ",
+                _ => "",
+            }
+        }
+        _ => "",
+    };
+    format!("{}({})", name, factors)
+}
+
+fn format_quality(estimate: &QualityEstimate) -> String {
+    let table_match = match estimate.table_match {
+        TableMatch::Standard => "standard",
+        TableMatch::Approximate => "approximate",
+        TableMatch::Known(name) => name,
+        TableMatch::Custom => "custom",
+    };
+    format!("~{} ({})", estimate.quality, table_match)
+}
+
+fn format_size(bytes: u64) -> String {
+    let size = match bytes {
+        0..=1023 => return format!("{} bytes", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    };
+    format!("{} ({} bytes)", size, bytes)
+}
+
+pub fn read_info(path: &Path) -> Result<ImageInfo, String> {
+    let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let segments = Segment::from_file(&mut BufReader::new(file)).map_err(|e| format!("{:?}", e))?;
+    Ok(ImageInfo::new(size, &segments))
+}
+
+// 在单独的线程里读取文件的所有段
+pub async fn load(path: PathBuf) -> (PathBuf, Result<ImageInfo, String>) {
+    let (sender, receiver) = oneshot::channel();
+    let file = path.clone();
+    thread::spawn(move || {
+        let _ = sender.send(read_info(&file));
+    });
+    let info = receiver
+        .await
+        .unwrap_or_else(|_| Err("读取线程意外退出".to_string()));
+    (path, info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field<'a>(info: &'a ImageInfo, title: &str, key: &str) -> Option<&'a str> {
+        let section = info.sections.iter().find(|s| s.title == title)?;
+        let (_, value) = section.fields.iter().find(|(k, _)| k == key)?;
+        Some(value)
+    }
+
+    #[test]
+    fn test_read_info() {
+        let info = read_info(Path::new("tests/data/baseline_420.jpg")).unwrap();
+        assert_eq!(field(&info, "Image", "Scans"), Some("1"));
+        assert!(field(&info, "Image", "Sampling")
+            .unwrap()
+            .starts_with("4:2:0"));
+        assert!(field(&info, "Quantization", "Quality (Y)").is_some());
+        assert!(field(&info, "JFIF", "Version").is_some());
+
+        let info = read_info(Path::new("tests/data/restart_420.jpg")).unwrap();
+        assert_ne!(field(&info, "Image", "Restart interval"), Some("none"));
+    }
+}
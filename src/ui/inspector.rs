@@ -0,0 +1,244 @@
+use std::{path::PathBuf, sync::Arc, thread};
+
+use iced::advanced::image::Data;
+use iced::futures::channel::oneshot;
+use iced::widget::{column, image, row, scrollable, text};
+use iced::{Element, Font, Length};
+
+use crate::{decode::dct::DCT, decode_coefficients_with_header, open_jpeg};
+
+const PANEL_WIDTH: f32 = 300.0;
+const KEY_WIDTH: f32 = 110.0;
+
+// 一个分量的量化系数，解码器里的 ComponentCoefficients 含有 Rc，不能跨线程传递
+#[derive(Debug)]
+struct Plane {
+    id: u8,
+    factor_x: usize,
+    factor_y: usize,
+    blocks_x: usize,
+    blocks: Vec<[i16; 64]>,
+    // 自然顺序的量化表
+    quant: [u16; 64],
+}
+
+// 整个文件的量化系数，用来查看光标下的像素属于哪个块
+#[derive(Debug)]
+pub struct Coefficients {
+    width: usize,
+    height: usize,
+    max_x: usize,
+    max_y: usize,
+    planes: Vec<Plane>,
+}
+
+// 光标下一个分量的样本和所在的块
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentProbe {
+    pub name: String,
+    pub block: (usize, usize),
+    // 反量化、IDCT 后、上采样和颜色转换前的样本值
+    pub sample: u8,
+    pub coefficients: [i16; 64],
+}
+
+// 光标下的像素，坐标是文件中的原始坐标
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    pub x: usize,
+    pub y: usize,
+    pub rgb: Option<[u8; 3]>,
+    pub mcu: (usize, usize),
+    pub components: Vec<ComponentProbe>,
+}
+
+impl Coefficients {
+    pub fn read(path: &str) -> Result<Self, String> {
+        let (mut reader, header) = open_jpeg(path).map_err(|e| e.to_string())?;
+        let width = header.frame.get_width() as usize;
+        let height = header.frame.get_height() as usize;
+        let (max_x, max_y) = header.frame.max_factors();
+        let coefs =
+            decode_coefficients_with_header(&mut reader, header).map_err(|e| e.to_string())?;
+        let planes = coefs
+            .into_iter()
+            .map(|comp| Plane {
+                id: comp.id,
+                factor_x: comp.factor_x as usize,
+                factor_y: comp.factor_y as usize,
+                blocks_x: comp.blocks_x,
+                quant: comp.dqt.natural_table(),
+                blocks: comp.blocks,
+            })
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            max_x,
+            max_y,
+            planes,
+        })
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // 计算 (x, y) 处各分量的原始样本，超出图像时返回 None
+    pub fn probe(&self, x: usize, y: usize) -> Option<Probe> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let dct = DCT::new();
+        let names: &[&str] = match self.planes.len() {
+            1 => &["Y"],
+            3 => &["Y", "Cb", "Cr"],
+            _ => &[],
+        };
+        let components = self
+            .planes
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| {
+                // 抽样后的分量平面中的坐标
+                let px = x * plane.factor_x / self.max_x;
+                let py = y * plane.factor_y / self.max_y;
+                let block = (px / 8, py / 8);
+                let coefficients = plane.blocks[block.1 * plane.blocks_x + block.0];
+                let mut data = [[0f32; 8]; 8];
+                for (k, (&c, &q)) in coefficients.iter().zip(&plane.quant).enumerate() {
+                    data[k / 8][k % 8] = c as f32 * q as f32;
+                }
+                let samples = dct.idct2d(data);
+                ComponentProbe {
+                    name: match names.get(i) {
+                        Some(name) => name.to_string(),
+                        None => format!("C{}", plane.id),
+                    },
+                    block,
+                    sample: (128.0 + samples[py % 8][px % 8]).round() as u8,
+                    coefficients,
+                }
+            })
+            .collect();
+        Some(Probe {
+            x,
+            y,
+            rgb: None,
+            mcu: (x / (self.max_x * 8), y / (self.max_y * 8)),
+            components,
+        })
+    }
+}
+
+impl Probe {
+    // 从界面显示的图像中取 (x, y) 处的 RGB 值，坐标是显示后的坐标
+    pub fn with_rgb(mut self, handle: &image::Handle, x: usize, y: usize) -> Self {
+        if let Data::Rgba { width, pixels, .. } = handle.data() {
+            let offset = (y * *width as usize + x) * 4;
+            self.rgb = pixels
+                .as_ref()
+                .get(offset..offset + 3)
+                .map(|p| [p[0], p[1], p[2]]);
+        }
+        self
+    }
+
+    pub fn view<'a, Message: 'a>(&'a self) -> Element<'a, Message> {
+        let field = |key: &str, value: String| {
+            row![
+                text(key.to_string()).size(12).width(KEY_WIDTH),
+                text(value).size(12)
+            ]
+        };
+        let mut content = column![
+            text("Pixel").size(16),
+            field("Position", format!("{}, {}", self.x, self.y)),
+        ]
+        .spacing(4)
+        .padding(8);
+        if let Some([r, g, b]) = self.rgb {
+            content = content.push(field("RGB", format!("{}, {}, {}", r, g, b)));
+        }
+        let samples = self
+            .components
+            .iter()
+            .map(|comp| format!("{} {}", comp.name, comp.sample))
+            .collect::<Vec<_>>()
+            .join("  ");
+        content = content
+            .push(field("Samples", samples))
+            .push(field("MCU", format!("{}, {}", self.mcu.0, self.mcu.1)));
+        for comp in &self.components {
+            content = content.push(text(format!("{} block", comp.name)).size(16));
+            content = content.push(field(
+                "Block",
+                format!("{}, {}", comp.block.0, comp.block.1),
+            ));
+            // 量化后的系数，左上角是 DC
+            let rows: Vec<String> = comp
+                .coefficients
+                .chunks(8)
+                .map(|row| row.iter().map(|c| format!("{:>5}", c)).collect())
+                .collect();
+            content = content.push(text(rows.join("\n")).size(12).font(Font::MONOSPACE));
+        }
+        scrollable(content)
+            .width(PANEL_WIDTH)
+            .height(Length::Fill)
+            .into()
+    }
+}
+
+// 光标不在图像上或系数还没读出时的提示
+pub fn message_view<'a, Message: 'a>(message: &'a str) -> Element<'a, Message> {
+    column![text("Pixel").size(16), text(message).size(12)]
+        .spacing(4)
+        .padding(8)
+        .width(PANEL_WIDTH)
+        .into()
+}
+
+// 在单独的线程里熵解码整个文件
+pub async fn load(path: PathBuf) -> (PathBuf, Result<Arc<Coefficients>, String>) {
+    let (sender, receiver) = oneshot::channel();
+    let file = path.display().to_string();
+    thread::spawn(move || {
+        let _ = sender.send(Coefficients::read(&file).map(Arc::new));
+    });
+    let coefs = receiver
+        .await
+        .unwrap_or_else(|_| Err("读取线程意外退出".to_string()));
+    (path, coefs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::OutputFormat, decode_jpeg_bytes};
+
+    #[test]
+    fn test_probe_samples() {
+        // 样本和不做上采样的平面输出一致
+        let path = "tests/data/baseline_420.jpg";
+        let coefs = Coefficients::read(path).unwrap();
+        let (width, height) = coefs.size();
+        let data = std::fs::read(path).unwrap();
+        let (_, _, planes) = decode_jpeg_bytes(&data, OutputFormat::YCbCr).unwrap();
+        let (cw, ch) = (width.div_ceil(2), height.div_ceil(2));
+        let (luma, chroma) = planes.split_at(width * height);
+        for (x, y) in [(0, 0), (17, 9), (width - 1, height - 1)] {
+            let probe = coefs.probe(x, y).unwrap();
+            assert_eq!(probe.mcu, (x / 16, y / 16));
+            assert_eq!(probe.components[0].block, (x / 8, y / 8));
+            assert_eq!(probe.components[1].block, (x / 16, y / 16));
+            assert_eq!(probe.components[0].sample, luma[y * width + x]);
+            assert_eq!(probe.components[1].sample, chroma[y / 2 * cw + x / 2]);
+            assert_eq!(
+                probe.components[2].sample,
+                chroma[cw * ch + y / 2 * cw + x / 2]
+            );
+        }
+        assert!(coefs.probe(width, 0).is_none());
+    }
+}
@@ -1,38 +1,170 @@
-use iced::widget::{column, image, Button, Image};
-use iced::{Command, Element};
+use std::{fs, path::PathBuf, sync::Arc};
+
+use iced::event::{self, Event};
+use iced::keyboard::{self, key::Named, Key};
+use iced::widget::{column, image, progress_bar, row, scrollable::Viewport, text, Button};
+use iced::{window, Color, Command, Element, Point, Rectangle, Size, Subscription};
 use rfd::FileDialog;
+use rustc_hash::FxHashSet;
+
+use cache::{CachedImage, ImageCache};
+use folder::{Folder, SortOrder};
+use gallery::Gallery;
+use histogram::Histogram;
+use info::ImageInfo;
+use inspector::{Coefficients, Probe};
+use orientation::{SaveMode, ViewOrientation};
+use progress::Progress;
+use zoom::{Zoom, ZoomImage, ZoomMessage, ZoomState};
+
+pub mod cache;
+pub mod folder;
+pub mod gallery;
+pub mod histogram;
+pub mod info;
+pub mod inspector;
+pub mod orientation;
+pub mod progress;
+pub mod zoom;
 
-use crate::get_jpeg_image_async;
+// 解码后图像缓存的上限
+const CACHE_BYTES: usize = 512 << 20;
+// 超过这个大小的图像在解码过程中只刷新 PARTIAL_UPDATES 次，每次刷新都要复制整个缓冲区
+const PARTIAL_UPDATE_BYTES: usize = 16 << 20;
+const PARTIAL_UPDATES: usize = 16;
 
 pub struct App {
     pixels: image::Handle,
     width: u16,
     height: u16,
     img_path: String,
+    // 正在解码的文件，解码完成前逐步显示已经解码的行
+    loading: Option<Loading>,
+    // 每次打开文件加一，用来区分解码任务
+    generation: usize,
+    zoom: ZoomState,
+    // 当前文件所在的目录，用来切换到上一个或下一个文件
+    folder: Option<Folder>,
+    cache: ImageCache,
+    // 正在提前解码的文件
+    preloading: FxHashSet<PathBuf>,
+    // 显示缩略图网格时代替查看器
+    gallery: Option<Gallery>,
+    window: Size,
+    // 元数据面板，切换文件后重新读取
+    show_info: bool,
+    info: Option<Result<ImageInfo, String>>,
+    // 最近一次打开失败的原因，显示在工具栏下方
+    error: Option<String>,
+    // 保存成功等提示，和错误显示在同一位置
+    status: Option<String>,
+    // 查看时的旋转和翻转，不改变 pixels，变换后的图像和宽高放在 oriented
+    orientation: ViewOrientation,
+    oriented: Option<(image::Handle, u16, u16)>,
+    // 直方图面板，selection 是显示图像上选中的区域
+    show_histogram: bool,
+    histogram: Option<Histogram>,
+    selection: Option<Rectangle>,
+    // 像素检查面板，hovered 是光标下显示图像中的像素
+    show_inspector: bool,
+    coefficients: Option<Result<Arc<Coefficients>, String>>,
+    hovered: Option<(usize, usize)>,
+    probe: Option<Probe>,
+}
+
+struct Loading {
+    buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+    // 已经解码的行数
+    rows: usize,
+    // 已经显示出来的行数
+    shown: usize,
+    // 解码失败时恢复显示之前的图像
+    previous: Shown,
+}
+
+// 正在显示的图像
+struct Shown {
+    pixels: image::Handle,
+    width: u16,
+    height: u16,
+    img_path: String,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     OpenFile,
-    FileDecoded((usize, usize, Vec<u8>)),
+    // 解码任务的 generation 和进度
+    Progress(usize, Progress),
+    Zoom(ZoomMessage),
+    // true 为下一个文件
+    Step(bool),
+    ToggleSort,
+    Preloaded(PathBuf, Option<CachedImage>),
+    ToggleGallery,
+    GalleryScrolled(Viewport),
+    ThumbnailLoaded(PathBuf, Option<image::Handle>),
+    OpenThumbnail(usize),
+    WindowResized(Size),
+    ToggleInfo,
+    InfoLoaded(PathBuf, Result<ImageInfo, String>),
+    DismissError,
+    // true 为顺时针
+    Rotate(bool),
+    // true 为水平翻转
+    Flip(bool),
+    Save(SaveMode),
+    Saved(PathBuf, SaveMode, Result<String, String>),
+    ToggleHistogram,
+    Select(Option<Rectangle>),
+    ToggleInspector,
+    Hover(Option<Point>),
+    CoefficientsLoaded(PathBuf, Result<Arc<Coefficients>, String>),
+    FileDropped(PathBuf),
 }
 
 impl iced::Application for App {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
+    // 命令行中的路径，打开第一个
+    type Flags = Vec<PathBuf>;
     type Theme = iced::Theme;
 
     fn new(flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
-        (
-            App {
-                pixels: image::Handle::from_pixels(0, 0, vec![]),
-                width: 0,
-                height: 0,
-                img_path: String::new(),
-            },
-            Command::none(),
-        )
+        let mut app = App {
+            pixels: image::Handle::from_pixels(0, 0, vec![]),
+            width: 0,
+            height: 0,
+            img_path: String::new(),
+            loading: None,
+            generation: 0,
+            zoom: ZoomState::default(),
+            folder: None,
+            cache: ImageCache::new(CACHE_BYTES),
+            preloading: FxHashSet::default(),
+            gallery: None,
+            // 和 Settings 的默认窗口大小一致
+            window: Size::new(1024.0, 768.0),
+            show_info: false,
+            info: None,
+            error: None,
+            status: None,
+            orientation: ViewOrientation::default(),
+            oriented: None,
+            show_histogram: false,
+            histogram: None,
+            selection: None,
+            show_inspector: false,
+            coefficients: None,
+            hovered: None,
+            probe: None,
+        };
+        let command = match flags.into_iter().next() {
+            Some(path) => app.open(path),
+            None => Command::none(),
+        };
+        (app, command)
     }
 
     fn title(&self) -> String {
@@ -40,13 +172,114 @@ impl iced::Application for App {
     }
 
     fn view(&self) -> Element<Self::Message> {
-        column![
+        let zoom_label = match self.zoom.zoom() {
+            Zoom::Fit => format!("Fit {:.0}%", self.zoom.scale(self.image_size()) * 100.0),
+            Zoom::Scale(scale) => format!("{:.0}%", scale * 100.0),
+        };
+        let (folder_label, sort_label) = match &self.folder {
+            Some(folder) => {
+                let (index, count) = folder.position();
+                let sort = match folder.sort() {
+                    SortOrder::Name => "By Name",
+                    SortOrder::Date => "By Date",
+                };
+                (format!("{}/{}", index, count), sort)
+            }
+            None => (String::new(), "By Name"),
+        };
+        let toolbar = row![
             Button::new("Open File").on_press(Message::OpenFile),
-            Image::new(self.pixels.clone())
-                .width(self.width)
-                .height(self.height)
+            Button::new("Fit").on_press(Message::Zoom(ZoomMessage::Fit)),
+            Button::new("1:1").on_press(Message::Zoom(ZoomMessage::Actual)),
+            Button::new("-").on_press(Message::Zoom(ZoomMessage::Out)),
+            Button::new("+").on_press(Message::Zoom(ZoomMessage::In)),
+            text(zoom_label),
+            Button::new("<").on_press(Message::Step(false)),
+            Button::new(">").on_press(Message::Step(true)),
+            text(folder_label),
+            Button::new(sort_label).on_press(Message::ToggleSort),
+            Button::new(if self.gallery.is_some() {
+                "Viewer"
+            } else {
+                "Gallery"
+            })
+            .on_press(Message::ToggleGallery),
+            Button::new("Info").on_press(Message::ToggleInfo),
+            Button::new("Histogram").on_press(Message::ToggleHistogram),
+            Button::new("Pixel").on_press(Message::ToggleInspector),
+            Button::new("Rotate L").on_press(Message::Rotate(false)),
+            Button::new("Rotate R").on_press(Message::Rotate(true)),
+            Button::new("Flip H").on_press(Message::Flip(true)),
+            Button::new("Flip V").on_press(Message::Flip(false)),
+            Button::new("Save EXIF").on_press(Message::Save(SaveMode::Exif)),
+            Button::new("Save Lossless").on_press(Message::Save(SaveMode::Lossless)),
         ]
-        .into()
+        .spacing(4)
+        .align_items(iced::Alignment::Center);
+
+        let main: Element<Self::Message> = match (&self.gallery, &self.folder) {
+            (Some(gallery), Some(folder)) => gallery.view(
+                folder.files(),
+                folder.position().0 - 1,
+                Message::OpenThumbnail,
+                Message::GalleryScrolled,
+            ),
+            _ => {
+                let mut viewer = column![];
+                if let Some(loading) = &self.loading {
+                    viewer = viewer.push(progress_bar(
+                        0.0..=loading.height.max(1) as f32,
+                        loading.rows as f32,
+                    ));
+                }
+                let handle = match &self.oriented {
+                    Some((handle, _, _)) => handle.clone(),
+                    None => self.pixels.clone(),
+                };
+                let mut image = ZoomImage::new(handle, self.zoom, Message::Zoom)
+                    .selection(self.selection)
+                    .on_select(Message::Select);
+                if self.show_inspector {
+                    image = image.on_hover(Message::Hover);
+                }
+                viewer.push(image).into()
+            }
+        };
+        let mut panel = column![];
+        if let Some(histogram) = &self.histogram {
+            panel = panel.push(histogram.view(self.selection.is_some()));
+        }
+        if self.show_inspector {
+            panel = panel.push(match (&self.coefficients, &self.probe) {
+                (Some(Err(error)), _) => inspector::message_view(error),
+                (None, _) => inspector::message_view("Reading coefficients..."),
+                (Some(Ok(_)), Some(probe)) => probe.view(),
+                (Some(Ok(_)), None) => inspector::message_view("Move the cursor over the image"),
+            });
+        }
+        match &self.info {
+            Some(Ok(info)) if self.show_info => panel = panel.push(info.view()),
+            Some(Err(error)) if self.show_info => panel = panel.push(info::error_view(error)),
+            _ => {}
+        }
+        let mut content = column![toolbar];
+        let notice = match (&self.error, &self.status) {
+            (Some(error), _) => Some(text(error).style(Color::from_rgb(0.8, 0.1, 0.1))),
+            (None, Some(status)) => Some(text(status)),
+            (None, None) => None,
+        };
+        if let Some(notice) = notice {
+            content = content.push(
+                row![
+                    notice,
+                    Button::new("Dismiss").on_press(Message::DismissError),
+                ]
+                .spacing(8)
+                .padding(4)
+                .align_items(iced::Alignment::Center),
+            );
+        }
+        content.push(row![main, panel]).into()
     }
 
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
@@ -57,16 +290,545 @@ impl iced::Application for App {
                     .add_filter("jpeg", &["jpeg", "jpg"])
                     .pick_file()
                 {
-                    self.img_path = res.display().to_string();
+                    return self.open(res);
+                }
+                Command::none()
+            }
+            Message::Step(forward) => match &mut self.folder {
+                Some(folder) => {
+                    let path = folder.step(forward).to_path_buf();
+                    self.show(path)
+                }
+                None => Command::none(),
+            },
+            Message::ToggleSort => {
+                if let Some(folder) = &mut self.folder {
+                    folder.set_sort(match folder.sort() {
+                        SortOrder::Name => SortOrder::Date,
+                        SortOrder::Date => SortOrder::Name,
+                    });
+                }
+                Command::batch([self.preload(), self.load_thumbnails()])
+            }
+            Message::ToggleGallery => {
+                if self.gallery.take().is_some() {
+                    return Command::none();
+                }
+                if self.folder.is_none() {
+                    return match FileDialog::new().set_title("Open Folder").pick_folder() {
+                        Some(dir) => self.open(dir),
+                        None => Command::none(),
+                    };
+                }
+                self.gallery = Some(Gallery::new(self.window));
+                self.load_thumbnails()
+            }
+            Message::GalleryScrolled(viewport) => {
+                if let Some(gallery) = &mut self.gallery {
+                    gallery.scroll(viewport);
+                }
+                self.load_thumbnails()
+            }
+            Message::ThumbnailLoaded(path, thumb) => {
+                if let Some(gallery) = &mut self.gallery {
+                    gallery.loaded(path, thumb);
+                }
+                self.load_thumbnails()
+            }
+            Message::OpenThumbnail(index) => match &mut self.folder {
+                Some(folder) => {
+                    let path = folder.select(index).to_path_buf();
+                    self.gallery = None;
+                    self.show(path)
+                }
+                None => Command::none(),
+            },
+            Message::FileDropped(path) => self.open(path),
+            Message::Rotate(clockwise) => {
+                self.reorient(|orientation| orientation.rotate(clockwise));
+                Command::none()
+            }
+            Message::Flip(horizontal) => {
+                self.reorient(|orientation| orientation.flip(horizontal));
+                Command::none()
+            }
+            Message::Save(mode) => {
+                if self.loading.is_some() || self.img_path.is_empty() {
+                    return Command::none();
+                }
+                let input = PathBuf::from(&self.img_path);
+                let mut dialog = FileDialog::new()
+                    .set_title("Save File")
+                    .add_filter("jpeg", &["jpeg", "jpg"]);
+                if let Some(dir) = input.parent() {
+                    dialog = dialog.set_directory(dir);
+                }
+                if let Some(name) = input.file_name() {
+                    dialog = dialog.set_file_name(name.to_string_lossy());
+                }
+                let Some(output) = dialog.save_file() else {
+                    return Command::none();
+                };
+                Command::perform(
+                    orientation::save_file(input, output, self.orientation, mode),
+                    move |(path, result)| Message::Saved(path, mode, result),
+                )
+            }
+            Message::Saved(path, mode, result) => match result {
+                Ok(status) => {
+                    // 缓存中可能是保存前的内容
+                    self.cache.remove(&path);
+                    // 无损旋转后文件本身已经转正，重新打开
+                    let current =
+                        fs::canonicalize(&path).ok() == fs::canonicalize(&self.img_path).ok();
+                    let command = if mode == SaveMode::Lossless && current {
+                        self.show(path)
+                    } else {
+                        Command::none()
+                    };
+                    self.status = Some(status);
+                    command
+                }
+                Err(e) => {
+                    self.error = Some(format!("Cannot save: {}", e));
+                    Command::none()
+                }
+            },
+            Message::DismissError => {
+                self.error = None;
+                self.status = None;
+                Command::none()
+            }
+            Message::ToggleHistogram => {
+                self.show_histogram = !self.show_histogram;
+                self.refresh_histogram();
+                Command::none()
+            }
+            Message::Select(selection) => {
+                self.selection = selection;
+                self.refresh_histogram();
+                Command::none()
+            }
+            Message::ToggleInspector => {
+                self.show_inspector = !self.show_inspector;
+                self.refresh_probe();
+                self.load_coefficients()
+            }
+            Message::Hover(pixel) => {
+                self.hovered = pixel.map(|p| (p.x as usize, p.y as usize));
+                self.refresh_probe();
+                Command::none()
+            }
+            Message::CoefficientsLoaded(path, coefficients) => {
+                if path.display().to_string() == self.img_path {
+                    self.coefficients = Some(coefficients);
+                    self.refresh_probe();
+                }
+                Command::none()
+            }
+            Message::ToggleInfo => {
+                self.show_info = !self.show_info;
+                self.load_info()
+            }
+            Message::InfoLoaded(path, info) => {
+                if path.display().to_string() == self.img_path {
+                    self.info = Some(info);
+                }
+                Command::none()
+            }
+            Message::WindowResized(size) => {
+                self.window = size;
+                if let Some(gallery) = &mut self.gallery {
+                    gallery.resize(size);
+                }
+                self.load_thumbnails()
+            }
+            Message::Preloaded(path, image) => {
+                self.preloading.remove(&path);
+                if let Some(image) = image {
+                    self.cache.insert(path, image);
+                }
+                Command::none()
+            }
+            Message::Progress(generation, progress) => {
+                // 切换文件之前的解码任务留在队列里的消息
+                if generation != self.generation {
+                    return Command::none();
                 }
-                Command::perform(get_jpeg_image_async(self.img_path.clone()), Message::FileDecoded)
+                self.on_progress(progress)
             }
-            Message::FileDecoded((width, height, pixbuf)) => {
-                self.pixels = image::Handle::from_pixels(width as u32, height as u32, pixbuf);
+            Message::Zoom(message) => {
+                self.zoom.update(message, self.image_size());
+                Command::none()
+            }
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let decode = match self.loading {
+            Some(_) => progress::decode_file(self.generation, self.img_path.clone())
+                .map(|(generation, progress)| Message::Progress(generation, progress)),
+            None => Subscription::none(),
+        };
+        Subscription::batch([
+            decode,
+            keyboard::on_key_press(key_message),
+            event::listen_with(window_message),
+        ])
+    }
+}
+
+// + - 缩放，0 适应窗口，1 原始大小，左右方向键切换文件，i 显示元数据，g 显示直方图，p 检查像素
+// r l 顺/逆时针旋转，h v 水平/垂直翻转，Ctrl+S 保存方向标签，Ctrl+Shift+S 无损旋转后保存
+fn key_message(key: Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    let message = match key.as_ref() {
+        Key::Character("+") | Key::Character("=") => ZoomMessage::In,
+        Key::Character("-") => ZoomMessage::Out,
+        Key::Character("0") => ZoomMessage::Fit,
+        Key::Character("1") => ZoomMessage::Actual,
+        Key::Named(Named::ArrowLeft) => return Some(Message::Step(false)),
+        Key::Named(Named::ArrowRight) => return Some(Message::Step(true)),
+        Key::Character("i") => return Some(Message::ToggleInfo),
+        Key::Character("g") => return Some(Message::ToggleHistogram),
+        Key::Character("p") => return Some(Message::ToggleInspector),
+        Key::Character("s") | Key::Character("S") if modifiers.command() => {
+            return Some(Message::Save(if modifiers.shift() {
+                SaveMode::Lossless
+            } else {
+                SaveMode::Exif
+            }));
+        }
+        Key::Character("r") => return Some(Message::Rotate(true)),
+        Key::Character("l") => return Some(Message::Rotate(false)),
+        Key::Character("h") => return Some(Message::Flip(true)),
+        Key::Character("v") => return Some(Message::Flip(false)),
+        _ => return None,
+    };
+    Some(Message::Zoom(message))
+}
+
+fn window_message(event: Event, _status: event::Status) -> Option<Message> {
+    match event {
+        Event::Window(_, window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+        Event::Window(_, window::Event::Resized { width, height }) => Some(Message::WindowResized(
+            Size::new(width as f32, height as f32),
+        )),
+        _ => None,
+    }
+}
+
+impl App {
+    fn image_size(&self) -> Size {
+        match self.oriented {
+            Some((_, width, height)) => Size::new(width as f32, height as f32),
+            None => Size::new(self.width as f32, self.height as f32),
+        }
+    }
+
+    // 解码完成后才能旋转，否则还没解码的行不会被变换
+    fn reorient(&mut self, change: impl FnOnce(&mut ViewOrientation)) {
+        if self.loading.is_some() || self.img_path.is_empty() {
+            return;
+        }
+        change(&mut self.orientation);
+        self.oriented = match self.orientation.transform() {
+            Some(_) => self
+                .orientation
+                .apply(&self.pixels)
+                .map(|(handle, width, height)| (handle, width as u16, height as u16)),
+            None => None,
+        };
+        self.selection = None;
+        self.hovered = None;
+        self.zoom.update(ZoomMessage::Fit, self.image_size());
+        self.refresh_histogram();
+        self.refresh_probe();
+    }
+
+    // 统计当前显示的图像，解码完成前不统计
+    fn refresh_histogram(&mut self) {
+        self.histogram = match &self.oriented {
+            _ if !self.show_histogram || self.loading.is_some() => None,
+            Some((handle, _, _)) => Histogram::from_handle(handle, self.region()),
+            None => Histogram::from_handle(&self.pixels, self.region()),
+        };
+    }
+
+    // 显示图像中的像素先换算回文件中的坐标，再从系数计算原始样本
+    fn refresh_probe(&mut self) {
+        self.probe = match (&self.coefficients, self.hovered) {
+            (Some(Ok(coefficients)), Some(pixel)) if self.show_inspector => {
+                let (width, height) = coefficients.size();
+                let handle = match &self.oriented {
+                    Some((handle, _, _)) => handle,
+                    None => &self.pixels,
+                };
+                self.orientation
+                    .source(pixel, width, height)
+                    .and_then(|(x, y)| coefficients.probe(x, y))
+                    .map(|probe| probe.with_rgb(handle, pixel.0, pixel.1))
+            }
+            _ => None,
+        };
+    }
+
+    fn region(&self) -> Option<(usize, usize, usize, usize)> {
+        self.selection.map(|rect| {
+            (
+                rect.x as usize,
+                rect.y as usize,
+                rect.width as usize,
+                rect.height as usize,
+            )
+        })
+    }
+
+    // 目录显示缩略图网格，文件直接打开
+    fn open(&mut self, path: PathBuf) -> Command<Message> {
+        let sort = self.folder.as_ref().map_or(SortOrder::Name, |f| f.sort());
+        if !path.is_dir() {
+            self.folder = Folder::open(&path, sort).ok();
+            self.gallery = None;
+            return self.show(path);
+        }
+        match Folder::open_dir(&path, sort) {
+            Ok(folder) => {
+                self.folder = Some(folder);
+                self.gallery = Some(Gallery::new(self.window));
+                self.load_thumbnails()
+            }
+            Err(e) => {
+                self.error = Some(format!("Cannot open {}: {}", path.display(), e));
+                Command::none()
+            }
+        }
+    }
+
+    // 已经解码过的文件直接显示，否则开始逐步解码
+    fn show(&mut self, path: PathBuf) -> Command<Message> {
+        let previous = Shown {
+            pixels: self.pixels.clone(),
+            width: self.width,
+            height: self.height,
+            img_path: std::mem::replace(&mut self.img_path, path.display().to_string()),
+        };
+        self.info = None;
+        self.coefficients = None;
+        self.hovered = None;
+        self.probe = None;
+        self.error = None;
+        self.status = None;
+        self.orientation = ViewOrientation::default();
+        self.oriented = None;
+        self.selection = None;
+        match self.cache.get(&path) {
+            Some(image) => {
+                self.loading = None;
+                self.pixels = image.handle;
+                self.width = image.width as u16;
+                self.height = image.height as u16;
+                self.zoom.update(ZoomMessage::Fit, self.image_size());
+                self.refresh_histogram();
+            }
+            None => {
+                self.generation += 1;
+                self.loading = Some(Loading {
+                    buffer: vec![],
+                    width: 0,
+                    height: 0,
+                    rows: 0,
+                    shown: 0,
+                    previous,
+                });
+            }
+        }
+        Command::batch([self.preload(), self.load_info(), self.load_coefficients()])
+    }
+
+    // 面板打开时才读取元数据
+    fn load_info(&self) -> Command<Message> {
+        if !self.show_info || self.info.is_some() || self.img_path.is_empty() {
+            return Command::none();
+        }
+        Command::perform(info::load(PathBuf::from(&self.img_path)), |(path, info)| {
+            Message::InfoLoaded(path, info)
+        })
+    }
+
+    // 检查面板打开时才熵解码整个文件
+    fn load_coefficients(&self) -> Command<Message> {
+        if !self.show_inspector || self.coefficients.is_some() || self.img_path.is_empty() {
+            return Command::none();
+        }
+        Command::perform(
+            inspector::load(PathBuf::from(&self.img_path)),
+            |(path, coefficients)| Message::CoefficientsLoaded(path, coefficients),
+        )
+    }
+
+    // 在后台解码相邻的文件，切换时不需要等待
+    fn preload(&mut self) -> Command<Message> {
+        let Some(folder) = &self.folder else {
+            return Command::none();
+        };
+        let mut commands = vec![];
+        for path in folder.neighbours() {
+            if self.cache.contains(path) || !self.preloading.insert(path.to_path_buf()) {
+                continue;
+            }
+            commands.push(Command::perform(
+                cache::load(path.to_path_buf()),
+                |(path, image)| Message::Preloaded(path, image),
+            ));
+        }
+        Command::batch(commands)
+    }
+
+    // 为网格中能看到的文件生成缩略图
+    fn load_thumbnails(&mut self) -> Command<Message> {
+        let (Some(gallery), Some(folder)) = (&mut self.gallery, &self.folder) else {
+            return Command::none();
+        };
+        Command::batch(gallery.request(folder.files()).into_iter().map(|path| {
+            Command::perform(gallery::load_thumbnail(path), |(path, thumb)| {
+                Message::ThumbnailLoaded(path, thumb)
+            })
+        }))
+    }
+
+    fn on_progress(&mut self, progress: Progress) -> Command<Message> {
+        let Some(loading) = &mut self.loading else {
+            return Command::none();
+        };
+        match progress {
+            Progress::Header { width, height } => {
+                // 新图像的第一行到达之前先显示透明的空白
+                loading.buffer = vec![0; width * height * 4];
+                loading.width = width;
+                loading.height = height;
                 self.width = width as u16;
                 self.height = height as u16;
-                Command::none()
+                self.zoom.update(ZoomMessage::Fit, self.image_size());
+            }
+            Progress::Rows { first, pixels } => {
+                let stride = loading.width * 4;
+                loading.buffer[first * stride..][..pixels.len()].copy_from_slice(&pixels);
+                loading.rows = first + pixels.len() / stride;
+                let step = match loading.buffer.len() {
+                    len if len > PARTIAL_UPDATE_BYTES => loading.height / PARTIAL_UPDATES,
+                    _ => 0,
+                };
+                if loading.rows - loading.shown > step {
+                    loading.shown = loading.rows;
+                    self.pixels = image::Handle::from_pixels(
+                        loading.width as u32,
+                        loading.height as u32,
+                        loading.buffer.clone(),
+                    );
+                }
+            }
+            Progress::Finished => {
+                // 解码完成后不再需要缓冲区，直接交给图像
+                self.pixels = image::Handle::from_pixels(
+                    loading.width as u32,
+                    loading.height as u32,
+                    std::mem::take(&mut loading.buffer),
+                );
+                self.cache.insert(
+                    PathBuf::from(&self.img_path),
+                    CachedImage {
+                        handle: self.pixels.clone(),
+                        width: loading.width,
+                        height: loading.height,
+                    },
+                );
+                self.loading = None;
+                self.refresh_histogram();
+            }
+            Progress::Failed(error) => {
+                let name = PathBuf::from(&self.img_path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                self.error = Some(format!("Cannot open {}: {}", name, error));
+                let previous = self.loading.take().unwrap().previous;
+                self.pixels = previous.pixels;
+                self.width = previous.width;
+                self.height = previous.height;
+                self.img_path = previous.img_path;
+                self.info = None;
+                self.coefficients = None;
+                self.zoom.update(ZoomMessage::Fit, self.image_size());
+                self.refresh_histogram();
+                return Command::batch([self.load_info(), self.load_coefficients()]);
             }
         }
+        Command::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use iced::{advanced::image::Data, Application};
+
+    use super::*;
+
+    #[test]
+    fn test_stale_progress() {
+        let (mut app, _) = App::new(vec![]);
+        let _ = app.show(PathBuf::from("tests/data/baseline_420.jpg"));
+        let stale = app.generation;
+        let _ = app.show(PathBuf::from("tests/data/baseline_444.jpg"));
+
+        // 上一个文件的行和完成消息都被忽略，新文件还在解码
+        let rows = Progress::Rows {
+            first: 0,
+            pixels: vec![0; 48 * 4],
+        };
+        let _ = app.update(Message::Progress(stale, rows));
+        let _ = app.update(Message::Progress(stale, Progress::Finished));
+        assert!(app.loading.is_some());
+        assert!(!app.cache.contains(Path::new("tests/data/baseline_444.jpg")));
+
+        let header = Progress::Header {
+            width: 48,
+            height: 32,
+        };
+        let _ = app.update(Message::Progress(app.generation, header));
+        assert_eq!(app.image_size(), Size::new(48.0, 32.0));
+    }
+
+    #[test]
+    fn test_partial_updates() {
+        let (mut app, _) = App::new(vec![]);
+        let path = PathBuf::from("tests/data/baseline_420.jpg");
+        let _ = app.show(path.clone());
+        let generation = app.generation;
+        let (width, height) = (2048, 4096);
+        let header = Progress::Header { width, height };
+        let _ = app.update(Message::Progress(generation, header));
+
+        // 大图像每到 1/16 的行才刷新一次
+        let mut first = 0;
+        for (rows, shown) in [(200, 0), (100, 300), (10, 300)] {
+            let pixels = vec![255; rows * width * 4];
+            let _ = app.update(Message::Progress(
+                generation,
+                Progress::Rows { first, pixels },
+            ));
+            first += rows;
+            assert_eq!(app.loading.as_ref().unwrap().shown, shown);
+        }
+
+        let _ = app.update(Message::Progress(generation, Progress::Finished));
+        assert!(app.loading.is_none());
+        assert!(app.cache.contains(&path));
+        let Data::Rgba { pixels, .. } = app.pixels.data() else {
+            panic!("不是 RGBA 图像");
+        };
+        assert_eq!(pixels.as_ref()[first * width * 4 - 1], 255);
+        assert_eq!(pixels.as_ref()[first * width * 4], 0);
     }
 }
@@ -0,0 +1,317 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Seek},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use iced::advanced::image::Data;
+use iced::futures::channel::oneshot;
+use iced::widget::image;
+
+use crate::{
+    application::exif::Exif,
+    decode_coefficients_with_header,
+    encode::write_jpeg,
+    export::pixels::transform_pixels,
+    open_jpeg,
+    segment::{
+        rewrite::{rewrite, SegmentEdit},
+        Segment, SegmentType,
+    },
+    transform::{transform, Transform},
+};
+
+// 查看时的方向：先水平翻转（flipped），再顺时针旋转 turns 个 90°
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ViewOrientation {
+    turns: u8,
+    flipped: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveMode {
+    // 只修改 EXIF 方向标签
+    Exif,
+    // 在 DCT 域上旋转，不重新压缩
+    Lossless,
+}
+
+impl ViewOrientation {
+    pub fn rotate(&mut self, clockwise: bool) {
+        self.turns = (self.turns + if clockwise { 1 } else { 3 }) % 4;
+    }
+
+    // 翻转显示出来的图像：F·R^t·F^f = R^-t·F^(f+1)，垂直翻转等于水平翻转后旋转 180°
+    pub fn flip(&mut self, horizontal: bool) {
+        let turns = if horizontal { 4 } else { 6 } - self.turns;
+        self.turns = turns % 4;
+        self.flipped = !self.flipped;
+    }
+
+    pub fn transform(&self) -> Option<Transform> {
+        match (self.turns, self.flipped) {
+            (1, false) => Some(Transform::Rotate90),
+            (2, false) => Some(Transform::Rotate180),
+            (3, false) => Some(Transform::Rotate270),
+            (0, true) => Some(Transform::FlipHorizontal),
+            (1, true) => Some(Transform::Transverse),
+            (2, true) => Some(Transform::FlipVertical),
+            (3, true) => Some(Transform::Transpose),
+            _ => None,
+        }
+    }
+
+    // 对应的 EXIF 方向标签
+    pub fn exif(&self) -> u16 {
+        self.transform()
+            .and_then(|t| t.to_orientation())
+            .unwrap_or(1)
+    }
+
+    // 显示出来的 (x, y) 在原图中的位置，width 和 height 是原图的大小，超出图像时返回 None
+    pub fn source(
+        &self,
+        (x, y): (usize, usize),
+        width: usize,
+        height: usize,
+    ) -> Option<(usize, usize)> {
+        let (mut x, mut y) = (x, y);
+        let (mut w, mut h) = if self.turns % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        if x >= w || y >= h {
+            return None;
+        }
+        // 逐次撤销顺时针旋转，最后撤销水平翻转
+        for _ in 0..self.turns {
+            (x, y) = (y, w - 1 - x);
+            (w, h) = (h, w);
+        }
+        if self.flipped {
+            x = width - 1 - x;
+        }
+        Some((x, y))
+    }
+
+    // 变换 RGBA 图像，返回新图像和它的宽高
+    pub fn apply(&self, handle: &image::Handle) -> Option<(image::Handle, usize, usize)> {
+        let Data::Rgba {
+            width,
+            height,
+            pixels,
+        } = handle.data()
+        else {
+            return None;
+        };
+        let (width, height) = (*width as usize, *height as usize);
+        let (width, height, pixels) = match self.transform() {
+            Some(t) => transform_pixels(width, height, 4, pixels.as_ref(), t),
+            None => (width, height, pixels.as_ref().to_vec()),
+        };
+        let handle = image::Handle::from_pixels(width as u32, height as u32, pixels);
+        Some((handle, width, height))
+    }
+}
+
+// 按 mode 把方向写入 output，成功时返回给用户看的说明
+fn save(
+    input: &Path,
+    output: &Path,
+    orientation: ViewOrientation,
+    mode: SaveMode,
+) -> Result<String, String> {
+    let (data, note) = match mode {
+        SaveMode::Exif => save_exif(input, orientation)?,
+        SaveMode::Lossless => save_lossless(input, orientation)?,
+    };
+    // 先完整读取再写入，output 可以是 input 本身
+    fs::write(output, data).map_err(|e| e.to_string())?;
+    let name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(format!("Saved {} ({})", name, note))
+}
+
+fn save_exif(input: &Path, orientation: ViewOrientation) -> Result<(Vec<u8>, String), String> {
+    let file = File::open(input).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(file);
+    let segments = Segment::from_file(&mut reader).map_err(|e| format!("{:?}", e))?;
+    let value = orientation.exif();
+    let exif = segments.iter().find(|seg| {
+        matches!(seg.segment_type, SegmentType::APPn(1)) && seg.identifier() == b"Exif"
+    });
+    let data = match exif {
+        // 重写 IFD 需要移动其他数据的偏移，这种情况改用无损旋转
+        Some(seg) => Exif::set_orientation(&seg.data, value)
+            .ok_or("Exif 中没有方向标签，请使用无损旋转".to_string())?,
+        None => Exif::orientation_only(value),
+    };
+
+    reader.rewind().map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    let seg = Segment::with_data(SegmentType::APPn(1), data).map_err(|e| format!("{:?}", e))?;
+    let edit = SegmentEdit::Replace(seg);
+    rewrite(&mut reader, &mut out, &[edit]).map_err(|e| format!("{:?}", e))?;
+    Ok((out, format!("orientation {}", value)))
+}
+
+fn save_lossless(input: &Path, orientation: ViewOrientation) -> Result<(Vec<u8>, String), String> {
+    let (mut reader, header) =
+        open_jpeg(&input.display().to_string()).map_err(|e| e.to_string())?;
+    let width = header.frame.get_width() as usize;
+    let height = header.frame.get_height() as usize;
+    // 像素已经转正，原来的方向标签改为 1
+    let mut metadata = header.metadata.clone();
+    Exif::reset_orientation(&mut metadata);
+
+    let coefs = decode_coefficients_with_header(&mut reader, header).map_err(|e| e.to_string())?;
+    let (new_width, new_height, coefs) = match orientation.transform() {
+        Some(t) => transform(width, height, &coefs, t).map_err(|e| format!("{:?}", e))?,
+        None => (width, height, coefs),
+    };
+    let mut out = Vec::new();
+    write_jpeg(&mut out, new_width, new_height, &coefs, &metadata, None)
+        .map_err(|e| e.to_string())?;
+
+    // 翻转方向上不完整的 MCU 被裁掉
+    let (w, h) = if orientation.turns % 2 == 1 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let mut note = format!("{} x {}, lossless", new_width, new_height);
+    if (new_width, new_height) != (w, h) {
+        note.push_str(", edges trimmed to MCU boundary");
+    }
+    Ok((out, note))
+}
+
+// 在单独的线程里保存，返回保存的路径和结果
+pub async fn save_file(
+    input: PathBuf,
+    output: PathBuf,
+    orientation: ViewOrientation,
+    mode: SaveMode,
+) -> (PathBuf, Result<String, String>) {
+    let (sender, receiver) = oneshot::channel();
+    let path = output.clone();
+    thread::spawn(move || {
+        let _ = sender.send(save(&input, &output, orientation, mode));
+    });
+    let result = receiver
+        .await
+        .unwrap_or_else(|_| Err("保存线程意外退出".to_string()));
+    (path, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode::OutputFormat, decode_jpeg};
+
+    #[test]
+    fn test_compose() {
+        // 3x2 的灰度图，逐步变换和一次变换的结果相同
+        let pixels = vec![1, 2, 3, 4, 5, 6];
+        let step = |(w, h, p): (usize, usize, Vec<u8>), t| transform_pixels(w, h, 1, &p, t);
+        let mut orientation = ViewOrientation::default();
+        let mut image = (3, 2, pixels.clone());
+        let ops = [
+            (Some(true), Transform::Rotate90),
+            (None, Transform::FlipHorizontal),
+            (Some(false), Transform::Rotate270),
+            (None, Transform::FlipVertical),
+            (Some(true), Transform::Rotate90),
+            (None, Transform::FlipHorizontal),
+        ];
+        for (rotate, t) in ops {
+            match rotate {
+                Some(clockwise) => orientation.rotate(clockwise),
+                None => orientation.flip(t == Transform::FlipHorizontal),
+            }
+            image = step(image, t);
+            let expected = match orientation.transform() {
+                Some(t) => transform_pixels(3, 2, 1, &pixels, t),
+                None => (3, 2, pixels.clone()),
+            };
+            assert_eq!(image, expected);
+        }
+        assert_eq!(ViewOrientation::default().exif(), 1);
+        orientation = ViewOrientation::default();
+        orientation.rotate(true);
+        assert_eq!(orientation.exif(), 6);
+    }
+
+    #[test]
+    fn test_source() {
+        // 像素值就是它在原图中的下标
+        let (width, height) = (3, 2);
+        let pixels: Vec<u8> = (0..6).collect();
+        let mut orientation = ViewOrientation::default();
+        for i in 0..8 {
+            if i == 4 {
+                orientation.flip(true);
+            }
+            let (w, h, shown) = match orientation.transform() {
+                Some(t) => transform_pixels(width, height, 1, &pixels, t),
+                None => (width, height, pixels.clone()),
+            };
+            for y in 0..h {
+                for x in 0..w {
+                    let (sx, sy) = orientation.source((x, y), width, height).unwrap();
+                    assert_eq!(shown[y * w + x] as usize, sy * width + sx);
+                }
+            }
+            assert_eq!(orientation.source((w, 0), width, height), None);
+            orientation.rotate(true);
+        }
+    }
+
+    fn read_orientation(path: &Path) -> Option<u16> {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        let segments = Segment::from_file(&mut reader).unwrap();
+        let seg = segments.iter().find(|seg| {
+            matches!(seg.segment_type, SegmentType::APPn(1)) && seg.identifier() == b"Exif"
+        })?;
+        Exif::new(&seg.data)?.get_orientation()
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join("my-tiny-jpeg-decoder-orientation");
+        fs::create_dir_all(&dir).unwrap();
+        let input = Path::new("tests/data/baseline_420.jpg");
+        let exif = dir.join("exif.jpg");
+        let lossless = dir.join("lossless.jpg");
+        let mut orientation = ViewOrientation::default();
+        orientation.rotate(true);
+
+        // 没有 Exif 的文件加上方向标签
+        save(input, &exif, orientation, SaveMode::Exif).unwrap();
+        assert_eq!(read_orientation(&exif), Some(6));
+
+        // 无损旋转后方向标签改回 1
+        save(&exif, &lossless, orientation, SaveMode::Lossless).unwrap();
+        assert_eq!(read_orientation(&lossless), Some(1));
+        let (width, height, pixels) =
+            decode_jpeg(input.display().to_string(), OutputFormat::Rgb8).unwrap();
+        let actual = decode_jpeg(lossless.display().to_string(), OutputFormat::Rgb8).unwrap();
+        let expected = transform_pixels(width, height, 3, &pixels, Transform::Rotate90);
+        assert_eq!(
+            (actual.0 as usize, actual.1 as usize),
+            (expected.0, expected.1)
+        );
+        let max_diff = actual
+            .2
+            .iter()
+            .zip(&expected.2)
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+        assert!(max_diff <= 1);
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs::File,
+    io::Read,
+    thread,
+    time::{Duration, Instant},
+};
+
+use iced::futures::{channel::mpsc, future, SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+
+use crate::{
+    decode::{
+        push::{PushDecoder, PushEvent},
+        OutputFormat,
+    },
+    limits::Limits,
+    JpegErrorType,
+};
+
+// 每次从文件读取的字节数
+const CHUNK_SIZE: usize = 64 * 1024;
+// 两次把新解码的行发给界面的最短间隔，避免每行都重新生成图像
+const UPDATE_INTERVAL: Duration = Duration::from_millis(50);
+
+// 解码线程发给界面的进度
+#[derive(Debug, Clone)]
+pub enum Progress {
+    Header { width: usize, height: usize },
+    // 从第 first 行开始的若干整行 RGBA 像素
+    Rows { first: usize, pixels: Vec<u8> },
+    Finished,
+    Failed(String),
+}
+
+#[derive(Hash)]
+struct DecodeId(usize);
+
+// id 区分每次打开文件，重新打开同一个文件时也会重新解码
+// 进度带着 id 发出，界面据此丢弃切换文件之前排队的消息
+pub fn decode_file(id: usize, path: String) -> Subscription<(usize, Progress)> {
+    subscription::channel(DecodeId(id), 16, move |mut output| async move {
+        // 解码器内部使用 Rc，放在单独的线程里运行
+        let (sender, mut receiver) = mpsc::unbounded();
+        thread::spawn(move || {
+            if let Err(e) = decode_chunks(&path, &sender) {
+                let _ = sender.unbounded_send(Progress::Failed(e.to_string()));
+            }
+        });
+        let mut ended = false;
+        while let Some(progress) = receiver.next().await {
+            ended = matches!(progress, Progress::Finished | Progress::Failed(_));
+            let _ = output.send((id, progress)).await;
+        }
+        // 解码线程 panic 时没有发出结果
+        if !ended {
+            let _ = output
+                .send((id, Progress::Failed("解码线程意外退出".to_string())))
+                .await;
+        }
+        // 解码结束后保持订阅，直到界面不再需要它
+        future::pending().await
+    })
+}
+
+// 按块读取文件送入推送式解码器，界面取消订阅后提前结束
+fn decode_chunks(
+    path: &str,
+    sender: &mpsc::UnboundedSender<Progress>,
+) -> Result<(), JpegErrorType> {
+    let mut file = File::open(path).map_err(JpegErrorType::IOError)?;
+    let mut decoder = PushDecoder::new(OutputFormat::Rgba8, Limits::default())?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    // 还没有发出去的连续若干行
+    let mut pending: Option<(usize, Vec<u8>)> = None;
+    let mut last_update = Instant::now();
+
+    while !decoder.is_done() {
+        if sender.is_closed() {
+            return Ok(());
+        }
+        let n = file.read(&mut buf).map_err(JpegErrorType::IOError)?;
+        if n == 0 {
+            break;
+        }
+        for event in decoder.feed(&buf[..n])? {
+            match event {
+                PushEvent::Header { width, height } => {
+                    let _ = sender.unbounded_send(Progress::Header { width, height });
+                }
+                PushEvent::Rows { first, pixels } => match &mut pending {
+                    Some((_, rows)) => rows.extend_from_slice(&pixels),
+                    None => pending = Some((first, pixels)),
+                },
+                _ => {}
+            }
+        }
+        if last_update.elapsed() >= UPDATE_INTERVAL {
+            if let Some((first, pixels)) = pending.take() {
+                let _ = sender.unbounded_send(Progress::Rows { first, pixels });
+            }
+            last_update = Instant::now();
+        }
+    }
+    decoder.finish()?;
+
+    if let Some((first, pixels)) = pending {
+        let _ = sender.unbounded_send(Progress::Rows { first, pixels });
+    }
+    let _ = sender.unbounded_send(Progress::Finished);
+    Ok(())
+}
@@ -0,0 +1,442 @@
+use iced::advanced::{
+    image, layout, mouse, renderer,
+    widget::{self, Tree},
+    Clipboard, Layout, Shell, Widget,
+};
+use iced::event::{self, Event};
+use iced::{keyboard, Background, Border, Color, Element, Length, Point, Rectangle, Size, Vector};
+
+const MIN_SCALE: f32 = 0.05;
+const MAX_SCALE: f32 = 64.0;
+// 滚轮每格或按键一次的缩放倍数
+const SCALE_STEP: f32 = 1.25;
+// 放大到这个倍数以上时不做插值，可以看清单个像素
+const NEAREST_SCALE: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zoom {
+    // 缩放到正好放进窗口
+    Fit,
+    Scale(f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ZoomMessage {
+    // 显示区域的大小变化
+    Resized(Size),
+    // 以相对显示区域中心的 anchor 为中心缩放 steps 格
+    Wheel { steps: f32, anchor: Vector },
+    // 拖动的距离
+    Drag(Vector),
+    In,
+    Out,
+    Fit,
+    Actual,
+}
+
+// 缩放和平移的状态，offset 是图像中心相对显示区域中心的偏移
+#[derive(Debug, Clone, Copy)]
+pub struct ZoomState {
+    zoom: Zoom,
+    offset: Vector,
+    viewport: Size,
+}
+
+impl Default for ZoomState {
+    fn default() -> Self {
+        Self {
+            zoom: Zoom::Fit,
+            offset: Vector::new(0.0, 0.0),
+            viewport: Size::ZERO,
+        }
+    }
+}
+
+impl ZoomState {
+    // 当前的缩放倍数，image 为图像的原始大小
+    pub fn scale(&self, image: Size) -> f32 {
+        match self.zoom {
+            Zoom::Fit if image.width > 0.0 && image.height > 0.0 => (self.viewport.width
+                / image.width)
+                .min(self.viewport.height / image.height)
+                .clamp(MIN_SCALE, MAX_SCALE),
+            Zoom::Fit => 1.0,
+            Zoom::Scale(scale) => scale,
+        }
+    }
+
+    pub fn zoom(&self) -> Zoom {
+        self.zoom
+    }
+
+    pub fn update(&mut self, message: ZoomMessage, image: Size) {
+        match message {
+            ZoomMessage::Resized(viewport) => self.viewport = viewport,
+            ZoomMessage::Wheel { steps, anchor } => {
+                self.zoom_at(image, SCALE_STEP.powf(steps), anchor);
+            }
+            ZoomMessage::Drag(delta) => self.offset = self.offset + delta,
+            ZoomMessage::In => self.zoom_at(image, SCALE_STEP, Vector::new(0.0, 0.0)),
+            ZoomMessage::Out => self.zoom_at(image, 1.0 / SCALE_STEP, Vector::new(0.0, 0.0)),
+            ZoomMessage::Fit => {
+                self.zoom = Zoom::Fit;
+                self.offset = Vector::new(0.0, 0.0);
+            }
+            ZoomMessage::Actual => {
+                self.zoom = Zoom::Scale(1.0);
+                self.offset = Vector::new(0.0, 0.0);
+            }
+        }
+        self.offset = self.clamp_offset(image);
+    }
+
+    // 缩放后 anchor 处仍然是同一个图像点
+    fn zoom_at(&mut self, image: Size, factor: f32, anchor: Vector) {
+        let old = self.scale(image);
+        let new = (old * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.offset = anchor - (anchor - self.offset) * (new / old);
+        self.zoom = Zoom::Scale(new);
+    }
+
+    // 图像在显示区域中的位置和大小
+    fn image_bounds(&self, bounds: Rectangle, image: Size) -> Rectangle {
+        let scale = self.scale(image);
+        let size = Size::new(image.width * scale, image.height * scale);
+        let center = bounds.center() + self.clamp_offset(image);
+        Rectangle::new(
+            center - Vector::new(size.width / 2.0, size.height / 2.0),
+            size,
+        )
+    }
+
+    // 图像比显示区域小时居中，否则不能拖出图像的边缘
+    fn clamp_offset(&self, image: Size) -> Vector {
+        let scale = self.scale(image);
+        let clamp = |offset: f32, image: f32, viewport: f32| {
+            let hidden = ((image * scale - viewport) / 2.0).max(0.0);
+            offset.clamp(-hidden, hidden)
+        };
+        Vector::new(
+            clamp(self.offset.x, image.width, self.viewport.width),
+            clamp(self.offset.y, image.height, self.viewport.height),
+        )
+    }
+}
+
+// 按 ZoomState 绘制图像，滚轮、拖动和大小变化通过 on_zoom 交给应用处理
+// 设置了 on_select 时按住 Shift 拖动选择矩形区域，单击取消选择
+// 设置了 on_hover 时光标下的像素变化时通知应用
+pub struct ZoomImage<Message> {
+    handle: image::Handle,
+    state: ZoomState,
+    on_zoom: fn(ZoomMessage) -> Message,
+    // 图像坐标
+    selection: Option<Rectangle>,
+    on_select: Option<fn(Option<Rectangle>) -> Message>,
+    on_hover: Option<fn(Option<Point>) -> Message>,
+}
+
+impl<Message> ZoomImage<Message> {
+    pub fn new(
+        handle: image::Handle,
+        state: ZoomState,
+        on_zoom: fn(ZoomMessage) -> Message,
+    ) -> Self {
+        Self {
+            handle,
+            state,
+            on_zoom,
+            selection: None,
+            on_select: None,
+            on_hover: None,
+        }
+    }
+
+    pub fn selection(mut self, selection: Option<Rectangle>) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn on_select(mut self, on_select: fn(Option<Rectangle>) -> Message) -> Self {
+        self.on_select = Some(on_select);
+        self
+    }
+
+    pub fn on_hover(mut self, on_hover: fn(Option<Point>) -> Message) -> Self {
+        self.on_hover = Some(on_hover);
+        self
+    }
+}
+
+// 拖动时上一次的光标位置，选择时起点的图像坐标，hovered 是光标下的像素
+#[derive(Default)]
+struct DragState {
+    grabbed_at: Option<Point>,
+    selecting_from: Option<Point>,
+    modifiers: keyboard::Modifiers,
+    hovered: Option<Point>,
+}
+
+// 把两个图像坐标围成的矩形限制在图像内
+fn selection_rect(a: Point, b: Point, image: Size) -> Rectangle {
+    let clamp = |p: Point| {
+        Point::new(
+            p.x.clamp(0.0, image.width).round(),
+            p.y.clamp(0.0, image.height).round(),
+        )
+    };
+    let (a, b) = (clamp(a), clamp(b));
+    Rectangle::new(
+        Point::new(a.x.min(b.x), a.y.min(b.y)),
+        Size::new((a.x - b.x).abs(), (a.y - b.y).abs()),
+    )
+}
+
+impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for ZoomImage<Message>
+where
+    Renderer: image::Renderer<Handle = image::Handle>,
+{
+    fn tag(&self) -> widget::tree::Tag {
+        widget::tree::Tag::of::<DragState>()
+    }
+
+    fn state(&self) -> widget::tree::State {
+        widget::tree::State::new(DragState::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Fill, Length::Fill)
+    }
+
+    fn layout(
+        &self,
+        _tree: &mut Tree,
+        _renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        layout::Node::new(limits.max())
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+        if bounds.size() != self.state.viewport {
+            shell.publish((self.on_zoom)(ZoomMessage::Resized(bounds.size())));
+        }
+        let drag = tree.state.downcast_mut::<DragState>();
+        let dimensions = renderer.dimensions(&self.handle);
+        let image = Size::new(dimensions.width as f32, dimensions.height as f32);
+        let state = ZoomState {
+            viewport: bounds.size(),
+            ..self.state
+        };
+        let image_bounds = state.image_bounds(bounds, image);
+        let scale = state.scale(image);
+        let to_image = |p: Point| {
+            Point::new(
+                (p.x - image_bounds.x) / scale,
+                (p.y - image_bounds.y) / scale,
+            )
+        };
+
+        match event {
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                drag.modifiers = modifiers;
+                event::Status::Ignored
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let Some(position) = cursor.position_over(bounds) else {
+                    return event::Status::Ignored;
+                };
+                let steps = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / 60.0,
+                };
+                shell.publish((self.on_zoom)(ZoomMessage::Wheel {
+                    steps,
+                    anchor: position - bounds.center(),
+                }));
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let Some(position) = cursor.position_over(bounds) else {
+                    return event::Status::Ignored;
+                };
+                match self.on_select {
+                    Some(_) if drag.modifiers.shift() => {
+                        drag.selecting_from = Some(to_image(position));
+                    }
+                    Some(on_select) => {
+                        if self.selection.is_some() {
+                            shell.publish(on_select(None));
+                        }
+                        drag.grabbed_at = Some(position);
+                    }
+                    None => drag.grabbed_at = Some(position),
+                }
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let selecting = drag.selecting_from.take().is_some();
+                match drag.grabbed_at.take() {
+                    Some(_) => event::Status::Captured,
+                    None if selecting => event::Status::Captured,
+                    None => event::Status::Ignored,
+                }
+            }
+            Event::Mouse(mouse::Event::CursorLeft) => {
+                if let (Some(on_hover), Some(_)) = (self.on_hover, drag.hovered.take()) {
+                    shell.publish(on_hover(None));
+                }
+                event::Status::Ignored
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                if let Some(on_hover) = self.on_hover {
+                    let pixel = to_image(position);
+                    let hovered = (bounds.contains(position)
+                        && (0.0..image.width).contains(&pixel.x)
+                        && (0.0..image.height).contains(&pixel.y))
+                    .then(|| Point::new(pixel.x.floor(), pixel.y.floor()));
+                    if hovered != drag.hovered {
+                        drag.hovered = hovered;
+                        shell.publish(on_hover(hovered));
+                    }
+                }
+                if let (Some(start), Some(on_select)) = (drag.selecting_from, self.on_select) {
+                    let rect = selection_rect(start, to_image(position), image);
+                    let selection = (rect.width >= 1.0 && rect.height >= 1.0).then_some(rect);
+                    shell.publish(on_select(selection));
+                    return event::Status::Captured;
+                }
+                match drag.grabbed_at {
+                    Some(origin) => {
+                        drag.grabbed_at = Some(position);
+                        shell.publish((self.on_zoom)(ZoomMessage::Drag(position - origin)));
+                        event::Status::Captured
+                    }
+                    None => event::Status::Ignored,
+                }
+            }
+            _ => event::Status::Ignored,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let drag = tree.state.downcast_ref::<DragState>();
+        if drag.grabbed_at.is_some() {
+            mouse::Interaction::Grabbing
+        } else if drag.selecting_from.is_some()
+            || (self.on_select.is_some() && drag.modifiers.shift())
+        {
+            mouse::Interaction::Crosshair
+        } else if cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Grab
+        } else {
+            mouse::Interaction::Idle
+        }
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let dimensions = renderer.dimensions(&self.handle);
+        let image = Size::new(dimensions.width as f32, dimensions.height as f32);
+        if image.width == 0.0 || image.height == 0.0 {
+            return;
+        }
+        // 显示区域刚变化时 state 里还是旧的大小
+        let state = ZoomState {
+            viewport: bounds.size(),
+            ..self.state
+        };
+        let scale = state.scale(image);
+        let image_bounds = state.image_bounds(bounds, image);
+        let filter = if scale >= NEAREST_SCALE {
+            image::FilterMethod::Nearest
+        } else {
+            image::FilterMethod::Linear
+        };
+
+        renderer.with_layer(bounds, |renderer| {
+            renderer.draw(self.handle.clone(), filter, image_bounds);
+            if let Some(selection) = self.selection {
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle::new(
+                            image_bounds.position()
+                                + Vector::new(selection.x * scale, selection.y * scale),
+                            Size::new(selection.width * scale, selection.height * scale),
+                        ),
+                        border: Border {
+                            color: Color::from_rgb(1.0, 0.8, 0.0),
+                            width: 1.0,
+                            radius: 0.0.into(),
+                        },
+                        shadow: Default::default(),
+                    },
+                    Background::Color(Color::from_rgba(1.0, 0.8, 0.0, 0.15)),
+                );
+            }
+        });
+    }
+}
+
+impl<'a, Message, Theme, Renderer> From<ZoomImage<Message>>
+    for Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Renderer: image::Renderer<Handle = image::Handle> + 'a,
+{
+    fn from(image: ZoomImage<Message>) -> Self {
+        Element::new(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_at_anchor() {
+        let image = Size::new(400.0, 200.0);
+        let mut state = ZoomState::default();
+        state.update(ZoomMessage::Resized(Size::new(200.0, 200.0)), image);
+        assert_eq!(state.scale(image), 0.5);
+
+        // 光标下的图像点在缩放前后保持不动
+        let anchor = Vector::new(50.0, 0.0);
+        state.update(ZoomMessage::Actual, image);
+        state.update(ZoomMessage::Wheel { steps: 1.0, anchor }, image);
+        assert_eq!(state.zoom(), Zoom::Scale(SCALE_STEP));
+        assert_eq!(state.offset.x, anchor.x - anchor.x * SCALE_STEP);
+
+        // 不能把图像拖出显示区域
+        state.update(ZoomMessage::Drag(Vector::new(1000.0, 1000.0)), image);
+        assert_eq!(state.offset.x, (400.0 * SCALE_STEP - 200.0) / 2.0);
+        assert_eq!(state.offset.y, (200.0 * SCALE_STEP - 200.0) / 2.0);
+    }
+}
@@ -0,0 +1,100 @@
+// 用 tests/data 下的样例图片做一致性测试
+// 样例由 jpeg-encoder 生成，参考图（.ppm/.pgm）由 jpeg-decoder 解码生成，比较时允许一定误差：
+// 色度上采样用的是最近邻插值，带子采样的图片在色彩边缘处误差会偏大
+use my_tiny_jpeg_decoder::{
+    compare::compare,
+    decode::OutputFormat,
+    decode_jpeg, decode_reduced_with_header,
+    export::pixels::downscale,
+    import::read_image,
+    open_jpeg,
+};
+
+struct Case {
+    name: &'static str,
+    gray: bool,
+    min_psnr: f64,
+    max_mean_error: f64,
+}
+
+const CASES: [Case; 11] = [
+    Case { name: "baseline_444", gray: false, min_psnr: 45.0, max_mean_error: 1.0 },
+    Case { name: "baseline_422", gray: false, min_psnr: 30.0, max_mean_error: 3.0 },
+    Case { name: "baseline_420", gray: false, min_psnr: 28.0, max_mean_error: 4.5 },
+    Case { name: "baseline_440", gray: false, min_psnr: 30.0, max_mean_error: 3.5 },
+    Case { name: "grayscale", gray: true, min_psnr: 50.0, max_mean_error: 0.5 },
+    Case { name: "grayscale_odd", gray: true, min_psnr: 50.0, max_mean_error: 0.5 },
+    Case { name: "restart_420", gray: false, min_psnr: 28.0, max_mean_error: 4.5 },
+    Case { name: "restart_444", gray: false, min_psnr: 45.0, max_mean_error: 1.0 },
+    Case { name: "odd_420", gray: false, min_psnr: 26.0, max_mean_error: 5.5 },
+    Case { name: "odd_422", gray: false, min_psnr: 28.0, max_mean_error: 4.5 },
+    Case { name: "multi_tables", gray: false, min_psnr: 26.0, max_mean_error: 6.0 },
+];
+
+fn check(case: &Case) {
+    let path = format!("tests/data/{}.jpg", case.name);
+    let (format, ext) = if case.gray {
+        (OutputFormat::Gray8, "pgm")
+    } else {
+        (OutputFormat::Rgb8, "ppm")
+    };
+    let (width, height, pixels) = decode_jpeg(path.clone(), format)
+        .unwrap_or_else(|e| panic!("{} 解码失败: {}", path, e));
+
+    let reference = format!("tests/data/{}.{}", case.name, ext);
+    let (ref_width, ref_height, channels, expected) = read_image(&reference).unwrap();
+    assert_eq!((width, height), (ref_width, ref_height), "{} 尺寸不一致", case.name);
+    assert_eq!(pixels.len(), expected.len(), "{} 数据长度不一致", case.name);
+
+    let result = compare(width, height, channels, &pixels, &expected);
+    assert!(
+        result.psnr >= case.min_psnr,
+        "{}: PSNR {:.2} dB 低于 {:.2} dB",
+        case.name,
+        result.psnr,
+        case.min_psnr
+    );
+    assert!(
+        result.mean_error <= case.max_mean_error,
+        "{}: 平均误差 {:.3} 超过 {:.3}",
+        case.name,
+        result.mean_error,
+        case.max_mean_error
+    );
+}
+
+#[test]
+fn test_conformance() {
+    for case in CASES.iter() {
+        check(case);
+    }
+}
+
+#[test]
+fn test_rgba_matches_rgb() {
+    // 不同输出格式只是像素排列不同
+    let path = "tests/data/baseline_420.jpg".to_string();
+    let (_, _, rgb) = decode_jpeg(path.clone(), OutputFormat::Rgb8).unwrap();
+    let (_, _, rgba) = decode_jpeg(path, OutputFormat::Rgba8).unwrap();
+    for (px, pxa) in rgb.chunks(3).zip(rgba.chunks(4)) {
+        assert_eq!(px, &pxa[..3]);
+        assert_eq!(pxa[3], 255);
+    }
+}
+
+#[test]
+fn test_reduced_matches_downscale() {
+    // 只用 DC 系数得到的每个像素是对应 8x8 块的平均值
+    // 带子采样的图片色度块覆盖更大的区域，这里只检查没有子采样的样例
+    for name in ["baseline_444", "restart_444", "grayscale", "grayscale_odd"] {
+        let path = format!("tests/data/{}.jpg", name);
+        let (width, height, full) = decode_jpeg(path.clone(), OutputFormat::Rgba8).unwrap();
+        let (w, h, expected) = downscale(width, height, 4, &full, 8);
+
+        let (mut reader, header) = open_jpeg(&path).unwrap();
+        let (rw, rh, reduced) = decode_reduced_with_header(&mut reader, header).unwrap();
+        assert_eq!((rw, rh), (w, h), "{}", name);
+        let result = compare(w, h, 4, &reduced, &expected);
+        assert!(result.psnr >= 30.0, "{}: PSNR {:.2} dB", name, result.psnr);
+    }
+}
@@ -0,0 +1,207 @@
+// 损坏或构造的恶意输入只能返回错误，不能 panic
+// 更完整的随机测试见 fuzz/fuzz_targets/decode.rs（cargo fuzz run decode）
+use std::io::{BufReader, Cursor};
+
+use my_tiny_jpeg_decoder::{
+    decode::{push::PushDecoder, OutputFormat},
+    decode_coefficients_with_header, decode_jpeg_bytes, decode_jpeg_bytes_with_limits,
+    decode_reduced_with_header,
+    limits::{LimitErrorType, Limits},
+    try_read_header, try_read_header_with_limits, JpegErrorType,
+};
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("tests/data/{}.jpg", name)).unwrap()
+}
+
+// 第一个指定标记的位置
+fn find_marker(data: &[u8], marker: u8) -> usize {
+    data.windows(2).position(|w| w == [0xFF, marker]).unwrap()
+}
+
+#[test]
+fn test_truncated() {
+    let data = fixture("restart_420");
+    for len in (0..data.len()).step_by(3) {
+        assert!(decode_jpeg_bytes(&data[..len], OutputFormat::Rgba8).is_err());
+    }
+}
+
+#[test]
+fn test_mutated() {
+    // 固定种子的随机改写，覆盖各个段和熵编码数据
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as usize
+    };
+    for name in [
+        "baseline_444",
+        "baseline_420",
+        "grayscale_odd",
+        "restart_444",
+    ] {
+        let data = fixture(name);
+        for _ in 0..100 {
+            let mut data = data.clone();
+            for _ in 0..1 + next() % 4 {
+                let pos = next() % data.len();
+                match next() % 3 {
+                    0 => data[pos] = next() as u8,
+                    1 => data[pos] ^= 1 << (next() % 8),
+                    _ => data[pos] = 0xFF,
+                }
+            }
+            let _ = decode_jpeg_bytes(&data, OutputFormat::Rgba8);
+            let _ = decode_jpeg_bytes(&data, OutputFormat::YCbCr);
+            let mut decoder = PushDecoder::new(OutputFormat::Rgba8, Limits::default()).unwrap();
+            let _ = data
+                .chunks(64)
+                .try_for_each(|c| decoder.feed(c).map(|_| ()));
+        }
+    }
+}
+
+#[test]
+fn test_huge_dimensions() {
+    let mut data = fixture("baseline_444");
+    let sof = find_marker(&data, 0xC0);
+    data[sof + 5..sof + 9].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+    assert!(matches!(
+        decode_jpeg_bytes(&data, OutputFormat::Rgba8),
+        Err(JpegErrorType::LimitExceeded(_))
+    ));
+}
+
+#[test]
+fn test_coefficient_memory() {
+    // 16384x16384 的 4:4:4 图像没有超过像素上限，但量化系数需要 1.5 GiB
+    let mut data = fixture("baseline_444");
+    let sof = find_marker(&data, 0xC0);
+    data[sof + 5..sof + 9].copy_from_slice(&[0x40, 0x00, 0x40, 0x00]);
+    let mut reader = BufReader::new(Cursor::new(&data));
+    let header = try_read_header(&mut reader).unwrap();
+    assert!(matches!(
+        decode_coefficients_with_header(&mut reader, header),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::Memory(n, _))) if n == 3 << 29
+    ));
+
+    // 只解码 DC 时的平面和输出图像
+    let data = fixture("baseline_444");
+    let limits = Limits {
+        max_memory: 256,
+        ..Limits::default()
+    };
+    let mut reader = BufReader::new(Cursor::new(&data));
+    let header = try_read_header_with_limits(&mut reader, &limits).unwrap();
+    assert!(matches!(
+        decode_reduced_with_header(&mut reader, header),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::Memory(..)))
+    ));
+}
+
+#[test]
+fn test_invalid_segments() {
+    let data = fixture("baseline_420");
+
+    // 帧头声明的分量数超过段长度
+    let mut bad = data.clone();
+    let sof = find_marker(&bad, 0xC0);
+    bad[sof + 9] = 200;
+    assert!(decode_jpeg_bytes(&bad, OutputFormat::Rgba8).is_err());
+
+    // 采样因子为 0
+    let mut bad = data.clone();
+    bad[sof + 11] = 0x00;
+    assert!(decode_jpeg_bytes(&bad, OutputFormat::Rgba8).is_err());
+
+    // 量化表的段长度小于表的大小
+    let mut bad = data.clone();
+    let dqt = find_marker(&bad, 0xDB);
+    let len = u16::from_be_bytes([bad[dqt + 2], bad[dqt + 3]]) as usize;
+    bad.drain(dqt + 4 + 10..dqt + 2 + len);
+    bad[dqt + 2..dqt + 4].copy_from_slice(&12u16.to_be_bytes());
+    assert!(decode_jpeg_bytes(&bad, OutputFormat::Rgba8).is_err());
+
+    // 哈夫曼表的码长计数超过实际的符号数
+    let mut bad = data.clone();
+    let dht = find_marker(&bad, 0xC4);
+    bad[dht + 5..dht + 21].fill(0xFF);
+    assert!(decode_jpeg_bytes(&bad, OutputFormat::Rgba8).is_err());
+
+    // 扫描头声明的分量数超过段长度
+    let mut bad = data.clone();
+    let sos = find_marker(&bad, 0xDA);
+    bad[sos + 4] = 50;
+    assert!(decode_jpeg_bytes(&bad, OutputFormat::Rgba8).is_err());
+
+    // 扫描引用了不存在的哈夫曼表
+    let mut bad = data;
+    bad[sos + 6] = 0x33;
+    assert!(decode_jpeg_bytes(&bad, OutputFormat::Rgba8).is_err());
+}
+
+#[test]
+fn test_limits() {
+    // 48x32，带一个 JFIF 段，只有一个扫描
+    let data = fixture("baseline_444");
+    let decode = |limits: Limits, format| decode_jpeg_bytes_with_limits(&data, format, &limits);
+
+    assert!(decode(Limits::default(), OutputFormat::Rgba8).is_ok());
+    assert!(decode(Limits::unlimited(), OutputFormat::Rgba8).is_ok());
+
+    let limits = Limits {
+        max_pixels: 48 * 32 - 1,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        decode(limits, OutputFormat::Gray8),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::Pixels(
+            1536, 1535
+        )))
+    ));
+
+    // 灰度输出每像素 1 字节，RGBA 输出每像素 4 字节
+    let limits = Limits {
+        max_memory: 48 * 32 * 2,
+        ..Limits::default()
+    };
+    assert!(decode(limits, OutputFormat::Gray8).is_ok());
+    assert!(matches!(
+        decode(limits, OutputFormat::Rgba8),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::Memory(
+            6144, 3072
+        )))
+    ));
+
+    let limits = Limits {
+        max_scans: 0,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        decode(limits, OutputFormat::Rgba8),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::Scans(0)))
+    ));
+
+    let limits = Limits {
+        max_markers: 3,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        decode(limits, OutputFormat::Rgba8),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::Markers(3)))
+    ));
+
+    let limits = Limits {
+        max_metadata_bytes: 8,
+        ..Limits::default()
+    };
+    assert!(matches!(
+        decode(limits, OutputFormat::Rgba8),
+        Err(JpegErrorType::LimitExceeded(LimitErrorType::MetadataBytes(
+            8
+        )))
+    ));
+}
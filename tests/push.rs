@@ -0,0 +1,102 @@
+// 分块送入的数据解码结果必须和一次解码整个文件完全相同
+use my_tiny_jpeg_decoder::{
+    decode::{
+        push::{PushDecoder, PushEvent},
+        OutputFormat,
+    },
+    decode_jpeg,
+    limits::Limits,
+    segment::SegmentType,
+    JpegErrorType,
+};
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("tests/data/{}.jpg", name)).unwrap()
+}
+
+fn push_all(data: &[u8], format: OutputFormat, chunk: usize) -> (Vec<PushEvent>, Vec<u8>) {
+    let mut decoder = PushDecoder::new(format, Limits::default()).unwrap();
+    let mut events = Vec::new();
+    for part in data.chunks(chunk) {
+        events.extend(decoder.feed(part).unwrap());
+    }
+    decoder.finish().unwrap();
+
+    let mut pixels = Vec::new();
+    let mut stride = 0;
+    for event in &events {
+        match event {
+            PushEvent::Header { width, .. } => stride = width * format.bytes_per_pixel(),
+            // 行按顺序输出，中间没有空缺
+            PushEvent::Rows {
+                first,
+                pixels: rows,
+            } => {
+                assert_eq!(*first * stride, pixels.len());
+                pixels.extend_from_slice(rows);
+            }
+            _ => {}
+        }
+    }
+    (events, pixels)
+}
+
+#[test]
+fn test_push_chunks() {
+    for name in [
+        "baseline_444",
+        "baseline_420",
+        "grayscale_odd",
+        "odd_422",
+        "restart_420",
+        "multi_tables",
+    ] {
+        let path = format!("tests/data/{}.jpg", name);
+        let data = fixture(name);
+        for format in [OutputFormat::Rgba8, OutputFormat::Gray8] {
+            let (width, height, expected) = decode_jpeg(path.clone(), format).unwrap();
+            for chunk in [1, 7, 1000, data.len()] {
+                let (events, pixels) = push_all(&data, format, chunk);
+                assert_eq!(pixels, expected, "{} {}", name, chunk);
+                assert!(matches!(
+                    events[..],
+                    [.., PushEvent::ScanComplete, PushEvent::Done]
+                ));
+                assert!(events.iter().any(|e| matches!(
+                    e,
+                    PushEvent::Header { width: w, height: h }
+                        if *w == width && *h == height
+                )));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_push_metadata() {
+    // JFIF 段在头部信息之前输出
+    let (events, _) = push_all(&fixture("baseline_444"), OutputFormat::Rgb8, 5);
+    assert!(matches!(
+        &events[0],
+        PushEvent::Metadata(seg) if matches!(seg.segment_type, SegmentType::APPn(0))
+    ));
+}
+
+#[test]
+fn test_push_truncated() {
+    let data = fixture("restart_420");
+    for len in (0..data.len()).step_by(50) {
+        let mut decoder = PushDecoder::new(OutputFormat::Rgba8, Limits::default()).unwrap();
+        assert!(decoder.feed(&data[..len]).is_ok());
+        assert!(!decoder.is_done());
+        assert!(matches!(decoder.finish(), Err(JpegErrorType::Truncated)));
+    }
+}
+
+#[test]
+fn test_planar_format() {
+    assert!(matches!(
+        PushDecoder::new(OutputFormat::YCbCr, Limits::default()),
+        Err(JpegErrorType::UnsupportedFormat(OutputFormat::YCbCr))
+    ));
+}
@@ -0,0 +1,68 @@
+// 逐行解码的结果必须和一次解码整幅图像完全相同
+use my_tiny_jpeg_decoder::{
+    decode::{scanline::ScanlineDecoder, OutputFormat},
+    decode_jpeg, open_jpeg, JpegErrorType,
+};
+
+fn read_all(path: &str, format: OutputFormat, lines: usize) -> Vec<u8> {
+    let (mut reader, header) = open_jpeg(path).unwrap();
+    let mut decoder = ScanlineDecoder::new(&mut reader, header, format).unwrap();
+    let mut buf = vec![0; decoder.bytes_per_line() * lines];
+    let mut pixels = Vec::new();
+    loop {
+        let n = decoder.read_scanlines(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        assert!(n <= lines);
+        pixels.extend_from_slice(&buf[..n * decoder.bytes_per_line()]);
+    }
+    assert_eq!(decoder.output_line(), decoder.height());
+    pixels
+}
+
+#[test]
+fn test_scanlines() {
+    for name in [
+        "baseline_444",
+        "baseline_420",
+        "baseline_440",
+        "grayscale_odd",
+        "odd_420",
+        "odd_422",
+        "restart_420",
+    ] {
+        let path = format!("tests/data/{}.jpg", name);
+        for format in [OutputFormat::Rgba8, OutputFormat::Rgb8, OutputFormat::Gray8] {
+            let (_, _, expected) = decode_jpeg(path.clone(), format).unwrap();
+            // 每次读取的行数和 MCU 高度不对齐
+            for lines in [1, 5, 16, 100] {
+                assert_eq!(
+                    read_all(&path, format, lines),
+                    expected,
+                    "{} {}",
+                    name,
+                    lines
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_invalid_arguments() {
+    let (mut reader, header) = open_jpeg("tests/data/baseline_420.jpg").unwrap();
+    assert!(matches!(
+        ScanlineDecoder::new(&mut reader, header, OutputFormat::YCbCr),
+        Err(JpegErrorType::UnsupportedFormat(OutputFormat::YCbCr))
+    ));
+
+    let (mut reader, header) = open_jpeg("tests/data/baseline_420.jpg").unwrap();
+    let mut decoder = ScanlineDecoder::new(&mut reader, header, OutputFormat::Rgb8).unwrap();
+    let mut buf = vec![0; decoder.bytes_per_line() - 1];
+    assert!(matches!(
+        decoder.read_scanlines(&mut buf),
+        Err(JpegErrorType::IOError(_))
+    ));
+    assert_eq!(decoder.output_line(), 0);
+}
@@ -0,0 +1,132 @@
+// 无损变换后再解码，结果必须和直接变换解码后的像素一致
+use std::{fs::File, io::BufReader};
+
+use my_tiny_jpeg_decoder::{
+    application::exif::Exif,
+    decode::OutputFormat,
+    decode_jpeg,
+    export::pixels::transform_pixels,
+    open_jpeg,
+    segment::{
+        rewrite::{rewrite, SegmentEdit},
+        Segment, SegmentType,
+    },
+    transform::{Transform, TransformErrorType},
+    transform_jpeg,
+};
+
+const TRANSFORMS: [Transform; 8] = [
+    Transform::FlipHorizontal,
+    Transform::FlipVertical,
+    Transform::Transpose,
+    Transform::Transverse,
+    Transform::Rotate90,
+    Transform::Rotate180,
+    Transform::Rotate270,
+    Transform::Crop {
+        x: 16,
+        y: 16,
+        width: 24,
+        height: 16,
+    },
+];
+
+fn output(name: &str) -> String {
+    let dir = std::env::temp_dir().join("my-tiny-jpeg-decoder-transform");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name).display().to_string()
+}
+
+#[test]
+fn test_round_trip() {
+    // 尺寸都是 MCU 的整数倍，不会被裁掉边缘
+    for name in ["baseline_420", "baseline_444", "restart_420", "grayscale"] {
+        let input = format!("tests/data/{}.jpg", name);
+        let (width, height, pixels) = decode_jpeg(input.clone(), OutputFormat::Rgb8).unwrap();
+        for (i, t) in TRANSFORMS.into_iter().enumerate() {
+            let path = output(&format!("{}_{}.jpg", name, i));
+            transform_jpeg(input.clone(), path.clone(), t).unwrap();
+            let actual = decode_jpeg(path, OutputFormat::Rgb8).unwrap();
+            let expected = transform_pixels(width, height, 3, &pixels, t);
+            assert_eq!(
+                (actual.0, actual.1),
+                (expected.0, expected.1),
+                "{} {:?}",
+                name,
+                t
+            );
+            // IDCT 的浮点舍入可能差 1
+            let max_diff = actual
+                .2
+                .iter()
+                .zip(&expected.2)
+                .map(|(&a, &b)| a.abs_diff(b))
+                .max()
+                .unwrap();
+            assert!(max_diff <= 1, "{} {:?}: max diff {}", name, t, max_diff);
+        }
+    }
+}
+
+#[test]
+fn test_orientation_reset() {
+    // 加上方向标签 6 的输入文件
+    let file = File::open("tests/data/baseline_420.jpg").unwrap();
+    let exif = Segment::with_data(SegmentType::APPn(1), Exif::orientation_only(6)).unwrap();
+    let mut data = Vec::new();
+    rewrite(
+        &mut BufReader::new(file),
+        &mut data,
+        &[SegmentEdit::Insert(exif)],
+    )
+    .unwrap();
+    let input = output("orientation_input.jpg");
+    std::fs::write(&input, data).unwrap();
+
+    // 旋转后方向标签改为 1，裁剪后保持不变
+    let crop = Transform::Crop {
+        x: 0,
+        y: 0,
+        width: 16,
+        height: 16,
+    };
+    for (t, expected) in [
+        (Transform::Rotate90, 1),
+        (Transform::Transpose, 1),
+        (crop, 6),
+    ] {
+        let path = output("orientation.jpg");
+        transform_jpeg(input.clone(), path.clone(), t).unwrap();
+        let (_, header) = open_jpeg(&path).unwrap();
+        assert_eq!(header.orientation(), Some(expected), "{:?}", t);
+    }
+}
+
+#[test]
+fn test_errors() {
+    let result = transform_jpeg(
+        "tests/data/missing.jpg".to_string(),
+        output("missing.jpg"),
+        Transform::Rotate90,
+    );
+    assert!(matches!(result, Err(TransformErrorType::Decode(_))));
+
+    // 扫描数据被截断
+    let data = std::fs::read("tests/data/baseline_420.jpg").unwrap();
+    let truncated = output("truncated_input.jpg");
+    std::fs::write(&truncated, &data[..data.len() / 2]).unwrap();
+    let result = transform_jpeg(truncated, output("truncated.jpg"), Transform::Rotate90);
+    assert!(matches!(result, Err(TransformErrorType::Decode(_))));
+
+    let result = transform_jpeg(
+        "tests/data/baseline_420.jpg".to_string(),
+        output("crop.jpg"),
+        Transform::Crop {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        },
+    );
+    assert!(matches!(result, Err(TransformErrorType::InvalidCrop)));
+}